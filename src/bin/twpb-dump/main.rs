@@ -0,0 +1,347 @@
+// Prints the fields of an encoded message, for looking at captured frames.
+//
+//   twpb-dump [FILE]                          the raw field tree, like `protoc --decode_raw`
+//   twpb-dump -p FILE.proto -m NAME [FILE]    fields by name and type, from the schema
+//
+// Without FILE (or with `-`) the message is read from stdin as hex, e.g. `echo 08 96 01 | twpb-dump`.
+// Every field is printed with its offset in the input, in hex.
+// Malformed input exits with status 1 and the offset where decoding failed,
+// bad arguments or schemas with status 2.
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use twpb::codec::{Bool, Double, Fixed32, Fixed64, Float, Int32, Int64, SFixed32, SFixed64,
+    SInt32, SInt64, UInt32, UInt64};
+use twpb::decoder::{DecodeError, RawFieldIter, RawValue};
+use twpb::text::TextCodec;
+use twpb::{wire_types, Writer, WriterError};
+
+mod schema;
+
+use schema::{Schema, Type};
+
+const USAGE: &str = "usage: twpb-dump [-I DIR]... [-p FILE.proto -m MESSAGE] [FILE]
+
+Prints the fields of a protobuf message, read from FILE or as hex from stdin.
+
+  -p, --proto FILE.proto   schema of the message, its imports are looked up in the -I directories
+  -m, --message MESSAGE    the message in the schema, e.g. api.v1.Request
+  -I, --include DIR        directory with imported .proto files, the directory of --proto by default";
+
+#[derive(Default)]
+struct Options {
+    input: Option<PathBuf>,
+    proto: Option<PathBuf>,
+    message: Option<String>,
+    includes: Vec<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "-p" | "--proto" => options.proto = Some(value(&arg)?.into()),
+            "-m" | "--message" => options.message = Some(value(&arg)?),
+            "-I" | "--include" => options.includes.push(value(&arg)?.into()),
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option '{}'", arg)),
+            _ if options.input.is_some() => return Err("only one input file can be given".to_string()),
+            _ => options.input = Some(arg.into()),
+        }
+    }
+    if options.proto.is_some() != options.message.is_some() {
+        return Err("--proto and --message go together".to_string());
+    }
+    Ok(options)
+}
+
+// Hex digits, whitespace between them is ignored.
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<u32> = text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).ok_or_else(|| format!("'{}' is not a hex digit", c)))
+        .collect::<Result<_, _>>()?;
+    if !digits.len().is_multiple_of(2) {
+        return Err("odd number of hex digits".to_string());
+    }
+    Ok(digits.chunks(2).map(|pair| (pair[0] << 4 | pair[1]) as u8).collect())
+}
+
+fn read_input(input: &Option<PathBuf>) -> Result<Vec<u8>, String> {
+    match input {
+        Some(path) if path.as_os_str() != "-" => std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e)),
+        _ => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text).map_err(|e| format!("stdin: {}", e))?;
+            parse_hex(&text)
+        },
+    }
+}
+
+// Where and why decoding stopped.
+struct Failure {
+    offset: usize,
+    error: DecodeError,
+}
+
+// Collects the printed lines, so they can be shown up to the point of a failure.
+struct Printer {
+    output: String,
+}
+
+impl Printer {
+    fn line(&mut self, offset: Option<usize>, depth: usize, text: &str) {
+        match offset {
+            Some(offset) => self.output += &format!("{:04x}  ", offset),
+            None => self.output += "      ",
+        }
+        self.output += &"  ".repeat(depth);
+        self.output += text;
+        self.output += "\n";
+    }
+
+    // `name {`, the fields of `bytes` and `}`, or `name {}` without fields.
+    fn message(&mut self, offset: usize, depth: usize, name: &str, bytes: &[u8],
+        fields: impl FnOnce(&mut Self) -> Result<(), Failure>) -> Result<(), Failure> {
+        if bytes.is_empty() {
+            self.line(Some(offset), depth, &format!("{} {{}}", name));
+            return Ok(());
+        }
+        self.line(Some(offset), depth, &format!("{} {{", name));
+        fields(self)?;
+        self.line(None, depth, "}");
+        Ok(())
+    }
+}
+
+// Text format output, to escape strings and bytes the same way.
+struct Text(Vec<u8>);
+
+impl Writer for Text {
+    fn write(&mut self, byte: u8) -> Result<(), WriterError> {
+        self.0.push(byte);
+        Ok(())
+    }
+}
+
+fn text(encode: impl FnOnce(&mut Text) -> Result<usize, WriterError>) -> String {
+    let mut text = Text(Vec::new());
+    // writing to a Vec can't fail
+    encode(&mut text).unwrap();
+    String::from_utf8(text.0).unwrap()
+}
+
+// A string if it is one, bytes otherwise.
+fn quoted(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => text(|t| twpb::text::string(t, s)),
+        Err(_) => text(|t| twpb::text::bytes(t, bytes)),
+    }
+}
+
+// Walks the fields in `buffer`, which starts at `base` in the input.
+fn fields<'a>(buffer: &'a [u8], base: usize,
+    mut field: impl FnMut(usize, u32, RawValue<'a>, usize) -> Result<(), Failure>) -> Result<(), Failure> {
+    let mut iter = RawFieldIter::new(buffer);
+    loop {
+        let offset = base + iter.position();
+        match iter.next() {
+            None => return Ok(()),
+            Some(Err(error)) => return Err(Failure { offset, error }),
+            Some(Ok((number, value))) => field(offset, number, value, base + iter.position())?,
+        }
+    }
+}
+
+fn print_raw(printer: &mut Printer, buffer: &[u8], base: usize, depth: usize) -> Result<(), Failure> {
+    fields(buffer, base, |offset, number, value, end| print_raw_field(printer, offset, depth, &number.to_string(), value, end))
+}
+
+fn print_raw_field(printer: &mut Printer, offset: usize, depth: usize, name: &str, value: RawValue, end: usize) -> Result<(), Failure> {
+    let text = match value {
+        RawValue::Varint(v) => v.to_string(),
+        RawValue::Fixed32(v) => format!("0x{:08x}", v),
+        RawValue::Fixed64(v) => format!("0x{:016x}", v),
+        RawValue::LengthDelimited(bytes) => {
+            if value.as_message().is_some() {
+                return printer.message(offset, depth, name, bytes, |printer| print_raw(printer, bytes, end - bytes.len(), depth + 1));
+            }
+            quoted(bytes)
+        },
+    };
+    printer.line(Some(offset), depth, &format!("{}: {}", name, text));
+    Ok(())
+}
+
+// The wire type a scalar is encoded with, outside of packed fields.
+fn wire_type(scalar: &str) -> u8 {
+    match scalar {
+        "fixed32" | "sfixed32" | "float" => wire_types::B32,
+        "fixed64" | "sfixed64" | "double" => wire_types::B64,
+        "string" | "bytes" => wire_types::LENGTHDELIMITED,
+        _ => wire_types::VARINT,
+    }
+}
+
+fn raw_wire_type(value: &RawValue) -> u8 {
+    match value {
+        RawValue::Varint(_) => wire_types::VARINT,
+        RawValue::Fixed32(_) => wire_types::B32,
+        RawValue::Fixed64(_) => wire_types::B64,
+        RawValue::LengthDelimited(_) => wire_types::LENGTHDELIMITED,
+    }
+}
+
+// A scalar in text format, the wire type has been checked.
+fn scalar(type_name: &str, value: RawValue) -> String {
+    let (v32, v64) = match value {
+        RawValue::Varint(v) | RawValue::Fixed64(v) => (v as u32, v),
+        RawValue::Fixed32(v) => (v, v as u64),
+        RawValue::LengthDelimited(bytes) => return match type_name {
+            "bytes" => text(|t| twpb::text::bytes(t, bytes)),
+            _ => quoted(bytes),
+        },
+    };
+    text(|t| match type_name {
+        "int32" => <Int32 as TextCodec<i32>>::encode_text(t, &(v64 as i32)),
+        "int64" => <Int64 as TextCodec<i64>>::encode_text(t, &(v64 as i64)),
+        "uint32" => <UInt32 as TextCodec<u32>>::encode_text(t, &v32),
+        "uint64" => <UInt64 as TextCodec<u64>>::encode_text(t, &v64),
+        "sint32" => <SInt32 as TextCodec<i32>>::encode_text(t, &((v32 >> 1) as i32 ^ -((v32 & 1) as i32))),
+        "sint64" => <SInt64 as TextCodec<i64>>::encode_text(t, &((v64 >> 1) as i64 ^ -((v64 & 1) as i64))),
+        "fixed32" => <Fixed32 as TextCodec<u32>>::encode_text(t, &v32),
+        "fixed64" => <Fixed64 as TextCodec<u64>>::encode_text(t, &v64),
+        "sfixed32" => <SFixed32 as TextCodec<i32>>::encode_text(t, &(v32 as i32)),
+        "sfixed64" => <SFixed64 as TextCodec<i64>>::encode_text(t, &(v64 as i64)),
+        "float" => <Float as TextCodec<f32>>::encode_text(t, &f32::from_bits(v32)),
+        "double" => <Double as TextCodec<f64>>::encode_text(t, &f64::from_bits(v64)),
+        "bool" => <Bool as TextCodec<bool>>::encode_text(t, &(v64 != 0)),
+        _ => unreachable!("not a scalar type: {}", type_name),
+    })
+}
+
+// An enum by name, or by number if the schema doesn't know it.
+fn enum_value(names: &[(String, i32)], value: RawValue) -> String {
+    let value = match value {
+        RawValue::Varint(v) => v as i32,
+        _ => unreachable!("enums are varints"),
+    };
+    match names.iter().find(|(_, v)| *v == value) {
+        Some((name, _)) => name.clone(),
+        None => value.to_string(),
+    }
+}
+
+// Splits a packed repeated field into its values.
+fn unpack<'a>(type_name: &str, bytes: &'a [u8], base: usize) -> Result<Vec<RawValue<'a>>, Failure> {
+    let mut values = Vec::new();
+    let mut rest = bytes.iter();
+    while !rest.as_slice().is_empty() {
+        let offset = base + bytes.len() - rest.as_slice().len();
+        let failure = |error| Failure { offset, error };
+        let value = match wire_type(type_name) {
+            wire_types::B32 => {
+                let bytes = rest.as_slice().get(..4).ok_or(failure(DecodeError::UnexpectedEndOfBuffer))?;
+                rest.nth(3);
+                RawValue::Fixed32(u32::from_le_bytes(bytes.try_into().unwrap()))
+            },
+            wire_types::B64 => {
+                let bytes = rest.as_slice().get(..8).ok_or(failure(DecodeError::UnexpectedEndOfBuffer))?;
+                rest.nth(7);
+                RawValue::Fixed64(u64::from_le_bytes(bytes.try_into().unwrap()))
+            },
+            _ => RawValue::Varint(twpb::decoder::leb128(rest.by_ref().copied()).map_err(failure)?),
+        };
+        values.push(value);
+    }
+    Ok(values)
+}
+
+fn print_message(printer: &mut Printer, schema: &Schema, message: &str, buffer: &[u8], base: usize, depth: usize) -> Result<(), Failure> {
+    let Some(Type::Message(message_fields)) = schema.types.get(message) else { unreachable!() };
+    fields(buffer, base, |offset, number, value, end| {
+        // unknown fields and fields of the wrong wire type are printed raw, by number
+        let Some(field) = message_fields.iter().find(|f| f.number == number) else {
+            return print_raw_field(printer, offset, depth, &number.to_string(), value, end);
+        };
+        let name = &field.name;
+        match (schema.types.get(&field.type_name), value) {
+            (Some(Type::Message(_)), RawValue::LengthDelimited(bytes)) => {
+                printer.message(offset, depth, name, bytes,
+                    |printer| print_message(printer, schema, &field.type_name, bytes, end - bytes.len(), depth + 1))
+            },
+            (Some(Type::Enum(names)), RawValue::Varint(_)) => {
+                printer.line(Some(offset), depth, &format!("{}: {}", name, enum_value(names, value)));
+                Ok(())
+            },
+            (Some(Type::Enum(names)), RawValue::LengthDelimited(bytes)) if field.repeated => {
+                let values = unpack("int32", bytes, end - bytes.len())?;
+                let values: Vec<_> = values.into_iter().map(|v| enum_value(names, v)).collect();
+                printer.line(Some(offset), depth, &format!("{}: [{}]", name, values.join(", ")));
+                Ok(())
+            },
+            (None, value) if raw_wire_type(&value) == wire_type(&field.type_name) => {
+                printer.line(Some(offset), depth, &format!("{}: {}", name, scalar(&field.type_name, value)));
+                Ok(())
+            },
+            (None, RawValue::LengthDelimited(bytes)) if field.repeated => {
+                let values = unpack(&field.type_name, bytes, end - bytes.len())?;
+                let values: Vec<_> = values.into_iter().map(|v| scalar(&field.type_name, v)).collect();
+                printer.line(Some(offset), depth, &format!("{}: [{}]", name, values.join(", ")));
+                Ok(())
+            },
+            _ => print_raw_field(printer, offset, depth, &number.to_string(), value, end),
+        }
+    })
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            if !e.is_empty() {
+                eprintln!("twpb-dump: {}", e);
+            }
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        },
+    };
+    let schema = match &options.proto {
+        Some(proto) => {
+            let mut includes = options.includes.clone();
+            includes.push(proto.parent().map(PathBuf::from).unwrap_or_default());
+            let message = options.message.as_deref().unwrap();
+            match Schema::load(proto, &includes).and_then(|schema| schema.find_message(message).map(|m| (schema, m))) {
+                Ok(schema) => Some(schema),
+                Err(e) => {
+                    eprintln!("twpb-dump: {}", e);
+                    return ExitCode::from(2);
+                },
+            }
+        },
+        None => None,
+    };
+    let input = match read_input(&options.input) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("twpb-dump: {}", e);
+            return ExitCode::from(2);
+        },
+    };
+
+    let mut printer = Printer { output: String::new() };
+    let result = match &schema {
+        Some((schema, message)) => print_message(&mut printer, schema, message, &input, 0, 0),
+        None => print_raw(&mut printer, &input, 0, 0),
+    };
+    print!("{}", printer.output);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure { offset, error }) => {
+            eprintln!("twpb-dump: malformed input at offset 0x{:04x}: {:?}", offset, error);
+            ExitCode::FAILURE
+        },
+    }
+}
@@ -0,0 +1,471 @@
+// Just enough of a .proto parser to print messages by field name:
+// messages, enums, oneofs and maps, with their package and imports.
+// Options, services, extensions and reserved ranges are read and ignored.
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+pub struct Field {
+    pub name: String,
+    pub number: u32,
+    pub repeated: bool,
+    // a scalar type like "sint32", or the full name of a message or enum
+    pub type_name: String,
+}
+
+pub enum Type {
+    Message(Vec<Field>),
+    Enum(Vec<(String, i32)>),
+}
+
+#[derive(Default)]
+pub struct Schema {
+    // by full name without leading dot, e.g. "api.v1.Request.EmptyRequest"
+    pub types: HashMap<String, Type>,
+}
+
+const SCALARS: &[&str] = &["double", "float", "int32", "int64", "uint32", "uint64", "sint32", "sint64",
+    "fixed32", "fixed64", "sfixed32", "sfixed64", "bool", "string", "bytes"];
+
+pub fn is_scalar(type_name: &str) -> bool {
+    SCALARS.contains(&type_name)
+}
+
+impl Schema {
+    // Reads `path` and everything it imports, imports are looked up in `includes`.
+    pub fn load(path: &Path, includes: &[PathBuf]) -> Result<Schema, String> {
+        let mut schema = Schema::default();
+        let mut loaded = HashSet::new();
+        // fields with the type name as written, and the scope to resolve it in
+        let mut unresolved = Vec::new();
+        schema.load_file(path, includes, &mut loaded, &mut unresolved)?;
+
+        for (message, index, scope) in unresolved {
+            let Some(Type::Message(fields)) = schema.types.get(&message) else { unreachable!() };
+            let field = &fields[index];
+            let full_name = schema.resolve(&field.type_name, &scope)
+                .ok_or_else(|| format!("unknown type '{}' of field '{}.{}'", field.type_name, message, field.name))?;
+            if let Some(Type::Message(fields)) = schema.types.get_mut(&message) {
+                fields[index].type_name = full_name;
+            }
+        }
+        Ok(schema)
+    }
+
+    fn load_file(&mut self, path: &Path, includes: &[PathBuf], loaded: &mut HashSet<PathBuf>,
+        unresolved: &mut Vec<(String, usize, String)>) -> Result<(), String> {
+        let canonical = path.canonicalize().map_err(|e| format!("{}: {}", path.display(), e))?;
+        if !loaded.insert(canonical) {
+            return Ok(());
+        }
+        let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut parser = Parser {
+            tokens: tokenize(&source).map_err(|line| format!("{}:{}: invalid token", path.display(), line))?,
+            position: 0,
+            package: String::new(),
+            imports: Vec::new(),
+        };
+        let mut types = Vec::new();
+        parser.file(&mut types).map_err(|(line, e)| format!("{}:{}: {}", path.display(), line, e))?;
+
+        for (full_name, scope, ty) in types {
+            if let Type::Message(fields) = &ty {
+                for (index, field) in fields.iter().enumerate() {
+                    if !is_scalar(&field.type_name) {
+                        unresolved.push((full_name.clone(), index, scope.clone()));
+                    }
+                }
+            }
+            if self.types.insert(full_name.clone(), ty).is_some() {
+                return Err(format!("{}: '{}' is defined twice", path.display(), full_name));
+            }
+        }
+        for import in std::mem::take(&mut parser.imports) {
+            let found = includes.iter()
+                .map(|dir| dir.join(&import))
+                .find(|candidate| candidate.is_file())
+                .ok_or_else(|| format!("{}: import '{}' not found, add its directory with -I", path.display(), import))?;
+            self.load_file(&found, includes, loaded, unresolved)?;
+        }
+        Ok(())
+    }
+
+    // Protobuf scoping: `Name` used in `pkg.Outer` is looked for as `pkg.Outer.Name`, `pkg.Name`, `Name`.
+    fn resolve(&self, type_name: &str, scope: &str) -> Option<String> {
+        if let Some(absolute) = type_name.strip_prefix('.') {
+            return self.types.contains_key(absolute).then(|| absolute.to_string());
+        }
+        let mut scope = scope;
+        loop {
+            let candidate = if scope.is_empty() { type_name.to_string() } else { format!("{}.{}", scope, type_name) };
+            if self.types.contains_key(&candidate) {
+                return Some(candidate);
+            }
+            if scope.is_empty() {
+                return None;
+            }
+            scope = scope.rfind('.').map(|i| &scope[..i]).unwrap_or("");
+        }
+    }
+
+    // A message by full name, or by a suffix of it if that is unique, e.g. "Request" for "api.v1.Request".
+    pub fn find_message(&self, name: &str) -> Result<String, String> {
+        let name = name.strip_prefix('.').unwrap_or(name);
+        if let Some(Type::Message(_)) = self.types.get(name) {
+            return Ok(name.to_string());
+        }
+        let suffix = format!(".{}", name);
+        let mut matches: Vec<_> = self.types.iter()
+            .filter(|(full_name, ty)| matches!(ty, Type::Message(_)) && full_name.ends_with(&suffix))
+            .map(|(full_name, _)| full_name.clone())
+            .collect();
+        match matches.len() {
+            1 => Ok(matches.remove(0)),
+            0 => Err(format!("no message '{}' in the schema", name)),
+            _ => {
+                matches.sort();
+                Err(format!("'{}' is ambiguous, it could be {}", name, matches.join(", ")))
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    // identifiers and dotted names, e.g. `api.v1.Request`
+    Ident(String),
+    Int(i64),
+    Str(String),
+    Symbol(char),
+}
+
+// Returns the line of the first invalid token on error.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, usize> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => (),
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            },
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            last = c;
+                        },
+                        None => return Err(line),
+                    }
+                }
+            },
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some('\\') => value.push(chars.next().ok_or(line)?),
+                        Some('\n') | None => return Err(line),
+                        Some(c) => value.push(c),
+                    }
+                }
+                tokens.push((Token::Str(value), line));
+            },
+            c if c.is_ascii_digit() => {
+                let mut text = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '.') {
+                    text.push(c);
+                }
+                let value = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                    i64::from_str_radix(hex, 16)
+                } else if text.len() > 1 && text.starts_with('0') {
+                    i64::from_str_radix(&text[1..], 8)
+                } else {
+                    text.parse()
+                };
+                // floats only appear in options, which are skipped
+                tokens.push((value.map(Token::Int).unwrap_or(Token::Ident(text)), line));
+            },
+            c if c.is_alphabetic() || c == '_' || c == '.' => {
+                let mut text = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '.') {
+                    text.push(c);
+                }
+                tokens.push((Token::Ident(text), line));
+            },
+            '{' | '}' | '[' | ']' | '(' | ')' | '<' | '>' | '=' | ';' | ',' | ':' | '-' | '+' => {
+                tokens.push((Token::Symbol(c), line));
+            },
+            _ => return Err(line),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    package: String,
+    imports: Vec<String>,
+}
+
+// The line and what's wrong there.
+type ParseError = (usize, String);
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        let line = self.tokens.get(self.position).or(self.tokens.last()).map(|(_, line)| *line).unwrap_or(1);
+        (line, message.to_string())
+    }
+
+    fn next(&mut self) -> Result<&Token, ParseError> {
+        let token = self.tokens.get(self.position).map(|(token, _)| token).ok_or_else(|| self.error("unexpected end of file"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn symbol(&mut self, symbol: char) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(Token::Symbol(c)) if *c == symbol => {
+                self.position += 1;
+                Ok(())
+            },
+            _ => Err(self.error(&format!("expected '{}'", symbol))),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => {
+                let ident = ident.clone();
+                self.position += 1;
+                Ok(ident)
+            },
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        match self.next()? {
+            Token::Str(s) => Ok(s.clone()),
+            _ => Err(self.error("expected a string")),
+        }
+    }
+
+    fn int(&mut self) -> Result<i64, ParseError> {
+        let negative = self.peek() == Some(&Token::Symbol('-'));
+        if negative {
+            self.position += 1;
+        }
+        match self.next()? {
+            Token::Int(value) => Ok(if negative { -value } else { *value }),
+            _ => Err(self.error("expected a number")),
+        }
+    }
+
+    // Skips up to and including the `;` ending a statement, or a `{..}` block.
+    fn skip_statement(&mut self) -> Result<(), ParseError> {
+        let mut depth = 0;
+        loop {
+            match self.next()? {
+                Token::Symbol(';') if depth == 0 => return Ok(()),
+                Token::Symbol('{') => depth += 1,
+                Token::Symbol('}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+
+    // Field options like `[packed = true]`, the wire format tells everything needed.
+    fn skip_options(&mut self) -> Result<(), ParseError> {
+        if self.peek() == Some(&Token::Symbol('[')) {
+            while self.next()? != &Token::Symbol(']') {}
+        }
+        Ok(())
+    }
+
+    fn file(&mut self, types: &mut Vec<(String, String, Type)>) -> Result<(), ParseError> {
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Ident(keyword) if keyword == "package" => {
+                    self.position += 1;
+                    self.package = self.ident()?;
+                    self.symbol(';')?;
+                },
+                Token::Ident(keyword) if keyword == "import" => {
+                    self.position += 1;
+                    if let Some(Token::Ident(_)) = self.peek() {
+                        // public or weak
+                        self.position += 1;
+                    }
+                    let import = self.string()?;
+                    self.imports.push(import);
+                    self.symbol(';')?;
+                },
+                Token::Ident(keyword) if keyword == "message" => {
+                    let scope = self.package.clone();
+                    self.message(&scope, types)?;
+                },
+                Token::Ident(keyword) if keyword == "enum" => {
+                    let scope = self.package.clone();
+                    self.enumeration(&scope, types)?;
+                },
+                Token::Symbol(';') => self.position += 1,
+                // syntax, option, service, extend
+                _ => self.skip_statement()?,
+            }
+        }
+        Ok(())
+    }
+
+    fn message(&mut self, scope: &str, types: &mut Vec<(String, String, Type)>) -> Result<(), ParseError> {
+        self.position += 1;
+        let full_name = join(scope, &self.ident()?);
+        self.symbol('{')?;
+        let mut fields = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Symbol('}')) => {
+                    self.position += 1;
+                    break;
+                },
+                Some(Token::Symbol(';')) => self.position += 1,
+                Some(Token::Ident(keyword)) => match keyword.as_str() {
+                    "message" => self.message(&full_name, types)?,
+                    "enum" => self.enumeration(&full_name, types)?,
+                    "oneof" => {
+                        self.position += 1;
+                        self.ident()?;
+                        self.symbol('{')?;
+                        while self.peek() != Some(&Token::Symbol('}')) {
+                            match self.peek() {
+                                Some(Token::Ident(keyword)) if keyword == "option" => self.skip_statement()?,
+                                Some(Token::Symbol(';')) => self.position += 1,
+                                _ => fields.push(self.field(false)?),
+                            }
+                        }
+                        self.position += 1;
+                    },
+                    "map" => {
+                        // a repeated message with the key as field 1 and the value as field 2
+                        self.position += 1;
+                        self.symbol('<')?;
+                        let key = self.ident()?;
+                        self.symbol(',')?;
+                        let value = self.ident()?;
+                        self.symbol('>')?;
+                        let mut field = self.field_rest(true, String::new())?;
+                        let entry = format!("{}Entry", camel_case(&field.name));
+                        types.push((join(&full_name, &entry), full_name.clone(), Type::Message(vec![
+                            Field { name: "key".to_string(), number: 1, repeated: false, type_name: key },
+                            Field { name: "value".to_string(), number: 2, repeated: false, type_name: value },
+                        ])));
+                        field.type_name = entry;
+                        fields.push(field);
+                    },
+                    "option" | "reserved" | "extensions" | "extend" => self.skip_statement()?,
+                    "repeated" => {
+                        self.position += 1;
+                        fields.push(self.field(true)?);
+                    },
+                    "optional" | "required" => {
+                        self.position += 1;
+                        fields.push(self.field(false)?);
+                    },
+                    _ => fields.push(self.field(false)?),
+                },
+                _ => return Err(self.error("expected a field")),
+            }
+        }
+        types.push((full_name.clone(), full_name, Type::Message(fields)));
+        Ok(())
+    }
+
+    // `type name = number [options];`
+    fn field(&mut self, repeated: bool) -> Result<Field, ParseError> {
+        let type_name = self.ident()?;
+        if type_name == "group" {
+            return Err(self.error("groups are not supported"));
+        }
+        self.field_rest(repeated, type_name)
+    }
+
+    fn field_rest(&mut self, repeated: bool, type_name: String) -> Result<Field, ParseError> {
+        let name = self.ident()?;
+        self.symbol('=')?;
+        let number = self.int()?;
+        let number = u32::try_from(number).map_err(|_| self.error("invalid field number"))?;
+        self.skip_options()?;
+        self.symbol(';')?;
+        Ok(Field { name, number, repeated, type_name })
+    }
+
+    fn enumeration(&mut self, scope: &str, types: &mut Vec<(String, String, Type)>) -> Result<(), ParseError> {
+        self.position += 1;
+        let full_name = join(scope, &self.ident()?);
+        self.symbol('{')?;
+        let mut values = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Symbol('}')) => {
+                    self.position += 1;
+                    break;
+                },
+                Some(Token::Symbol(';')) => self.position += 1,
+                Some(Token::Ident(keyword)) if keyword == "option" || keyword == "reserved" => self.skip_statement()?,
+                _ => {
+                    let name = self.ident()?;
+                    self.symbol('=')?;
+                    let value = self.int()?;
+                    let value = i32::try_from(value).map_err(|_| self.error("enum value out of range"))?;
+                    self.skip_options()?;
+                    self.symbol(';')?;
+                    values.push((name, value));
+                },
+            }
+        }
+        types.push((full_name.clone(), full_name, Type::Enum(values)));
+        Ok(())
+    }
+}
+
+fn join(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+// `map_field` becomes `MapField`, as protoc names map entries.
+fn camel_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut upper = true;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            result.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
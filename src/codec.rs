@@ -0,0 +1,284 @@
+// Typed codecs for protobuf scalar fields.
+//
+// Every proto scalar type has a marker type here (`Int32`, `SInt64`, `Fixed32`, ...).
+// A marker implements `ScalarCodec<T>` for each Rust type `T` it can be stored in,
+// which is how the derive macros find the wire type and (de)serializer of a field.
+// Downstream crates can add their own encodings by implementing `ScalarCodec`
+// on their own marker types and pointing a field at it with `#[twpb(codec = "path")]`.
+use crate::decoder::{self, DecodeError};
+use crate::encoder;
+use crate::iterators::LimitedIterator;
+use crate::traits::{Writer, WriterError};
+use crate::wiretypes::wire_types;
+
+pub trait ScalarCodec<T> {
+    // One of the constants in `wire_types`.
+    const WIRE_TYPE: u8;
+
+    // Write the value without its tag, returns the amount of bytes written.
+    fn encode(buffer: &mut impl Writer, value: &T) -> Result<usize, WriterError>;
+
+    // Read a value (without its tag) from the byte stream.
+    fn decode<I>(bytes: I, field_name: &'static str) -> Result<T, DecodeError>
+    where I: Iterator<Item = u8>;
+
+    // The amount of bytes `encode` would write for this value.
+    fn encoded_len(value: &T) -> usize {
+        let mut nullbuffer = crate::iterators::NullCounterBuffer::new();
+        Self::encode(&mut nullbuffer, value).unwrap_or(0)
+    }
+}
+
+// Write a tag followed by the value.
+pub fn encode_field<C, T>(buffer: &mut impl Writer, field_number: u32, value: &T) -> Result<usize, WriterError>
+where C: ScalarCodec<T> {
+    let mut bytes_written = encoder::tag(buffer, &field_number, &C::WIRE_TYPE)?;
+    bytes_written += C::encode(buffer, value)?;
+    Ok(bytes_written)
+}
+
+// Same as `encode_field`, but for non-repeated fields.
+// Empty length delimited values (strings, bytes, ...) are left out of the message.
+pub fn encode_singular<C, T>(buffer: &mut impl Writer, field_number: u32, value: &T) -> Result<usize, WriterError>
+where C: ScalarCodec<T> {
+    // only a length byte would be written, content is empty
+    if C::WIRE_TYPE == wire_types::LENGTHDELIMITED && C::encoded_len(value) == 1 {
+        return Ok(0);
+    }
+    encode_field::<C, T>(buffer, field_number, value)
+}
+
+// The amount of bytes encode_field would write.
+pub fn encoded_len_field<C, T>(field_number: u32, value: &T) -> usize
+where C: ScalarCodec<T> {
+    encoder::tag_len(&field_number) + C::encoded_len(value)
+}
+
+// The amount of bytes encode_singular would write.
+pub fn encoded_len_singular<C, T>(field_number: u32, value: &T) -> usize
+where C: ScalarCodec<T> {
+    let len = C::encoded_len(value);
+    if C::WIRE_TYPE == wire_types::LENGTHDELIMITED && len == 1 {
+        return 0;
+    }
+    encoder::tag_len(&field_number) + len
+}
+
+// Decode one occurrence of a repeated field, handing every value to `push`.
+// Repeated fields can be encoded in packed or non-packed mode, both are accepted.
+pub fn decode_repeated<C, T, I, F>(wire_type: u8, mut bytes: I, field_name: &'static str, mut push: F) -> Result<(), DecodeError>
+where C: ScalarCodec<T>, I: Iterator<Item = u8>, F: FnMut(T) -> Result<(), DecodeError> {
+    // packed repeated field
+    // length delimited types are never packed, because their non-repeated encoding
+    // is already the same as packed repeated encoding
+    if wire_type == wire_types::LENGTHDELIMITED && C::WIRE_TYPE != wire_types::LENGTHDELIMITED {
+        let bufsize = decoder::leb128_u32(&mut bytes)?;
+        let mut iterator = LimitedIterator::new(&mut bytes, bufsize);
+        loop {
+            match C::decode(&mut iterator, field_name) {
+                Ok(value) => push(value)?,
+                Err(DecodeError::EmptyBuffer) => break,
+                Err(e) => return Err(e),
+            };
+        }
+    // non-packed repeated field
+    } else {
+        push(C::decode(&mut bytes, field_name)?)?;
+    }
+    Ok(())
+}
+
+macro_rules! scalar_codec {
+    ($marker:ident, $rust_type:ty, $wire_type:expr, $fn:ident, |$v:ident| $len:expr) => {
+        impl ScalarCodec<$rust_type> for $marker {
+            const WIRE_TYPE: u8 = $wire_type;
+
+            fn encode(buffer: &mut impl Writer, value: &$rust_type) -> Result<usize, WriterError> {
+                encoder::$fn(buffer, value)
+            }
+
+            fn decode<I>(bytes: I, field_name: &'static str) -> Result<$rust_type, DecodeError>
+            where I: Iterator<Item = u8> {
+                decoder::$fn(bytes, field_name)
+            }
+
+            fn encoded_len($v: &$rust_type) -> usize {
+                $len
+            }
+        }
+    };
+}
+
+pub struct Int32;
+pub struct Int64;
+pub struct UInt32;
+pub struct UInt64;
+pub struct SInt32;
+pub struct SInt64;
+pub struct Fixed32;
+pub struct Fixed64;
+pub struct SFixed32;
+pub struct SFixed64;
+pub struct Float;
+pub struct Double;
+pub struct Bool;
+pub struct String;
+pub struct Bytes;
+
+// Negative int32 values are sign extended to 64 bits on the wire.
+scalar_codec!(Int32, i32, wire_types::VARINT, int32, |v| encoder::leb128_len(&(*v as i64 as u64)));
+scalar_codec!(Int64, i64, wire_types::VARINT, int64, |v| encoder::leb128_len(&(*v as u64)));
+scalar_codec!(UInt32, u32, wire_types::VARINT, uint32, |v| encoder::leb128_len(&(*v as u64)));
+scalar_codec!(UInt64, u64, wire_types::VARINT, uint64, |v| encoder::leb128_len(v));
+// ZigZag encoding, see encoder::sint32
+scalar_codec!(SInt32, i32, wire_types::VARINT, sint32, |v| encoder::leb128_len(&(((*v << 1) ^ (*v >> 31)) as u32 as u64)));
+scalar_codec!(SInt64, i64, wire_types::VARINT, sint64, |v| encoder::leb128_len(&(((*v << 1) ^ (*v >> 63)) as u64)));
+scalar_codec!(Fixed32, u32, wire_types::B32, fixed32, |_v| 4);
+scalar_codec!(Fixed64, u64, wire_types::B64, fixed64, |_v| 8);
+scalar_codec!(SFixed32, i32, wire_types::B32, sfixed32, |_v| 4);
+scalar_codec!(SFixed64, i64, wire_types::B64, sfixed64, |_v| 8);
+scalar_codec!(Float, f32, wire_types::B32, float, |_v| 4);
+scalar_codec!(Double, f64, wire_types::B64, double, |_v| 8);
+scalar_codec!(Bool, bool, wire_types::VARINT, bool, |_v| 1);
+
+// Narrower Rust integers for 32 bit proto types.
+// They are widened when encoding, and range checked when decoding.
+macro_rules! narrow_codec {
+    ($marker:ident, $wide_type:ty, $rust_type:ty) => {
+        impl ScalarCodec<$rust_type> for $marker {
+            const WIRE_TYPE: u8 = <$marker as ScalarCodec<$wide_type>>::WIRE_TYPE;
+
+            fn encode(buffer: &mut impl Writer, value: &$rust_type) -> Result<usize, WriterError> {
+                <$marker as ScalarCodec<$wide_type>>::encode(buffer, &(*value as $wide_type))
+            }
+
+            fn decode<I>(bytes: I, field_name: &'static str) -> Result<$rust_type, DecodeError>
+            where I: Iterator<Item = u8> {
+                let value = <$marker as ScalarCodec<$wide_type>>::decode(bytes, field_name)?;
+                <$rust_type>::try_from(value).map_err(|_| DecodeError::ValueOutOfRange(field_name))
+            }
+
+            fn encoded_len(value: &$rust_type) -> usize {
+                <$marker as ScalarCodec<$wide_type>>::encoded_len(&(*value as $wide_type))
+            }
+        }
+    };
+}
+
+narrow_codec!(UInt32, u32, u8);
+narrow_codec!(UInt32, u32, u16);
+narrow_codec!(Int32, i32, i8);
+narrow_codec!(Int32, i32, i16);
+narrow_codec!(SInt32, i32, i8);
+narrow_codec!(SInt32, i32, i16);
+
+// Proto enums are int32 on the wire. The Rust type is typically a fieldless enum
+// with `From<MyEnum> for i32` and `TryFrom<i32> for MyEnum` implemented.
+// Values unknown to the Rust type fail to decode.
+pub struct Enum;
+
+impl<T> ScalarCodec<T> for Enum
+where T: Copy + Into<i32> + TryFrom<i32> {
+    const WIRE_TYPE: u8 = wire_types::VARINT;
+
+    fn encode(buffer: &mut impl Writer, value: &T) -> Result<usize, WriterError> {
+        encoder::int32(buffer, &(*value).into())
+    }
+
+    fn decode<I>(bytes: I, field_name: &'static str) -> Result<T, DecodeError>
+    where I: Iterator<Item = u8> {
+        let value = decoder::int32(bytes, field_name)?;
+        T::try_from(value).map_err(|_| DecodeError::ValueOutOfRange(field_name))
+    }
+
+    fn encoded_len(value: &T) -> usize {
+        <Int32 as ScalarCodec<i32>>::encoded_len(&(*value).into())
+    }
+}
+
+impl<const SIZE: usize> ScalarCodec<heapless::String<SIZE>> for String {
+    const WIRE_TYPE: u8 = wire_types::LENGTHDELIMITED;
+
+    fn encode(buffer: &mut impl Writer, value: &heapless::String<SIZE>) -> Result<usize, WriterError> {
+        encoder::string(buffer, value)
+    }
+
+    fn decode<I>(bytes: I, field_name: &'static str) -> Result<heapless::String<SIZE>, DecodeError>
+    where I: Iterator<Item = u8> {
+        decoder::string(bytes, field_name)
+    }
+
+    fn encoded_len(value: &heapless::String<SIZE>) -> usize {
+        encoder::leb128_len(&(value.len() as u64)) + value.len()
+    }
+}
+
+impl<const SIZE: usize> ScalarCodec<heapless::Vec<u8, SIZE>> for Bytes {
+    const WIRE_TYPE: u8 = wire_types::LENGTHDELIMITED;
+
+    fn encode(buffer: &mut impl Writer, value: &heapless::Vec<u8, SIZE>) -> Result<usize, WriterError> {
+        encoder::bytes(buffer, value)
+    }
+
+    fn decode<I>(bytes: I, field_name: &'static str) -> Result<heapless::Vec<u8, SIZE>, DecodeError>
+    where I: Iterator<Item = u8> {
+        decoder::bytes(bytes, field_name)
+    }
+
+    fn encoded_len(value: &heapless::Vec<u8, SIZE>) -> usize {
+        encoder::leb128_len(&(value.len() as u64)) + value.len()
+    }
+}
+
+impl<const SIZE: usize> ScalarCodec<[u8; SIZE]> for Bytes {
+    const WIRE_TYPE: u8 = wire_types::LENGTHDELIMITED;
+
+    fn encode(buffer: &mut impl Writer, value: &[u8; SIZE]) -> Result<usize, WriterError> {
+        encoder::bytes_array(buffer, value)
+    }
+
+    fn decode<I>(bytes: I, field_name: &'static str) -> Result<[u8; SIZE], DecodeError>
+    where I: Iterator<Item = u8> {
+        decoder::bytes_array(bytes, field_name)
+    }
+
+    fn encoded_len(_value: &[u8; SIZE]) -> usize {
+        encoder::leb128_len(&(SIZE as u64)) + SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // encoded_len must agree with what encode actually writes
+    fn check_len<C: ScalarCodec<T>, T>(value: T) {
+        let mut buffer = [0u8; 20];
+        let written = C::encode(&mut buffer.as_mut(), &value).unwrap();
+        assert_eq!(C::encoded_len(&value), written);
+    }
+
+    #[test]
+    fn test_encoded_len() {
+        check_len::<Int32, _>(0);
+        check_len::<Int32, _>(-1);
+        check_len::<Int32, _>(i32::MAX);
+        check_len::<Int64, _>(i64::MIN);
+        check_len::<UInt32, _>(300u32);
+        check_len::<UInt64, _>(u64::MAX);
+        check_len::<SInt32, _>(-300);
+        check_len::<SInt32, _>(i32::MIN);
+        check_len::<SInt64, _>(i64::MAX);
+        check_len::<SInt64, _>(i64::MIN);
+        check_len::<Fixed32, _>(1);
+        check_len::<SFixed64, _>(-1);
+        check_len::<Double, _>(1.0);
+        check_len::<Bool, _>(true);
+        check_len::<UInt32, _>(200u8);
+        check_len::<Int32, _>(-1i8);
+        check_len::<SInt32, _>(i16::MIN);
+        check_len::<String, _>(heapless::String::<10>::from("🐉"));
+        check_len::<Bytes, _>(heapless::Vec::<u8, 10>::new());
+        check_len::<Bytes, _>([0u8; 6]);
+    }
+}
@@ -11,9 +11,25 @@ pub enum DecodeError {
     StringParseError,
     UnknownFieldNumber(usize),
     FieldOverflow(&'static str),
+    LengthMismatch(&'static str),
+    ValueOutOfRange(&'static str),
     WrongWireType(u8, &'static str),
+    RecursionLimit,
+    AllocationFailed(&'static str),
+    // an after_decode hook did not accept the message
+    Rejected(&'static str),
+    // malformed JSON, or a JSON value of the wrong type, at this offset in the input
+    InvalidJson(usize),
+    // malformed text format, or an unknown field name, at this offset in the input
+    InvalidText(usize),
+    // a group, or a wire type that doesn't exist
+    UnsupportedWireType(u8),
 }
 
+// Default maximum depth of embedded messages, the same as the reference implementation.
+pub const RECURSION_LIMIT: u32 = 100;
+
+#[allow(clippy::extra_unused_lifetimes, clippy::len_zero, clippy::redundant_pattern_matching)]
 pub fn leb128<'a, I>(mut bytes: I) -> Result<u64, DecodeError>
 where I: Iterator<Item = u8> {
     // LEB128 encoded numbers are split up in 7-bit chunks
@@ -95,6 +111,7 @@ where I: Iterator<Item = u8> {
 }
 
 
+#[allow(clippy::needless_return, clippy::unnecessary_cast)]
 pub fn tag<I>(bytes: I) -> Result<(u32, u8), DecodeError>
 where I: Iterator<Item = u8> {
     let val = leb128_u32(bytes)?;
@@ -107,6 +124,7 @@ where I: Iterator<Item = u8> {
     return Ok((field_number, wire_type));
 }
 
+#[allow(clippy::extra_unused_lifetimes)]
 pub fn string<'a, const SIZE: usize, I>(mut bytes: I, field_name: &'static str) -> Result<heapless::String<SIZE>, DecodeError>
 where I: Iterator<Item = u8> {
     // println!("decoding string of max size {}", SIZE);
@@ -130,26 +148,31 @@ where I: Iterator<Item = u8> {
     heapless::String::from_str(s).or(Err(DecodeError::StringParseError))
 }
 
+#[allow(clippy::extra_unused_lifetimes)]
 pub fn int32<'a, I>(mut bytes: I, _field_name: &str) -> Result<i32, DecodeError>
 where I: Iterator<Item = u8> {
     leb128_i32(&mut bytes)
 }
 
+#[allow(clippy::extra_unused_lifetimes)]
 pub fn int64<'a, I>(mut bytes: I, _field_name: &str) -> Result<i64, DecodeError>
 where I: Iterator<Item = u8> {
     leb128_i64(&mut bytes)
 }
 
+#[allow(clippy::extra_unused_lifetimes)]
 pub fn uint32<'a, I>(mut bytes: I, _field_name: &str) -> Result<u32, DecodeError>
 where I: Iterator<Item = u8> {
     leb128_u32(&mut bytes)
 }
 
+#[allow(clippy::extra_unused_lifetimes)]
 pub fn uint64<'a, I>(mut bytes: I, _field_name: &str) -> Result<u64, DecodeError>
 where I: Iterator<Item = u8> {
     leb128(&mut bytes)
 }
 
+#[allow(clippy::extra_unused_lifetimes)]
 pub fn sint32<'a, I>(mut bytes: I, _field_name: &str) -> Result<i32, DecodeError>
 where I: Iterator<Item = u8> {
     let value = leb128_u32(&mut bytes)?;
@@ -176,6 +199,7 @@ where I: Iterator<Item = u8> {
     Ok(abs ^ -sign)
 }
 
+#[allow(clippy::extra_unused_lifetimes)]
 pub fn sint64<'a, I>(mut bytes: I, _field_name: &str) -> Result<i64, DecodeError>
 where I: Iterator<Item = u8> {
     // same as sint32, but everything is 64
@@ -185,6 +209,7 @@ where I: Iterator<Item = u8> {
     Ok(abs ^ -sign)
 }
 
+#[allow(clippy::extra_unused_lifetimes)]
 pub fn unknown<'a, I>(mut bytes: I, wire_type: u8) -> Result<(), DecodeError>
 where I: Iterator<Item = u8> {
     match wire_type {
@@ -213,6 +238,7 @@ where I: Iterator<Item = u8> {
     Ok(())
 }
 
+#[allow(clippy::extra_unused_lifetimes, clippy::needless_range_loop)]
 pub fn fixed32<'a, I>(mut bytes: I, _field_name: &str) -> Result<u32, DecodeError>
 where I: Iterator<Item = u8> {
     const SIZE: usize = (u32::BITS/8) as usize;
@@ -230,6 +256,7 @@ where I: Iterator<Item = u8> {
     Ok(u32::from_le_bytes(slice))
 }
 
+#[allow(clippy::extra_unused_lifetimes, clippy::needless_range_loop)]
 pub fn fixed64<'a, I>(mut bytes: I, _field_name: &str) -> Result<u64, DecodeError>
 where I: Iterator<Item = u8> {
     const SIZE: usize = (u64::BITS/8) as usize;
@@ -257,6 +284,7 @@ where I: Iterator<Item = u8> {
     fixed64(bytes, field_name).map(|u| u as i64)
 }
 
+#[allow(clippy::extra_unused_lifetimes, clippy::needless_range_loop, clippy::unnecessary_cast)]
 pub fn float<'a, I>(mut bytes: I, _field_name: &str) -> Result<f32, DecodeError>
 where I: Iterator<Item = u8> {
     let mut buf = [0 as u8; 32/8];
@@ -270,6 +298,7 @@ where I: Iterator<Item = u8> {
     Ok(f32::from_le_bytes(buf))
 }
 
+#[allow(clippy::extra_unused_lifetimes, clippy::needless_range_loop, clippy::unnecessary_cast)]
 pub fn double<'a, I>(mut bytes: I, _field_name: &str) -> Result<f64, DecodeError>
 where I: Iterator<Item = u8> {
     let mut buf = [0 as u8; 64/8];
@@ -283,6 +312,7 @@ where I: Iterator<Item = u8> {
     Ok(f64::from_le_bytes(buf))
 }
 
+#[allow(clippy::extra_unused_lifetimes, clippy::needless_return)]
 pub fn bool<'a, I>(mut bytes: I, _field_name: &str) -> Result<bool, DecodeError>
 where I: Iterator<Item = u8> {
     match bytes.next() {
@@ -291,6 +321,7 @@ where I: Iterator<Item = u8> {
     }
 }
 
+#[allow(clippy::extra_unused_lifetimes)]
 pub fn bytes<'a, const SIZE: usize, I>(mut bytes: I, field_name: &'static str) -> Result<heapless::Vec<u8, SIZE>, DecodeError>
 where I: Iterator<Item = u8> {
     // println!("decoding bytes of max size {}", SIZE);
@@ -310,4 +341,114 @@ where I: Iterator<Item = u8> {
         };
     }
     Ok(strbuf)
-}
\ No newline at end of file
+}
+
+pub fn bytes_array<const SIZE: usize, I>(mut bytes: I, field_name: &'static str) -> Result<[u8; SIZE], DecodeError>
+where I: Iterator<Item = u8> {
+    let bufsize = leb128_u32(&mut bytes)?;
+
+    // fixed size fields must match exactly, anything else is not the value we expect
+    if bufsize != (SIZE as u32) {
+        return Err(DecodeError::LengthMismatch(field_name))
+    }
+
+    let mut buf = [0u8; SIZE];
+    for slot in buf.iter_mut() {
+        match bytes.next() {
+            Some(byte) => *slot = byte,
+            None => return Err(DecodeError::UnexpectedEndOfBuffer),
+        };
+    }
+    Ok(buf)
+}
+
+// The value of a field, as far as it can be known without a message definition.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RawValue<'a> {
+    Varint(u64),
+    Fixed32(u32),
+    Fixed64(u64),
+    LengthDelimited(&'a [u8]),
+}
+
+impl<'a> RawValue<'a> {
+    // The fields of an embedded message, if the value looks like one.
+    // Like `protoc --decode_raw`, length delimited values that parse as a message are taken for one,
+    // strings and bytes may be mistaken for a message by accident.
+    pub fn as_message(&self) -> Option<RawFieldIter<'a>> {
+        match self {
+            RawValue::LengthDelimited(bytes) if !bytes.is_empty()
+                && RawFieldIter::new(bytes).all(|field| field.is_ok()) => Some(RawFieldIter::new(bytes)),
+            _ => None,
+        }
+    }
+}
+
+// Walks the fields of an encoded message without knowing its definition,
+// yielding the field number and value of each field, see `protoc --decode_raw`.
+// Iteration stops after an error, `position` is then the offset of the field that could not be read.
+pub struct RawFieldIter<'a> {
+    buffer: &'a [u8],
+    position: usize,
+    failed: bool,
+}
+
+impl<'a> RawFieldIter<'a> {
+    pub fn new(buffer: &'a [u8]) -> Self {
+        RawFieldIter { buffer, position: 0, failed: false }
+    }
+
+    // The offset in the buffer of the next field.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // Returns the field and the offset after it.
+    fn field(&self) -> Result<(u32, RawValue<'a>, usize), DecodeError> {
+        let mut rest = self.buffer[self.position..].iter();
+        let (field_number, wire_type) = tag(rest.by_ref().copied())?;
+        if field_number == 0 {
+            return Err(DecodeError::UnknownFieldNumber(0));
+        }
+        // the tag was there, so a missing value means the buffer was cut short
+        let truncated = |e| if e == DecodeError::EmptyBuffer { DecodeError::UnexpectedEndOfBuffer } else { e };
+        let value = match wire_type {
+            wire_types::VARINT => RawValue::Varint(leb128(rest.by_ref().copied()).map_err(truncated)?),
+            wire_types::B32 => RawValue::Fixed32(u32::from_le_bytes(take(&mut rest, 4)?.try_into().unwrap())),
+            wire_types::B64 => RawValue::Fixed64(u64::from_le_bytes(take(&mut rest, 8)?.try_into().unwrap())),
+            wire_types::LENGTHDELIMITED => {
+                let len = leb128_u32(rest.by_ref().copied()).map_err(truncated)?;
+                RawValue::LengthDelimited(take(&mut rest, len as usize)?)
+            },
+            _ => return Err(DecodeError::UnsupportedWireType(wire_type)),
+        };
+        Ok((field_number, value, self.buffer.len() - rest.as_slice().len()))
+    }
+}
+
+fn take<'a>(bytes: &mut core::slice::Iter<'a, u8>, len: usize) -> Result<&'a [u8], DecodeError> {
+    let slice = bytes.as_slice();
+    let taken = slice.get(..len).ok_or(DecodeError::UnexpectedEndOfBuffer)?;
+    *bytes = slice[len..].iter();
+    Ok(taken)
+}
+
+impl<'a> Iterator for RawFieldIter<'a> {
+    type Item = Result<(u32, RawValue<'a>), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.position == self.buffer.len() {
+            return None;
+        }
+        match self.field() {
+            Ok((field_number, value, end)) => {
+                self.position = end;
+                Some(Ok((field_number, value)))
+            },
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            },
+        }
+    }
+}
@@ -0,0 +1,559 @@
+// Messages without a Rust type, described by a serialized FileDescriptorSet (`protoc -o`).
+// Their fields are read and written by name, e.g. for a gateway forwarding messages of
+// many device types without compiling in their schemas.
+//
+// The descriptor set must hold the files of every message it refers to, use
+// `protoc --include_imports`. Its field types are those of `reflect::ProtoType`,
+// oneof members (including proto3 `optional` fields) have `Label::Oneof`.
+// Repeated fields are encoded non-packed like those of derived messages, both are decoded.
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::decoder::{self, DecodeError, RawFieldIter, RawValue};
+use crate::encoder;
+use crate::reflect::{Label, ProtoType};
+use crate::traits::{MessageEncoder, Writer, WriterError};
+use crate::wiretypes::wire_types;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DescriptorError {
+    // the FileDescriptorSet is malformed
+    Decode(DecodeError),
+    // a field refers to a message that isn't in the set, or not by its fully qualified name
+    UnknownType(String),
+    // a proto2 group
+    UnsupportedField(String),
+}
+
+impl From<DecodeError> for DescriptorError {
+    fn from(error: DecodeError) -> Self {
+        DescriptorError::Decode(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DynamicError {
+    // not a field of the message
+    UnknownField(String),
+    // the value is of another type than the field, or a message of another type
+    TypeMismatch(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct DynamicField {
+    pub name: String,
+    pub number: u32,
+    pub proto_type: ProtoType,
+    pub label: Label,
+    // index into `MessageType::oneofs`, for Label::Oneof
+    pub oneof: Option<usize>,
+    // the type of message fields, an index into the pool
+    message: Option<usize>,
+    // the fully qualified type name until it's resolved
+    type_name: String,
+}
+
+#[derive(Debug, Clone)]
+struct MessageInfo {
+    // fully qualified, without leading dot, e.g. `api.v1.Request`
+    full_name: String,
+    fields: Vec<DynamicField>,
+    oneofs: Vec<String>,
+}
+
+// All messages of a FileDescriptorSet.
+#[derive(Debug, Clone)]
+pub struct DescriptorPool {
+    messages: Vec<MessageInfo>,
+}
+
+impl DescriptorPool {
+    // Load a serialized FileDescriptorSet.
+    pub fn decode(buffer: &[u8]) -> Result<Self, DescriptorError> {
+        let mut pool = DescriptorPool { messages: Vec::new() };
+        for field in RawFieldIter::new(buffer) {
+            // FileDescriptorSet.file
+            if let (1, RawValue::LengthDelimited(file)) = field? {
+                pool.add_file(file)?;
+            }
+        }
+
+        // message types can refer to messages defined later, or in other files
+        let names: BTreeMap<String, usize> = pool.messages.iter().enumerate()
+            .map(|(index, message)| (message.full_name.clone(), index))
+            .collect();
+        for message in pool.messages.iter_mut() {
+            for field in message.fields.iter_mut().filter(|f| f.proto_type == ProtoType::Message) {
+                // protoc writes fully qualified names with a leading dot
+                let index = field.type_name.strip_prefix('.').and_then(|name| names.get(name))
+                    .ok_or_else(|| DescriptorError::UnknownType(field.type_name.clone()))?;
+                field.message = Some(*index);
+            }
+        }
+        Ok(pool)
+    }
+
+    // FileDescriptorProto
+    fn add_file(&mut self, buffer: &[u8]) -> Result<(), DescriptorError> {
+        let mut package = String::new();
+        let mut messages = Vec::new();
+        for field in RawFieldIter::new(buffer) {
+            match field? {
+                (2, RawValue::LengthDelimited(name)) => package = utf8(name)?,
+                (4, RawValue::LengthDelimited(message)) => messages.push(message),
+                _ => {},
+            }
+        }
+        for message in messages {
+            self.add_message(&package, message)?;
+        }
+        Ok(())
+    }
+
+    // DescriptorProto, its nested messages are added as well.
+    fn add_message(&mut self, scope: &str, buffer: &[u8]) -> Result<(), DescriptorError> {
+        let mut name = String::new();
+        let mut fields = Vec::new();
+        let mut nested = Vec::new();
+        let mut oneofs = Vec::new();
+        for field in RawFieldIter::new(buffer) {
+            match field? {
+                (1, RawValue::LengthDelimited(value)) => name = utf8(value)?,
+                (2, RawValue::LengthDelimited(value)) => fields.push(value),
+                (3, RawValue::LengthDelimited(value)) => nested.push(value),
+                // OneofDescriptorProto.name
+                (8, RawValue::LengthDelimited(value)) => {
+                    let mut oneof = String::new();
+                    for field in RawFieldIter::new(value) {
+                        if let (1, RawValue::LengthDelimited(value)) = field? {
+                            oneof = utf8(value)?;
+                        }
+                    }
+                    oneofs.push(oneof);
+                },
+                _ => {},
+            }
+        }
+
+        let full_name = if scope.is_empty() { name } else { format!("{}.{}", scope, name) };
+        let fields: Result<Vec<_>, _> = fields.into_iter().map(parse_field).collect();
+        self.messages.push(MessageInfo { full_name: full_name.clone(), fields: fields?, oneofs });
+        for message in nested {
+            self.add_message(&full_name, message)?;
+        }
+        Ok(())
+    }
+
+    // The message type with this fully qualified name, e.g. `api.v1.Request`.
+    pub fn message(&self, full_name: &str) -> Option<MessageType<'_>> {
+        let full_name = full_name.strip_prefix('.').unwrap_or(full_name);
+        self.messages.iter()
+            .position(|message| message.full_name == full_name)
+            .map(|index| MessageType { pool: self, index })
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = MessageType<'_>> {
+        (0..self.messages.len()).map(|index| MessageType { pool: self, index })
+    }
+}
+
+// FieldDescriptorProto
+fn parse_field(buffer: &[u8]) -> Result<DynamicField, DescriptorError> {
+    let mut field = DynamicField {
+        name: String::new(),
+        number: 0,
+        proto_type: ProtoType::Int32,
+        label: Label::Singular,
+        oneof: None,
+        message: None,
+        type_name: String::new(),
+    };
+    let mut repeated = false;
+    let mut proto_type = 0;
+    for value in RawFieldIter::new(buffer) {
+        match value? {
+            (1, RawValue::LengthDelimited(value)) => field.name = utf8(value)?,
+            (3, RawValue::Varint(value)) => field.number = value as u32,
+            (4, RawValue::Varint(value)) => repeated = value == 3,
+            (5, RawValue::Varint(value)) => proto_type = value,
+            (6, RawValue::LengthDelimited(value)) => field.type_name = utf8(value)?,
+            (9, RawValue::Varint(value)) => field.oneof = Some(value as usize),
+            _ => {},
+        }
+    }
+    field.proto_type = match proto_type {
+        1 => ProtoType::Double,
+        2 => ProtoType::Float,
+        3 => ProtoType::Int64,
+        4 => ProtoType::UInt64,
+        5 => ProtoType::Int32,
+        6 => ProtoType::Fixed64,
+        7 => ProtoType::Fixed32,
+        8 => ProtoType::Bool,
+        9 => ProtoType::String,
+        11 => ProtoType::Message,
+        12 => ProtoType::Bytes,
+        13 => ProtoType::UInt32,
+        14 => ProtoType::Enum,
+        15 => ProtoType::SFixed32,
+        16 => ProtoType::SFixed64,
+        17 => ProtoType::SInt32,
+        18 => ProtoType::SInt64,
+        _ => return Err(DescriptorError::UnsupportedField(field.name)),
+    };
+    field.label = match (repeated, field.oneof) {
+        (true, _) => Label::Repeated,
+        (false, Some(_)) => Label::Oneof,
+        (false, None) => Label::Singular,
+    };
+    Ok(field)
+}
+
+fn utf8(bytes: &[u8]) -> Result<String, DecodeError> {
+    core::str::from_utf8(bytes).map(str::to_string).map_err(|_| DecodeError::StringParseError)
+}
+
+// A message of a DescriptorPool.
+#[derive(Clone, Copy)]
+pub struct MessageType<'a> {
+    pool: &'a DescriptorPool,
+    index: usize,
+}
+
+impl core::fmt::Debug for MessageType<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_tuple("MessageType").field(&self.full_name()).finish()
+    }
+}
+
+impl PartialEq for MessageType<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self.pool, other.pool) && self.index == other.index
+    }
+}
+
+impl<'a> MessageType<'a> {
+    fn info(&self) -> &'a MessageInfo {
+        &self.pool.messages[self.index]
+    }
+
+    pub fn full_name(&self) -> &'a str {
+        &self.info().full_name
+    }
+
+    pub fn fields(&self) -> &'a [DynamicField] {
+        &self.info().fields
+    }
+
+    pub fn oneofs(&self) -> &'a [String] {
+        &self.info().oneofs
+    }
+
+    pub fn field(&self, number: u32) -> Option<&'a DynamicField> {
+        self.fields().iter().find(|field| field.number == number)
+    }
+
+    pub fn field_by_name(&self, name: &str) -> Option<&'a DynamicField> {
+        self.fields().iter().find(|field| field.name == name)
+    }
+
+    // The type of a message field.
+    pub fn message_type(&self, field: &DynamicField) -> Option<MessageType<'a>> {
+        field.message.map(|index| MessageType { pool: self.pool, index })
+    }
+
+    // A message without any fields set.
+    pub fn new_message(&self) -> DynamicMessage<'a> {
+        DynamicMessage { message_type: *self, values: BTreeMap::new(), unknown: Vec::new() }
+    }
+
+    pub fn decode(&self, buffer: &[u8]) -> Result<DynamicMessage<'a>, DecodeError> {
+        self.decode_with_limit(buffer, decoder::RECURSION_LIMIT)
+    }
+
+    // Same as decode, but with a custom maximum depth of embedded messages.
+    pub fn decode_with_limit(&self, buffer: &[u8], recursion_limit: u32) -> Result<DynamicMessage<'a>, DecodeError> {
+        let mut message = self.new_message();
+        let mut rest = buffer.iter();
+        while !rest.as_slice().is_empty() {
+            let start = rest.as_slice();
+            let (field_number, wire_type) = decoder::tag(rest.by_ref().copied())?;
+            let field = match self.field(field_number) {
+                Some(field) => field,
+                None => {
+                    skip(&mut rest, wire_type)?;
+                    // kept as they are, and written again when encoding
+                    message.unknown.extend_from_slice(&start[..start.len() - rest.as_slice().len()]);
+                    continue;
+                },
+            };
+
+            if field.label == Label::Repeated {
+                let mut values = match message.values.remove(&field.number) {
+                    Some(DynamicValue::List(values)) => values,
+                    _ => Vec::new(),
+                };
+                // packed repeated field
+                if wire_type == wire_types::LENGTHDELIMITED && wire_type_of(field.proto_type) != wire_types::LENGTHDELIMITED {
+                    let packed = length_delimited(&mut rest)?;
+                    let mut bytes = packed.iter().copied();
+                    while bytes.len() > 0 {
+                        values.push(decode_scalar(field, &mut bytes)?);
+                    }
+                } else {
+                    values.push(self.decode_value(field, wire_type, &mut rest, recursion_limit)?);
+                }
+                message.values.insert(field.number, DynamicValue::List(values));
+            } else {
+                let value = self.decode_value(field, wire_type, &mut rest, recursion_limit)?;
+                message.clear_oneof(field);
+                message.values.insert(field.number, value);
+            }
+        }
+        Ok(message)
+    }
+
+    fn decode_value(&self, field: &DynamicField, wire_type: u8, rest: &mut core::slice::Iter<u8>, recursion_limit: u32) -> Result<DynamicValue<'a>, DecodeError> {
+        if wire_type != wire_type_of(field.proto_type) {
+            return Err(DecodeError::WrongWireType(wire_type, "DynamicMessage"));
+        }
+        match field.proto_type {
+            ProtoType::Message => {
+                if recursion_limit == 0 {
+                    return Err(DecodeError::RecursionLimit);
+                }
+                let bytes = length_delimited(rest)?;
+                // resolved when loading the descriptor set
+                let message_type = self.message_type(field).unwrap();
+                Ok(DynamicValue::Message(message_type.decode_with_limit(bytes, recursion_limit - 1)?))
+            },
+            ProtoType::String => Ok(DynamicValue::String(utf8(length_delimited(rest)?)?)),
+            ProtoType::Bytes => Ok(DynamicValue::Bytes(length_delimited(rest)?.to_vec())),
+            _ => decode_scalar(field, rest.by_ref().copied()),
+        }
+    }
+}
+
+// Read the length and the value of a length delimited field.
+fn length_delimited<'b>(rest: &mut core::slice::Iter<'b, u8>) -> Result<&'b [u8], DecodeError> {
+    let len = decoder::leb128_u32(rest.by_ref().copied())? as usize;
+    let bytes = rest.as_slice();
+    if bytes.len() < len {
+        return Err(DecodeError::UnexpectedEndOfBuffer);
+    }
+    *rest = bytes[len..].iter();
+    Ok(&bytes[..len])
+}
+
+// Skip the value of an unknown field.
+fn skip(rest: &mut core::slice::Iter<u8>, wire_type: u8) -> Result<(), DecodeError> {
+    let len = match wire_type {
+        wire_types::VARINT => {
+            decoder::leb128(rest.by_ref().copied())?;
+            return Ok(());
+        },
+        wire_types::LENGTHDELIMITED => {
+            length_delimited(rest)?;
+            return Ok(());
+        },
+        wire_types::B32 => 4,
+        wire_types::B64 => 8,
+        other => return Err(DecodeError::UnsupportedWireType(other)),
+    };
+    if rest.len() < len {
+        return Err(DecodeError::UnexpectedEndOfBuffer);
+    }
+    *rest = rest.as_slice()[len..].iter();
+    Ok(())
+}
+
+fn decode_scalar<'a>(field: &DynamicField, bytes: impl Iterator<Item = u8>) -> Result<DynamicValue<'a>, DecodeError> {
+    let name = field.name.as_str();
+    Ok(match field.proto_type {
+        ProtoType::Int32 => DynamicValue::Int32(decoder::int32(bytes, name)?),
+        ProtoType::SInt32 => DynamicValue::Int32(decoder::sint32(bytes, name)?),
+        ProtoType::SFixed32 => DynamicValue::Int32(decoder::sfixed32(bytes, name)?),
+        ProtoType::Int64 => DynamicValue::Int64(decoder::int64(bytes, name)?),
+        ProtoType::SInt64 => DynamicValue::Int64(decoder::sint64(bytes, name)?),
+        ProtoType::SFixed64 => DynamicValue::Int64(decoder::sfixed64(bytes, name)?),
+        ProtoType::UInt32 => DynamicValue::UInt32(decoder::uint32(bytes, name)?),
+        ProtoType::Fixed32 => DynamicValue::UInt32(decoder::fixed32(bytes, name)?),
+        ProtoType::UInt64 => DynamicValue::UInt64(decoder::uint64(bytes, name)?),
+        ProtoType::Fixed64 => DynamicValue::UInt64(decoder::fixed64(bytes, name)?),
+        ProtoType::Float => DynamicValue::Float(decoder::float(bytes, name)?),
+        ProtoType::Double => DynamicValue::Double(decoder::double(bytes, name)?),
+        ProtoType::Bool => DynamicValue::Bool(decoder::bool(bytes, name)?),
+        ProtoType::Enum => DynamicValue::Enum(decoder::int32(bytes, name)?),
+        // length delimited types are never packed
+        _ => return Err(DecodeError::WrongWireType(wire_types::LENGTHDELIMITED, "DynamicMessage")),
+    })
+}
+
+fn wire_type_of(proto_type: ProtoType) -> u8 {
+    match proto_type {
+        ProtoType::Fixed32 | ProtoType::SFixed32 | ProtoType::Float => wire_types::B32,
+        ProtoType::Fixed64 | ProtoType::SFixed64 | ProtoType::Double => wire_types::B64,
+        ProtoType::String | ProtoType::Bytes | ProtoType::Message | ProtoType::Custom => wire_types::LENGTHDELIMITED,
+        _ => wire_types::VARINT,
+    }
+}
+
+// The value of a field, signed integers are `Int32` and `Int64` whether int, sint or sfixed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicValue<'a> {
+    Int32(i32),
+    Int64(i64),
+    UInt32(u32),
+    UInt64(u64),
+    Float(f32),
+    Double(f64),
+    Bool(bool),
+    Enum(i32),
+    String(String),
+    Bytes(Vec<u8>),
+    Message(DynamicMessage<'a>),
+    // the values of a repeated field
+    List(Vec<DynamicValue<'a>>),
+}
+
+impl DynamicValue<'_> {
+    // Whether this can be the value of a singular field of `proto_type`, of `message_type` for messages.
+    fn is_a(&self, proto_type: ProtoType, message_type: Option<MessageType>) -> bool {
+        match (self, proto_type) {
+            (DynamicValue::Int32(_), ProtoType::Int32 | ProtoType::SInt32 | ProtoType::SFixed32) |
+            (DynamicValue::Int64(_), ProtoType::Int64 | ProtoType::SInt64 | ProtoType::SFixed64) |
+            (DynamicValue::UInt32(_), ProtoType::UInt32 | ProtoType::Fixed32) |
+            (DynamicValue::UInt64(_), ProtoType::UInt64 | ProtoType::Fixed64) |
+            (DynamicValue::Float(_), ProtoType::Float) |
+            (DynamicValue::Double(_), ProtoType::Double) |
+            (DynamicValue::Bool(_), ProtoType::Bool) |
+            (DynamicValue::Enum(_), ProtoType::Enum) |
+            (DynamicValue::String(_), ProtoType::String) |
+            (DynamicValue::Bytes(_), ProtoType::Bytes) => true,
+            (DynamicValue::Message(message), ProtoType::Message) => Some(message.message_type) == message_type,
+            _ => false,
+        }
+    }
+
+    fn encode(&self, buffer: &mut impl Writer, field: &DynamicField) -> Result<usize, WriterError> {
+        let mut bytes_written = encoder::tag(buffer, &field.number, &wire_type_of(field.proto_type))?;
+        bytes_written += match (self, field.proto_type) {
+            (DynamicValue::Int32(v), ProtoType::SInt32) => encoder::sint32(buffer, v)?,
+            (DynamicValue::Int32(v), ProtoType::SFixed32) => encoder::sfixed32(buffer, v)?,
+            (DynamicValue::Int32(v) | DynamicValue::Enum(v), _) => encoder::int32(buffer, v)?,
+            (DynamicValue::Int64(v), ProtoType::SInt64) => encoder::sint64(buffer, v)?,
+            (DynamicValue::Int64(v), ProtoType::SFixed64) => encoder::sfixed64(buffer, v)?,
+            (DynamicValue::Int64(v), _) => encoder::int64(buffer, v)?,
+            (DynamicValue::UInt32(v), ProtoType::Fixed32) => encoder::fixed32(buffer, v)?,
+            (DynamicValue::UInt32(v), _) => encoder::uint32(buffer, v)?,
+            (DynamicValue::UInt64(v), ProtoType::Fixed64) => encoder::fixed64(buffer, v)?,
+            (DynamicValue::UInt64(v), _) => encoder::uint64(buffer, v)?,
+            (DynamicValue::Float(v), _) => encoder::float(buffer, v)?,
+            (DynamicValue::Double(v), _) => encoder::double(buffer, v)?,
+            (DynamicValue::Bool(v), _) => encoder::bool(buffer, v)?,
+            (DynamicValue::String(v), _) => length_delimited_value(buffer, v.as_bytes())?,
+            (DynamicValue::Bytes(v), _) => length_delimited_value(buffer, v)?,
+            (DynamicValue::Message(v), _) => {
+                let len = v.twpb_encoded_len();
+                encoder::leb128_u32(buffer, &(len as u32))? + v.twpb_encode(buffer)?
+            },
+            // set() only accepts lists for repeated fields, which are written value by value
+            (DynamicValue::List(_), _) => 0,
+        };
+        Ok(bytes_written)
+    }
+}
+
+fn length_delimited_value(buffer: &mut impl Writer, value: &[u8]) -> Result<usize, WriterError> {
+    Ok(encoder::leb128_u32(buffer, &(value.len() as u32))? + buffer.write_all(value)?)
+}
+
+// A message of a DescriptorPool, with the values of its fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicMessage<'a> {
+    message_type: MessageType<'a>,
+    // by field number, repeated fields hold a List
+    values: BTreeMap<u32, DynamicValue<'a>>,
+    // encoded fields that aren't part of the message type
+    unknown: Vec<u8>,
+}
+
+impl<'a> DynamicMessage<'a> {
+    pub fn message_type(&self) -> MessageType<'a> {
+        self.message_type
+    }
+
+    // The value of a set field, a `List` for repeated fields.
+    pub fn get(&self, name: &str) -> Option<&DynamicValue<'a>> {
+        let field = self.message_type.field_by_name(name)?;
+        self.values.get(&field.number)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut DynamicValue<'a>> {
+        let field = self.message_type.field_by_name(name)?;
+        self.values.get_mut(&field.number)
+    }
+
+    // Set a field, a `List` of values for repeated fields.
+    // Setting a oneof member clears the others.
+    pub fn set(&mut self, name: &str, value: DynamicValue<'a>) -> Result<(), DynamicError> {
+        let field = self.message_type.field_by_name(name)
+            .ok_or_else(|| DynamicError::UnknownField(name.to_string()))?;
+        let message_type = self.message_type.message_type(field);
+        let valid = match &value {
+            DynamicValue::List(values) => field.label == Label::Repeated
+                && values.iter().all(|v| v.is_a(field.proto_type, message_type)),
+            value => field.label != Label::Repeated && value.is_a(field.proto_type, message_type),
+        };
+        if !valid {
+            return Err(DynamicError::TypeMismatch(name.to_string()));
+        }
+        self.clear_oneof(field);
+        self.values.insert(field.number, value);
+        Ok(())
+    }
+
+    // Unset a field, returns its value.
+    pub fn clear(&mut self, name: &str) -> Option<DynamicValue<'a>> {
+        let field = self.message_type.field_by_name(name)?;
+        self.values.remove(&field.number)
+    }
+
+    // The set fields, by field number.
+    pub fn fields(&self) -> impl Iterator<Item = (&'a DynamicField, &DynamicValue<'a>)> {
+        let message_type = self.message_type;
+        // only fields of the message type get a value
+        self.values.iter().map(move |(number, value)| (message_type.field(*number).unwrap(), value))
+    }
+
+    // Fields that aren't part of the message type, as they were encoded.
+    pub fn unknown_fields(&self) -> &[u8] {
+        &self.unknown
+    }
+
+    fn clear_oneof(&mut self, field: &DynamicField) {
+        if let Some(oneof) = field.oneof {
+            for other in self.message_type.fields().iter().filter(|f| f.oneof == Some(oneof)) {
+                self.values.remove(&other.number);
+            }
+        }
+    }
+}
+
+impl MessageEncoder for DynamicMessage<'_> {
+    fn twpb_encode(&self, buffer: &mut impl Writer) -> Result<usize, WriterError> {
+        let mut bytes_written = 0;
+        for (field, value) in self.fields() {
+            match value {
+                DynamicValue::List(values) => for value in values {
+                    bytes_written += value.encode(buffer, field)?;
+                },
+                value => bytes_written += value.encode(buffer, field)?,
+            }
+        }
+        bytes_written += buffer.write_all(&self.unknown)?;
+        Ok(bytes_written)
+    }
+}
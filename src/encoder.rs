@@ -32,6 +32,13 @@ pub fn leb128(bytes: &mut impl Writer, input: &u64) -> Result<usize, WriterError
     Ok(bytes_written)
 }
 
+// The amount of bytes leb128() would write for this value.
+pub fn leb128_len(input: &u64) -> usize {
+    // every byte holds 7 bits of the value, and we always write at least one byte
+    let bits = u64::BITS - input.leading_zeros();
+    core::cmp::max(1, bits.div_ceil(7) as usize)
+}
+
 pub fn leb128_u32(bytes: &mut impl Writer, input: &u32) -> Result<usize, WriterError> {
     leb128(bytes, &(*input as u64))
 }
@@ -56,6 +63,11 @@ pub fn tag(bytes: &mut impl Writer, field_number: &u32, wire_type: &u8) -> Resul
     leb128_u32(bytes, &(((*field_number << 3) & 0xFF_FF_FF_F8) | (*wire_type & 0b0111) as u32))
 }
 
+// The amount of bytes tag() writes for this field number.
+pub fn tag_len(field_number: &u32) -> usize {
+    leb128_len(&((*field_number as u64) << 3))
+}
+
 pub fn string<const SIZE: usize>(bytes: &mut impl Writer, input: &heapless::String<SIZE>) -> Result<usize, WriterError> {
     let b = input.as_bytes();
     let mut bytes_written = 0;
@@ -107,7 +119,8 @@ pub fn sint32(bytes: &mut impl Writer, input :&i32) -> Result<usize, WriterError
     // ..0010 (2)  => sign=0 => ..00100 XOR ..0000 (-0) = ..0100 (4)
     let converted = (*input << 1) ^ -sign;
 
-    leb128_i32(bytes, &converted)
+    // The result is unsigned, don't let leb128 sign extend it to 64 bits.
+    leb128_u32(bytes, &(converted as u32))
 }
 
 pub fn sint64(bytes: &mut impl Writer, input: &i64) -> Result<usize, WriterError> {
@@ -154,4 +167,14 @@ pub fn bytes<const SIZE: usize>(bytes: &mut impl Writer, input: &heapless::Vec<u
     bytes_written += write(bytes, input.as_ref())?;
 
     Ok(bytes_written)
-}
\ No newline at end of file
+}
+
+pub fn bytes_array<const SIZE: usize>(bytes: &mut impl Writer, input: &[u8; SIZE]) -> Result<usize, WriterError> {
+    let mut bytes_written = 0;
+    // Write the size bits
+    bytes_written += leb128_u32(bytes, &(SIZE as u32))?;
+
+    bytes_written += write(bytes, input)?;
+
+    Ok(bytes_written)
+}
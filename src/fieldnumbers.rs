@@ -0,0 +1,95 @@
+// Compile time checks on field numbers, used by the derive macros.
+// The field numbers of a oneof or flattened message are only known to its own type,
+// so a message containing one checks them in a const context instead of in the macro.
+
+// All field numbers of a derived type. Those of nested oneofs and flattened messages
+// are referenced instead of copied, that also works for types with generic parameters.
+#[derive(Debug)]
+pub struct FieldNumbers {
+    pub numbers: &'static [u32],
+    pub nested: &'static [&'static FieldNumbers],
+}
+
+impl FieldNumbers {
+    pub const fn contains(&self, number: u32) -> bool {
+        if contains(self.numbers, number) {
+            return true;
+        }
+        let mut i = 0;
+        while i < self.nested.len() {
+            if self.nested[i].contains(number) {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    // Whether all field numbers are in `numbers`.
+    const fn within(&self, numbers: &[u32]) -> bool {
+        let mut i = 0;
+        while i < self.numbers.len() {
+            if !contains(numbers, self.numbers[i]) {
+                return false;
+            }
+            i += 1;
+        }
+        let mut i = 0;
+        while i < self.nested.len() {
+            if !self.nested[i].within(numbers) {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+}
+
+// Equal to a list with the same field numbers, in any order.
+impl<const N: usize> PartialEq<&[u32; N]> for FieldNumbers {
+    fn eq(&self, numbers: &&[u32; N]) -> bool {
+        same(*numbers, self)
+    }
+}
+
+pub const fn contains(numbers: &[u32], number: u32) -> bool {
+    let mut i = 0;
+    while i < numbers.len() {
+        if numbers[i] == number {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+// Whether both contain the same field numbers, in any order.
+pub const fn same(a: &[u32], b: &FieldNumbers) -> bool {
+    let mut i = 0;
+    while i < a.len() {
+        if !b.contains(a[i]) {
+            return false;
+        }
+        i += 1;
+    }
+    b.within(a)
+}
+
+// Whether no field number is in both.
+pub const fn disjoint(a: &FieldNumbers, b: &FieldNumbers) -> bool {
+    let mut i = 0;
+    while i < a.numbers.len() {
+        if b.contains(a.numbers[i]) {
+            return false;
+        }
+        i += 1;
+    }
+    let mut i = 0;
+    while i < a.nested.len() {
+        if !disjoint(a.nested[i], b) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
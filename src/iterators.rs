@@ -7,6 +7,7 @@ pub struct LimitedIterator<I> where I: Iterator<Item = u8> {
 }
 
 impl<I> LimitedIterator<I> where I: Iterator<Item = u8> {
+    #[allow(clippy::redundant_field_names)]
     pub fn new(source_iterator: I, range: u32) -> Self {
         LimitedIterator{
             source_iterator: source_iterator,
@@ -33,6 +34,7 @@ pub struct NullCounterBuffer {
 }
 
 impl NullCounterBuffer {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         NullCounterBuffer{current_index: 0}
     }
@@ -46,6 +48,7 @@ impl Writer for NullCounterBuffer {
 }
 
 #[cfg(test)]
+#[allow(clippy::unnecessary_cast)]
 mod tests {
     use super::*;
 
@@ -0,0 +1,800 @@
+// Proto3 JSON mapping, see https://protobuf.dev/programming-guides/proto3/#json
+//
+// Messages deriving `Message` with `#[twpb(json)]` implement `JsonEncoder` and `JsonDecoder`.
+// Scalar fields go through `JsonCodec`, implemented by the same marker types as `ScalarCodec`:
+//   - 32 bit integers, floats and doubles are numbers, 64 bit integers are strings
+//   - NaN and infinities are the strings "NaN", "Infinity" and "-Infinity"
+//   - bytes are base64 strings
+//   - enums are the name of their value, see `EnumNames`
+//   - fields at their default value are left out
+// When decoding, numbers are also accepted as strings and the other way around,
+// enums are also accepted as numbers, and null is the default value of a field.
+// Members that are not a field of the message are ignored, like unknown fields in binary decoding.
+use core::fmt::Write as _;
+use core::str::FromStr;
+
+use crate::codec::{Bool, Bytes, Double, Enum, Fixed32, Fixed64, Float, Int32, Int64,
+    SFixed32, SFixed64, SInt32, SInt64, String, UInt32, UInt64};
+use crate::decoder::{DecodeError, RECURSION_LIMIT};
+use crate::traits::{Writer, WriterError};
+
+pub trait JsonCodec<T> {
+    // Write the value as JSON, returns the amount of bytes written.
+    fn encode_json(buffer: &mut impl Writer, value: &T) -> Result<usize, WriterError>;
+
+    // Read a JSON value, null is handled by the caller.
+    fn decode_json(parser: &mut JsonParser, field_name: &'static str) -> Result<T, DecodeError>;
+
+    // Fields at their default value are left out of the JSON object.
+    fn is_default(value: &T) -> bool;
+}
+
+pub trait JsonEncoder {
+    // Write all fields as members of a JSON object, without the braces around them.
+    // `first` is whether no member was written yet, it decides on the separating comma.
+    fn twpb_encode_json_members(&self, buffer: &mut impl Writer, first: &mut bool) -> Result<usize, WriterError>;
+
+    fn twpb_encode_json(&self, buffer: &mut impl Writer) -> Result<usize, WriterError> {
+        let mut first = true;
+        let mut bytes_written = buffer.write_all(b"{")?;
+        bytes_written += self.twpb_encode_json_members(buffer, &mut first)?;
+        bytes_written += buffer.write_all(b"}")?;
+        Ok(bytes_written)
+    }
+}
+
+pub trait JsonDecoder: Sized {
+    fn twpb_decode_json(buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut parser = JsonParser::new(buf);
+        let value = Self::twpb_decode_json_value(&mut parser, RECURSION_LIMIT)?;
+        parser.end()?;
+        Ok(value)
+    }
+
+    // Read a JSON object as this message, `recursion_limit` is the maximum depth of embedded messages.
+    fn twpb_decode_json_value(parser: &mut JsonParser, recursion_limit: u32) -> Result<Self, DecodeError>;
+
+    // Read the value of member `key` into its field, returns false if it is not a field of this message.
+    fn twpb_decode_json_member(&mut self, key: &JsonStr, parser: &mut JsonParser, recursion_limit: u32) -> Result<bool, DecodeError>;
+}
+
+// The names of the values of a proto enum, proto3 JSON writes enums by name.
+// #[derive(Enum)] with #[twpb(enum)] implements it for an enum of unit variants,
+// e.g. `const NAMES: &'static [(&'static str, i32)] = &[("MODE_OFF", 0), ("MODE_ON", 1)];`
+pub trait EnumNames {
+    const NAMES: &'static [(&'static str, i32)];
+}
+
+// Write `"key":`, preceded by a comma unless it's the first member of the object.
+pub fn member(buffer: &mut impl Writer, first: &mut bool, key: &str) -> Result<usize, WriterError> {
+    let mut bytes_written = 0;
+    if !*first {
+        bytes_written += buffer.write_all(b",")?;
+    }
+    *first = false;
+    bytes_written += string(buffer, key)?;
+    bytes_written += buffer.write_all(b":")?;
+    Ok(bytes_written)
+}
+
+// Write a JSON string, escaping what needs escaping.
+pub fn string(buffer: &mut impl Writer, value: &str) -> Result<usize, WriterError> {
+    let mut bytes_written = buffer.write_all(b"\"")?;
+    for c in value.chars() {
+        bytes_written += escaped(buffer, c)?;
+    }
+    bytes_written += buffer.write_all(b"\"")?;
+    Ok(bytes_written)
+}
+
+// Write a character of a JSON string.
+pub(crate) fn escaped(buffer: &mut impl Writer, c: char) -> Result<usize, WriterError> {
+    match c {
+        '"' => buffer.write_all(b"\\\""),
+        '\\' => buffer.write_all(b"\\\\"),
+        '\n' => buffer.write_all(b"\\n"),
+        '\r' => buffer.write_all(b"\\r"),
+        '\t' => buffer.write_all(b"\\t"),
+        c if (c as u32) < 0x20 => format(buffer, format_args!("\\u{:04x}", c as u32)),
+        c => buffer.write_all(c.encode_utf8(&mut [0; 4]).as_bytes()),
+    }
+}
+
+// Write formatted text, for numbers.
+pub(crate) fn format(buffer: &mut impl Writer, args: core::fmt::Arguments) -> Result<usize, WriterError> {
+    struct Adapter<'b, W> {
+        buffer: &'b mut W,
+        bytes_written: usize,
+        error: Option<WriterError>,
+    }
+    impl<W: Writer> core::fmt::Write for Adapter<'_, W> {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            match self.buffer.write_all(s.as_bytes()) {
+                Ok(n) => {
+                    self.bytes_written += n;
+                    Ok(())
+                },
+                Err(e) => {
+                    self.error = Some(e);
+                    Err(core::fmt::Error)
+                },
+            }
+        }
+    }
+    let mut adapter = Adapter { buffer, bytes_written: 0, error: None };
+    match adapter.write_fmt(args) {
+        Ok(()) => Ok(adapter.bytes_written),
+        Err(_) => Err(adapter.error.unwrap_or(WriterError::BufferOverflow)),
+    }
+}
+
+fn float(buffer: &mut impl Writer, value: f64, text: core::fmt::Arguments) -> Result<usize, WriterError> {
+    if value.is_nan() {
+        buffer.write_all(b"\"NaN\"")
+    } else if value == f64::INFINITY {
+        buffer.write_all(b"\"Infinity\"")
+    } else if value == f64::NEG_INFINITY {
+        buffer.write_all(b"\"-Infinity\"")
+    } else {
+        format(buffer, text)
+    }
+}
+
+// Standard base64 with padding.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64(buffer: &mut impl Writer, value: &[u8]) -> Result<usize, WriterError> {
+    let mut bytes_written = buffer.write_all(b"\"")?;
+    for chunk in value.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        let mut out = [b'='; 4];
+        for (i, slot) in out.iter_mut().enumerate().take(chunk.len() + 1) {
+            *slot = BASE64[(n >> (18 - 6 * i) & 0x3f) as usize];
+        }
+        bytes_written += buffer.write_all(&out)?;
+    }
+    bytes_written += buffer.write_all(b"\"")?;
+    Ok(bytes_written)
+}
+
+// Decode base64, both the standard and the URL safe alphabet, with or without padding.
+fn decode_base64(text: &JsonStr, mut push: impl FnMut(u8) -> Result<(), DecodeError>) -> Result<(), DecodeError> {
+    let mut bits: u32 = 0;
+    let mut nbits = 0;
+    let mut padding = false;
+    for c in text.chars() {
+        let value = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' | '-' => 62,
+            '/' | '_' => 63,
+            '=' => {
+                padding = true;
+                continue;
+            },
+            _ => return Err(DecodeError::InvalidJson(text.position)),
+        };
+        if padding {
+            return Err(DecodeError::InvalidJson(text.position));
+        }
+        bits = (bits << 6 | value) & 0xffff;
+        nbits += 6;
+        if nbits >= 8 {
+            nbits -= 8;
+            push((bits >> nbits) as u8)?;
+        }
+    }
+    Ok(())
+}
+
+// Reads JSON values from a byte slice, as needed by the derived `JsonDecoder`s.
+// Errors are `DecodeError::InvalidJson` with the offset where the input went wrong.
+pub struct JsonParser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+// The contents of a JSON string, escape sequences are resolved by `chars`.
+#[derive(Debug, Clone, Copy)]
+pub struct JsonStr<'a> {
+    raw: &'a str,
+    // offset of the string in the input, for errors
+    position: usize,
+}
+
+impl<'a> JsonStr<'a> {
+    pub fn chars(&self) -> JsonChars<'a> {
+        JsonChars { chars: self.raw.chars() }
+    }
+
+    // The string as is, if it has no escape sequences.
+    pub fn as_str(&self) -> Option<&'a str> {
+        if self.raw.contains('\\') {
+            None
+        } else {
+            Some(self.raw)
+        }
+    }
+
+    pub fn to_string<const SIZE: usize>(&self, field_name: &'static str) -> Result<heapless::String<SIZE>, DecodeError> {
+        let mut result = heapless::String::new();
+        for c in self.chars() {
+            result.push(c).map_err(|_| DecodeError::FieldOverflow(field_name))?;
+        }
+        Ok(result)
+    }
+}
+
+impl PartialEq<&str> for JsonStr<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.chars().eq(other.chars())
+    }
+}
+
+pub struct JsonChars<'a> {
+    chars: core::str::Chars<'a>,
+}
+
+impl JsonChars<'_> {
+    fn hex4(&mut self) -> u32 {
+        let mut value = 0;
+        for _ in 0..4 {
+            value = value << 4 | self.chars.next().and_then(|c| c.to_digit(16)).unwrap_or(0);
+        }
+        value
+    }
+}
+
+impl Iterator for JsonChars<'_> {
+    type Item = char;
+
+    // The parser checked the escape sequences, invalid surrogates become U+FFFD.
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c != '\\' {
+            return Some(c);
+        }
+        Some(match self.chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'u' => {
+                let high = self.hex4();
+                if (0xd800..0xdc00).contains(&high) {
+                    // only a low surrogate completes the pair, anything else is read on its own
+                    let mut next = JsonChars { chars: self.chars.clone() };
+                    if let (Some('\\'), Some('u')) = (next.chars.next(), next.chars.next()) {
+                        let low = next.hex4();
+                        if (0xdc00..0xe000).contains(&low) {
+                            self.chars = next.chars;
+                            return char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00));
+                        }
+                    }
+                }
+                char::from_u32(high).unwrap_or('\u{fffd}')
+            },
+            // '"', '\\' and '/'
+            c => c,
+        })
+    }
+}
+
+impl<'a> JsonParser<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        JsonParser { input, position: 0 }
+    }
+
+    // The offset of the next byte to be read.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    fn error(&self) -> DecodeError {
+        DecodeError::InvalidJson(self.position)
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.get(self.position) {
+            self.position += 1;
+        }
+        self.input.get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), DecodeError> {
+        if self.peek() != Some(byte) {
+            return Err(self.error());
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &[u8]) -> bool {
+        if self.peek().is_some() && self.input[self.position..].starts_with(literal) {
+            self.position += literal.len();
+            return true;
+        }
+        false
+    }
+
+    // Only whitespace may follow the value.
+    pub fn end(&mut self) -> Result<(), DecodeError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error()),
+        }
+    }
+
+    // Reads a null if that's the next value.
+    pub fn null(&mut self) -> bool {
+        self.literal(b"null")
+    }
+
+    pub fn bool(&mut self) -> Result<bool, DecodeError> {
+        if self.literal(b"true") {
+            Ok(true)
+        } else if self.literal(b"false") {
+            Ok(false)
+        } else {
+            Err(self.error())
+        }
+    }
+
+    pub fn is_string(&mut self) -> bool {
+        self.peek() == Some(b'"')
+    }
+
+    pub fn string(&mut self) -> Result<JsonStr<'a>, DecodeError> {
+        self.expect(b'"')?;
+        let start = self.position;
+        loop {
+            match self.input.get(self.position) {
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    match self.input.get(self.position + 1) {
+                        Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => self.position += 2,
+                        Some(b'u') => {
+                            let hex = self.input.get(self.position + 2..self.position + 6).ok_or(self.error())?;
+                            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                                return Err(self.error());
+                            }
+                            self.position += 6;
+                        },
+                        _ => return Err(self.error()),
+                    }
+                },
+                Some(0..=0x1f) | None => return Err(self.error()),
+                Some(_) => self.position += 1,
+            }
+        }
+        let raw = core::str::from_utf8(&self.input[start..self.position])
+            .map_err(|e| DecodeError::InvalidJson(start + e.valid_up_to()))?;
+        self.position += 1;
+        Ok(JsonStr { raw, position: start - 1 })
+    }
+
+    // A number as written in the input, e.g. "-1.5e3".
+    pub fn number(&mut self) -> Result<&'a str, DecodeError> {
+        self.peek();
+        let start = self.position;
+        let digits = |parser: &mut Self| {
+            let from = parser.position;
+            while parser.input.get(parser.position).is_some_and(u8::is_ascii_digit) {
+                parser.position += 1;
+            }
+            parser.position > from
+        };
+        if self.input.get(self.position) == Some(&b'-') {
+            self.position += 1;
+        }
+        if self.input.get(self.position) == Some(&b'0') {
+            self.position += 1;
+        } else if !digits(self) {
+            return Err(self.error());
+        }
+        if self.input.get(self.position) == Some(&b'.') {
+            self.position += 1;
+            if !digits(self) {
+                return Err(self.error());
+            }
+        }
+        if let Some(b'e' | b'E') = self.input.get(self.position) {
+            self.position += 1;
+            if let Some(b'+' | b'-') = self.input.get(self.position) {
+                self.position += 1;
+            }
+            if !digits(self) {
+                return Err(self.error());
+            }
+        }
+        // only ASCII was consumed
+        Ok(core::str::from_utf8(&self.input[start..self.position]).unwrap())
+    }
+
+    // Reads an object, calling `member` for every key, which must read the value.
+    pub fn object<F>(&mut self, mut member: F) -> Result<(), DecodeError>
+    where F: FnMut(&mut Self, JsonStr<'a>) -> Result<(), DecodeError> {
+        self.expect(b'{')?;
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(());
+        }
+        loop {
+            let key = self.string()?;
+            self.expect(b':')?;
+            member(self, key)?;
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(());
+                },
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    // Reads an array, calling `item` for every value, which must read it.
+    pub fn array<F>(&mut self, mut item: F) -> Result<(), DecodeError>
+    where F: FnMut(&mut Self) -> Result<(), DecodeError> {
+        self.expect(b'[')?;
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(());
+        }
+        loop {
+            item(self)?;
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(());
+                },
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    // Reads any value and throws it away, `recursion_limit` is the maximum nesting depth.
+    pub fn skip(&mut self, recursion_limit: u32) -> Result<(), DecodeError> {
+        match self.peek() {
+            Some(b'"') => self.string().map(|_| ()),
+            Some(b'{') | Some(b'[') if recursion_limit == 0 => Err(DecodeError::RecursionLimit),
+            Some(b'{') => self.object(|parser, _| parser.skip(recursion_limit - 1)),
+            Some(b'[') => self.array(|parser| parser.skip(recursion_limit - 1)),
+            Some(b't') | Some(b'f') => self.bool().map(|_| ()),
+            Some(b'n') if self.null() => Ok(()),
+            _ => self.number().map(|_| ()),
+        }
+    }
+
+    // A number, or a string holding one. 64 bit integers are usually written as string.
+    fn number_or_string(&mut self) -> Result<(&'a str, usize), DecodeError> {
+        let position = self.position;
+        if self.is_string() {
+            let s = self.string()?;
+            let s = s.as_str().ok_or(DecodeError::InvalidJson(position))?;
+            // the string must hold a number and nothing else
+            let mut inner = JsonParser::new(s.as_bytes());
+            inner.number().map_err(|_| DecodeError::InvalidJson(position))?;
+            inner.end().map_err(|_| DecodeError::InvalidJson(position))?;
+            Ok((s, position))
+        } else {
+            Ok((self.number()?, position))
+        }
+    }
+
+    // Integers may also be written as a float without fractional part, e.g. 1e3.
+    fn integer<T>(&mut self, field_name: &'static str) -> Result<T, DecodeError>
+    where T: FromStr + TryFrom<i64> + TryFrom<u64> {
+        let (text, position) = self.number_or_string()?;
+        if let Ok(value) = text.parse::<T>() {
+            return Ok(value);
+        }
+        let value = text.parse::<f64>().map_err(|_| DecodeError::InvalidJson(position))?;
+        if value.abs() >= u64::MAX as f64 {
+            return Err(DecodeError::ValueOutOfRange(field_name));
+        }
+        let integer = value as i128;
+        if integer as f64 != value {
+            return Err(DecodeError::InvalidJson(position));
+        }
+        let value = match (i64::try_from(integer), u64::try_from(integer)) {
+            (Ok(value), _) => T::try_from(value).ok(),
+            (_, Ok(value)) => T::try_from(value).ok(),
+            _ => None,
+        };
+        value.ok_or(DecodeError::ValueOutOfRange(field_name))
+    }
+
+    // Floats may also be "NaN", "Infinity" or "-Infinity".
+    fn float(&mut self, field_name: &'static str) -> Result<f64, DecodeError> {
+        let position = self.position;
+        if self.is_string() {
+            let s = self.string()?;
+            if s == "NaN" {
+                return Ok(f64::NAN);
+            } else if s == "Infinity" {
+                return Ok(f64::INFINITY);
+            } else if s == "-Infinity" {
+                return Ok(f64::NEG_INFINITY);
+            }
+            self.position = position;
+        }
+        let (text, position) = self.number_or_string()?;
+        let value = text.parse::<f64>().map_err(|_| DecodeError::InvalidJson(position))?;
+        if value.is_infinite() {
+            return Err(DecodeError::ValueOutOfRange(field_name));
+        }
+        Ok(value)
+    }
+}
+
+// Integers written as JSON numbers.
+macro_rules! json_number {
+    ($marker:ident, $rust_type:ty) => {
+        impl JsonCodec<$rust_type> for $marker {
+            fn encode_json(buffer: &mut impl Writer, value: &$rust_type) -> Result<usize, WriterError> {
+                format(buffer, format_args!("{}", value))
+            }
+
+            fn decode_json(parser: &mut JsonParser, field_name: &'static str) -> Result<$rust_type, DecodeError> {
+                parser.integer(field_name)
+            }
+
+            fn is_default(value: &$rust_type) -> bool {
+                *value == 0
+            }
+        }
+    };
+}
+
+// 64 bit integers are strings, JavaScript numbers can't hold all of them.
+macro_rules! json_string_number {
+    ($marker:ident, $rust_type:ty) => {
+        impl JsonCodec<$rust_type> for $marker {
+            fn encode_json(buffer: &mut impl Writer, value: &$rust_type) -> Result<usize, WriterError> {
+                format(buffer, format_args!("\"{}\"", value))
+            }
+
+            fn decode_json(parser: &mut JsonParser, field_name: &'static str) -> Result<$rust_type, DecodeError> {
+                parser.integer(field_name)
+            }
+
+            fn is_default(value: &$rust_type) -> bool {
+                *value == 0
+            }
+        }
+    };
+}
+
+json_number!(Int32, i32);
+json_number!(Int32, i16);
+json_number!(Int32, i8);
+json_number!(SInt32, i32);
+json_number!(SInt32, i16);
+json_number!(SInt32, i8);
+json_number!(SFixed32, i32);
+json_number!(UInt32, u32);
+json_number!(UInt32, u16);
+json_number!(UInt32, u8);
+json_number!(Fixed32, u32);
+json_string_number!(Int64, i64);
+json_string_number!(SInt64, i64);
+json_string_number!(SFixed64, i64);
+json_string_number!(UInt64, u64);
+json_string_number!(Fixed64, u64);
+
+impl JsonCodec<f32> for Float {
+    fn encode_json(buffer: &mut impl Writer, value: &f32) -> Result<usize, WriterError> {
+        float(buffer, *value as f64, format_args!("{}", value))
+    }
+
+    fn decode_json(parser: &mut JsonParser, field_name: &'static str) -> Result<f32, DecodeError> {
+        let value = parser.float(field_name)?;
+        // too large for a float, not an actual infinity
+        if value.is_finite() && (value as f32).is_infinite() {
+            return Err(DecodeError::ValueOutOfRange(field_name));
+        }
+        Ok(value as f32)
+    }
+
+    fn is_default(value: &f32) -> bool {
+        *value == 0.0 && value.is_sign_positive()
+    }
+}
+
+impl JsonCodec<f64> for Double {
+    fn encode_json(buffer: &mut impl Writer, value: &f64) -> Result<usize, WriterError> {
+        float(buffer, *value, format_args!("{}", value))
+    }
+
+    fn decode_json(parser: &mut JsonParser, field_name: &'static str) -> Result<f64, DecodeError> {
+        parser.float(field_name)
+    }
+
+    fn is_default(value: &f64) -> bool {
+        *value == 0.0 && value.is_sign_positive()
+    }
+}
+
+impl JsonCodec<bool> for Bool {
+    fn encode_json(buffer: &mut impl Writer, value: &bool) -> Result<usize, WriterError> {
+        buffer.write_all(if *value { b"true" } else { b"false" })
+    }
+
+    fn decode_json(parser: &mut JsonParser, _field_name: &'static str) -> Result<bool, DecodeError> {
+        parser.bool()
+    }
+
+    fn is_default(value: &bool) -> bool {
+        !*value
+    }
+}
+
+impl<T> JsonCodec<T> for Enum
+where T: Copy + Into<i32> + TryFrom<i32> + EnumNames {
+    fn encode_json(buffer: &mut impl Writer, value: &T) -> Result<usize, WriterError> {
+        let value: i32 = (*value).into();
+        match T::NAMES.iter().find(|(_, v)| *v == value) {
+            Some((name, _)) => string(buffer, name),
+            None => format(buffer, format_args!("{}", value)),
+        }
+    }
+
+    fn decode_json(parser: &mut JsonParser, field_name: &'static str) -> Result<T, DecodeError> {
+        let position = parser.position;
+        let value = if parser.is_string() {
+            let name = parser.string()?;
+            T::NAMES.iter().find(|(n, _)| name == *n)
+                .map(|(_, v)| *v)
+                .ok_or(DecodeError::InvalidJson(position))?
+        } else {
+            parser.integer::<i32>(field_name)?
+        };
+        T::try_from(value).map_err(|_| DecodeError::ValueOutOfRange(field_name))
+    }
+
+    fn is_default(value: &T) -> bool {
+        (*value).into() == 0
+    }
+}
+
+impl<const SIZE: usize> JsonCodec<heapless::String<SIZE>> for String {
+    fn encode_json(buffer: &mut impl Writer, value: &heapless::String<SIZE>) -> Result<usize, WriterError> {
+        string(buffer, value)
+    }
+
+    fn decode_json(parser: &mut JsonParser, field_name: &'static str) -> Result<heapless::String<SIZE>, DecodeError> {
+        parser.string()?.to_string(field_name)
+    }
+
+    fn is_default(value: &heapless::String<SIZE>) -> bool {
+        value.is_empty()
+    }
+}
+
+impl<const SIZE: usize> JsonCodec<heapless::Vec<u8, SIZE>> for Bytes {
+    fn encode_json(buffer: &mut impl Writer, value: &heapless::Vec<u8, SIZE>) -> Result<usize, WriterError> {
+        base64(buffer, value)
+    }
+
+    fn decode_json(parser: &mut JsonParser, field_name: &'static str) -> Result<heapless::Vec<u8, SIZE>, DecodeError> {
+        let mut result = heapless::Vec::new();
+        decode_base64(&parser.string()?, |byte| result.push(byte).map_err(|_| DecodeError::FieldOverflow(field_name)))?;
+        Ok(result)
+    }
+
+    fn is_default(value: &heapless::Vec<u8, SIZE>) -> bool {
+        value.is_empty()
+    }
+}
+
+impl<const SIZE: usize> JsonCodec<[u8; SIZE]> for Bytes {
+    fn encode_json(buffer: &mut impl Writer, value: &[u8; SIZE]) -> Result<usize, WriterError> {
+        base64(buffer, value)
+    }
+
+    fn decode_json(parser: &mut JsonParser, field_name: &'static str) -> Result<[u8; SIZE], DecodeError> {
+        let mut result = [0u8; SIZE];
+        let mut len = 0;
+        decode_base64(&parser.string()?, |byte| {
+            let slot = result.get_mut(len).ok_or(DecodeError::LengthMismatch(field_name))?;
+            *slot = byte;
+            len += 1;
+            Ok(())
+        })?;
+        // fixed size fields must match exactly
+        if len != SIZE {
+            return Err(DecodeError::LengthMismatch(field_name));
+        }
+        Ok(result)
+    }
+
+    // The proto default is empty bytes, which a fixed size field can't hold, so not even
+    // [0; SIZE] is left out. The binary encoding always writes it as well.
+    fn is_default(_value: &[u8; SIZE]) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode<C: JsonCodec<T>, T>(value: T) -> heapless::String<64> {
+        let mut buffer = [0u8; 64];
+        let len = C::encode_json(&mut buffer.as_mut(), &value).unwrap();
+        heapless::String::from(core::str::from_utf8(&buffer[..len]).unwrap())
+    }
+
+    fn decode<C: JsonCodec<T>, T>(json: &str) -> Result<T, DecodeError> {
+        let mut parser = JsonParser::new(json.as_bytes());
+        let value = C::decode_json(&mut parser, "field")?;
+        parser.end()?;
+        Ok(value)
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(encode::<Int32, _>(-5), "-5");
+        assert_eq!(encode::<Int64, _>(i64::MIN), "\"-9223372036854775808\"");
+        assert_eq!(encode::<Fixed64, _>(u64::MAX), "\"18446744073709551615\"");
+        assert_eq!(decode::<Int32, i32>("\"-5\""), Ok(-5));
+        assert_eq!(decode::<UInt64, u64>("18446744073709551615"), Ok(u64::MAX));
+        assert_eq!(decode::<Int32, i32>("1e3"), Ok(1000));
+        assert_eq!(decode::<Int32, i32>("1.5"), Err(DecodeError::InvalidJson(0)));
+        assert_eq!(decode::<Int32, i32>("2147483648"), Err(DecodeError::ValueOutOfRange("field")));
+        assert_eq!(decode::<UInt32, u8>("256"), Err(DecodeError::ValueOutOfRange("field")));
+        assert_eq!(decode::<Int32, i32>("01"), Err(DecodeError::InvalidJson(1)));
+        assert_eq!(decode::<Int64, i64>("\" 1\""), Err(DecodeError::InvalidJson(0)));
+    }
+
+    #[test]
+    fn test_floats() {
+        assert_eq!(encode::<Float, _>(0.1), "0.1");
+        assert_eq!(encode::<Double, _>(-2.5), "-2.5");
+        assert_eq!(encode::<Double, _>(f64::NAN), "\"NaN\"");
+        assert_eq!(encode::<Float, _>(f32::NEG_INFINITY), "\"-Infinity\"");
+        assert_eq!(decode::<Float, f32>("0.1"), Ok(0.1));
+        assert_eq!(decode::<Double, f64>("\"Infinity\""), Ok(f64::INFINITY));
+        assert_eq!(decode::<Double, f64>("\"1.5\""), Ok(1.5));
+        assert!(decode::<Double, f64>("\"NaN\"").unwrap().is_nan());
+        assert_eq!(decode::<Float, f32>("1e39"), Err(DecodeError::ValueOutOfRange("field")));
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(encode::<String, _>(heapless::String::<16>::from("a\"b\\\n\u{1}🐉")), "\"a\\\"b\\\\\\n\\u0001🐉\"");
+        assert_eq!(decode::<String, heapless::String<16>>("\"a\\\"\\u00e9\\ud83d\\udc09\""), Ok(heapless::String::from("a\"é🐉")));
+        // unpaired surrogates
+        assert_eq!(decode::<String, heapless::String<16>>("\"\\ud83d\\u0041\\udc09\\ud83d\""), Ok(heapless::String::from("\u{fffd}A\u{fffd}\u{fffd}")));
+        assert_eq!(decode::<String, heapless::String<2>>("\"abc\""), Err(DecodeError::FieldOverflow("field")));
+        assert_eq!(decode::<String, heapless::String<2>>("\"a\\x\""), Err(DecodeError::InvalidJson(2)));
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(encode::<Bytes, _>(heapless::Vec::<u8, 8>::from_slice(b"f").unwrap()), "\"Zg==\"");
+        assert_eq!(encode::<Bytes, _>(heapless::Vec::<u8, 8>::from_slice(b"fo").unwrap()), "\"Zm8=\"");
+        assert_eq!(encode::<Bytes, _>(*b"foo"), "\"Zm9v\"");
+        assert_eq!(decode::<Bytes, heapless::Vec<u8, 8>>("\"Zm8=\""), Ok(heapless::Vec::from_slice(b"fo").unwrap()));
+        assert_eq!(decode::<Bytes, heapless::Vec<u8, 8>>("\"-_8\""), Ok(heapless::Vec::from_slice(&[0xfb, 0xff]).unwrap()));
+        assert_eq!(decode::<Bytes, [u8; 2]>("\"Zm9v\""), Err(DecodeError::LengthMismatch("field")));
+        assert!(!<Bytes as JsonCodec<[u8; 2]>>::is_default(&[0; 2]));
+        assert_eq!(decode::<Bytes, heapless::Vec<u8, 8>>("\"Zm=8\""), Err(DecodeError::InvalidJson(0)));
+    }
+
+    #[test]
+    fn test_skip() {
+        let mut parser = JsonParser::new(b" {\"a\": [1, {\"b\": null}, \"c\"], \"d\": true} ");
+        parser.skip(RECURSION_LIMIT).unwrap();
+        parser.end().unwrap();
+
+        let mut parser = JsonParser::new(b"[[[1]]]");
+        assert_eq!(parser.skip(2), Err(DecodeError::RecursionLimit));
+    }
+}
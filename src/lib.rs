@@ -1,13 +1,32 @@
 #![no_std]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+// the derive macros refer to ::twpb, also when used in this crate (see wkt)
+extern crate self as twpb;
+
 // re-exporting whole modules
 pub mod iterators;
 pub mod wiretypes;
 pub mod encoder;
 pub mod decoder;
+pub mod codec;
+pub mod fieldnumbers;
+pub mod json;
+pub mod text;
+pub mod reflect;
+pub mod mask;
+pub mod schema;
+pub mod wkt;
+#[cfg(feature = "alloc")]
+pub mod dynamic;
 pub mod traits;
 
 // re-exporting specific pieces of modules for convenient shorter-hand access
 pub use crate::iterators::LimitedIterator;
 pub use crate::wiretypes::wire_types;
 pub use crate::decoder::DecodeError;
+pub use crate::codec::ScalarCodec;
+pub use crate::json::{JsonEncoder, JsonDecoder};
+pub use crate::text::{TextEncoder, TextDecoder};
+pub use crate::reflect::Reflect;
 pub use crate::traits::*;
\ No newline at end of file
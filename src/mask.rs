@@ -0,0 +1,211 @@
+// Partial encoding and merging of #[twpb(reflect)] messages, selected by a mask of field number paths.
+// A path selects a field, e.g. `&[3, 2]` is field 2 of the message in field 3,
+// a path ending at a message field selects that whole message:
+//   let mask: &[&[u32]] = &[&[1], &[3, 2]];
+//   mask::encode(&mut buffer, &settings, mask)?;           // on one side
+//   mask::merge(&mut current, &Settings::twpb_decode(..)?, mask)?;  // on the other
+// Selected fields are always written, also at their default value, so the message they're decoded
+// into has the value to merge. Codec fields can't be written from their reflected value, `check` and `merge`
+// reject paths naming them and `encode` leaves them out, also when the message holding them is selected.
+// Fixed size arrays must have all their values when decoded, select them to send a message that has them.
+use crate::codec::{self, ScalarCodec};
+use crate::encoder;
+use crate::iterators::NullCounterBuffer;
+use crate::reflect::{FieldDescriptor, Label, MessageDescriptor, ProtoType, Reflect, ReflectError, Value, EMPTY};
+use crate::traits::{Writer, WriterError};
+use crate::wiretypes::wire_types;
+
+// The selected fields of the message at some depth in the mask.
+#[derive(Copy, Clone)]
+enum Selection<'a> {
+    // every field, the message itself is selected
+    All,
+    // the fields of the paths starting with the path to this message
+    Paths(&'a [&'a [u32]], &'a [u32]),
+}
+
+impl<'a> Selection<'a> {
+    // The selection within field `number`, None if nothing in it is selected.
+    fn field(self, number: u32) -> Option<Selection<'a>> {
+        match self {
+            Selection::All => Some(Selection::All),
+            Selection::Paths(paths, prefix) => {
+                let depth = prefix.len();
+                let mut nested = None;
+                for path in paths.iter().filter(|path| path.len() > depth && path.starts_with(prefix) && path[depth] == number) {
+                    if path.len() == depth + 1 {
+                        return Some(Selection::All);
+                    }
+                    nested = Some(Selection::Paths(paths, &path[..=depth]));
+                }
+                nested
+            },
+        }
+    }
+
+    // Fields of the message in the field are selected, not the field itself.
+    fn is_nested(self) -> bool {
+        matches!(self, Selection::Paths(..))
+    }
+}
+
+// Whether every path leads to a field, through message fields only, and none to a codec field.
+pub fn check(descriptor: &'static MessageDescriptor, mask: &[&[u32]]) -> Result<(), ReflectError> {
+    for path in mask {
+        let mut descriptor = descriptor;
+        for (depth, &number) in path.iter().enumerate() {
+            let field = descriptor.field(number).ok_or(ReflectError::UnknownField(number))?;
+            if field.proto_type == ProtoType::Custom {
+                return Err(ReflectError::CustomCodec(field.name));
+            }
+            if depth + 1 < path.len() {
+                descriptor = match field.message_descriptor() {
+                    Some(message) if field.proto_type == ProtoType::Message => message,
+                    _ => return Err(ReflectError::TypeMismatch(field.name)),
+                };
+            }
+        }
+    }
+    Ok(())
+}
+
+// Write the fields of `message` selected by `mask`, paths that aren't fields of it are ignored.
+// Codec fields are left out, see `check` to reject a mask naming them.
+pub fn encode(buffer: &mut impl Writer, message: &dyn Reflect, mask: &[&[u32]]) -> Result<usize, WriterError> {
+    encode_message(buffer, message, Selection::Paths(mask, &[]))
+}
+
+// The amount of bytes `encode` would write.
+pub fn encoded_len(message: &dyn Reflect, mask: &[&[u32]]) -> usize {
+    encode(&mut NullCounterBuffer::new(), message, mask).unwrap_or(0)
+}
+
+fn encode_message(buffer: &mut impl Writer, message: &dyn Reflect, selection: Selection) -> Result<usize, WriterError> {
+    let mut result = Ok(0);
+    message.descriptor().for_each_field(|field| {
+        if let (Ok(bytes_written), Some(selection)) = (&mut result, selection.field(field.number)) {
+            match encode_field(buffer, message, field, selection) {
+                Ok(n) => *bytes_written += n,
+                Err(e) => result = Err(e),
+            }
+        }
+    });
+    result
+}
+
+fn encode_field(buffer: &mut impl Writer, message: &dyn Reflect, field: &FieldDescriptor, selection: Selection) -> Result<usize, WriterError> {
+    // a path going on past a scalar
+    if selection.is_nested() && field.proto_type != ProtoType::Message {
+        return Ok(0);
+    }
+    if field.label != Label::Repeated {
+        return match message.get(field.number) {
+            Some(value) => encode_value(buffer, field, value, selection),
+            None => Ok(0),
+        };
+    }
+    // non-packed, like derived messages
+    let mut bytes_written = 0;
+    for index in 0..message.repeated_len(field.number) {
+        if let Some(value) = message.get_index(field.number, index) {
+            bytes_written += encode_value(buffer, field, value, selection)?;
+        }
+    }
+    Ok(bytes_written)
+}
+
+fn encode_value(buffer: &mut impl Writer, field: &FieldDescriptor, value: Value, selection: Selection) -> Result<usize, WriterError> {
+    fn scalar<C: ScalarCodec<T>, T>(buffer: &mut impl Writer, number: u32, value: T) -> Result<usize, WriterError> {
+        codec::encode_field::<C, T>(buffer, number, &value)
+    }
+    fn length_delimited(buffer: &mut impl Writer, number: u32, len: usize) -> Result<usize, WriterError> {
+        let bytes_written = encoder::tag(buffer, &number, &wire_types::LENGTHDELIMITED)?;
+        Ok(bytes_written + encoder::leb128_u32(buffer, &(len as u32))?)
+    }
+
+    let number = field.number;
+    match (field.proto_type, value) {
+        (ProtoType::Int32 | ProtoType::Enum, Value::Int32(v) | Value::Enum(v)) => scalar::<codec::Int32, _>(buffer, number, v),
+        (ProtoType::SInt32, Value::Int32(v)) => scalar::<codec::SInt32, _>(buffer, number, v),
+        (ProtoType::SFixed32, Value::Int32(v)) => scalar::<codec::SFixed32, _>(buffer, number, v),
+        (ProtoType::Int64, Value::Int64(v)) => scalar::<codec::Int64, _>(buffer, number, v),
+        (ProtoType::SInt64, Value::Int64(v)) => scalar::<codec::SInt64, _>(buffer, number, v),
+        (ProtoType::SFixed64, Value::Int64(v)) => scalar::<codec::SFixed64, _>(buffer, number, v),
+        (ProtoType::UInt32, Value::UInt32(v)) => scalar::<codec::UInt32, _>(buffer, number, v),
+        (ProtoType::Fixed32, Value::UInt32(v)) => scalar::<codec::Fixed32, _>(buffer, number, v),
+        (ProtoType::UInt64, Value::UInt64(v)) => scalar::<codec::UInt64, _>(buffer, number, v),
+        (ProtoType::Fixed64, Value::UInt64(v)) => scalar::<codec::Fixed64, _>(buffer, number, v),
+        (ProtoType::Float, Value::Float(v)) => scalar::<codec::Float, _>(buffer, number, v),
+        (ProtoType::Double, Value::Double(v)) => scalar::<codec::Double, _>(buffer, number, v),
+        (ProtoType::Bool, Value::Bool(v)) => scalar::<codec::Bool, _>(buffer, number, v),
+        (ProtoType::String, Value::String(v)) => {
+            Ok(length_delimited(buffer, number, v.len())? + buffer.write_all(v.as_bytes())?)
+        },
+        (ProtoType::Bytes, Value::Bytes(v)) => Ok(length_delimited(buffer, number, v.len())? + buffer.write_all(v)?),
+        (ProtoType::Message, Value::Message(message)) => {
+            let len = encode_message(&mut NullCounterBuffer::new(), message, selection)?;
+            Ok(length_delimited(buffer, number, len)? + encode_message(buffer, message, selection)?)
+        },
+        _ => Ok(0),
+    }
+}
+
+// Copy the fields selected by `mask` from `source` into `target`, both messages of the same type.
+// Like proto FieldMask updates, a selected field that's unset in `source` is cleared in `target`,
+// and a path ending at a message replaces it. Fails on paths that aren't fields, see `check`.
+// On failure, `target` keeps the fields merged until then.
+pub fn merge(target: &mut dyn Reflect, source: &dyn Reflect, mask: &[&[u32]]) -> Result<(), ReflectError> {
+    check(target.descriptor(), mask)?;
+    merge_message(target, Some(source), Selection::Paths(mask, &[]))
+}
+
+// A `source` of None is a message with default values, those of the selected fields are cleared.
+fn merge_message(target: &mut dyn Reflect, source: Option<&dyn Reflect>, selection: Selection) -> Result<(), ReflectError> {
+    let mut result = Ok(());
+    target.descriptor().for_each_field(|field| {
+        if let (Ok(()), Some(selection)) = (&result, selection.field(field.number)) {
+            result = merge_field(target, source, field, selection);
+        }
+    });
+    result
+}
+
+fn merge_field(target: &mut dyn Reflect, source: Option<&dyn Reflect>, field: &FieldDescriptor, selection: Selection) -> Result<(), ReflectError> {
+    let number = field.number;
+    if field.label == Label::Repeated {
+        let len = source.map_or(0, |source| source.repeated_len(number));
+        for index in 0..len {
+            if let Some(value) = source.and_then(|source| source.get_index(number, index)) {
+                target.set_index(number, index, value)?;
+            }
+        }
+        return match target.truncate(number, len) {
+            // a fixed size array can't be emptied, its values are reset instead
+            Err(ReflectError::FixedSize(_)) if len == 0 => target.clear(number),
+            result => result,
+        };
+    }
+
+    let value = source.and_then(|source| source.get(number));
+    let is_unit = field.message_descriptor().is_some_and(|message| core::ptr::eq(message, &EMPTY));
+    match value {
+        Some(Value::Message(message)) if !is_unit => {
+            // a whole message is replaced, otherwise the selected fields are merged into it
+            if !selection.is_nested() {
+                target.clear(number)?;
+            }
+            let nested = target.get_mut(number).ok_or(ReflectError::AllocationFailed(field.name))?;
+            merge_message(nested, Some(message), selection)
+        },
+        Some(value) => target.set(number, value),
+        // the selected fields in a message source doesn't have
+        None if selection.is_nested() => match target.get(number) {
+            Some(Value::Message(_)) => {
+                let nested = target.get_mut(number).ok_or(ReflectError::AllocationFailed(field.name))?;
+                merge_message(nested, None, selection)
+            },
+            _ => Ok(()),
+        },
+        None => target.clear(number),
+    }
+}
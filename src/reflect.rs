@@ -0,0 +1,450 @@
+// Static descriptions of derived messages, and access to their fields by number,
+// for generic code like loggers, configuration UIs and command line tools.
+//
+// Every message deriving `Message` has a `DESCRIPTOR: &'static MessageDescriptor` constant,
+// every oneof enum deriving `Enum` a `DESCRIPTOR: &'static OneofDescriptor`
+// and every proto enum a `DESCRIPTOR: &'static EnumDescriptor`.
+// Messages with `#[twpb(reflect)]` also implement `Reflect`, reading and writing fields as `Value`s.
+// Scalar fields go through `ReflectCodec`, implemented by the same marker types as `ScalarCodec`.
+use defmt::Format;
+
+use crate::codec::{Bool, Bytes, Double, Enum, Fixed32, Fixed64, Float, Int32, Int64,
+    SFixed32, SFixed64, SInt32, SInt64, String, UInt32, UInt64};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Format)]
+pub enum ProtoType {
+    Int32,
+    Int64,
+    UInt32,
+    UInt64,
+    SInt32,
+    SInt64,
+    Fixed32,
+    Fixed64,
+    SFixed32,
+    SFixed64,
+    Float,
+    Double,
+    Bool,
+    String,
+    Bytes,
+    Enum,
+    Message,
+    // a #[twpb(codec = "..")] field, its encoding is up to the codec
+    Custom,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Format)]
+pub enum Label {
+    Singular,
+    Repeated,
+    // a variant of a oneof, at most one of them is set
+    Oneof,
+}
+
+#[derive(Debug)]
+pub struct FieldDescriptor {
+    // the name in the proto file
+    pub name: &'static str,
+    // the number the field is encoded with
+    pub number: u32,
+    pub proto_type: ProtoType,
+    pub label: Label,
+    // the descriptor of message fields, a function so recursive messages can refer to themselves
+    pub message: Option<fn() -> &'static MessageDescriptor>,
+    // the descriptor of enum fields
+    pub enumeration: Option<fn() -> &'static EnumDescriptor>,
+    // the name of the Rust type of enum and message fields, without its path
+    pub type_name: &'static str,
+}
+
+impl FieldDescriptor {
+    pub fn message_descriptor(&self) -> Option<&'static MessageDescriptor> {
+        self.message.map(|descriptor| descriptor())
+    }
+
+    pub fn enum_descriptor(&self) -> Option<&'static EnumDescriptor> {
+        self.enumeration.map(|descriptor| descriptor())
+    }
+}
+
+#[derive(Debug)]
+pub struct MessageDescriptor {
+    // the name in the proto file, that of the Rust type unless overridden,
+    // e.g. `Request.EmptyRequest` for a message nested in another one
+    pub name: &'static str,
+    // the proto package, empty without one
+    pub package: &'static str,
+    // singular and repeated fields
+    pub fields: &'static [FieldDescriptor],
+    // oneof fields, by field name
+    pub oneofs: &'static [(&'static str, &'static OneofDescriptor)],
+    // flattened messages, their fields are fields of this message
+    pub flattened: &'static [&'static MessageDescriptor],
+}
+
+#[derive(Debug)]
+pub struct OneofDescriptor {
+    // the name of the Rust enum
+    pub name: &'static str,
+    pub fields: &'static [FieldDescriptor],
+    // oneofs nested in a variant, their variants are variants of this oneof
+    pub nested: &'static [&'static OneofDescriptor],
+}
+
+// A message without fields, for unit variants of a oneof.
+pub static EMPTY: MessageDescriptor = MessageDescriptor { name: "", package: "", fields: &[], oneofs: &[], flattened: &[] };
+
+#[derive(Debug)]
+pub struct EnumDescriptor {
+    // the name in the proto file, like that of a message
+    pub name: &'static str,
+    pub package: &'static str,
+    // the names and numbers of the values, see `EnumNames`
+    pub values: &'static [(&'static str, i32)],
+}
+
+impl MessageDescriptor {
+    // All fields, those of the message itself first, then its oneofs and flattened messages.
+    pub fn for_each_field(&'static self, mut f: impl FnMut(&'static FieldDescriptor)) {
+        self.find(&mut |field| {
+            f(field);
+            false
+        });
+    }
+
+    // The field encoded with `number`.
+    pub fn field(&'static self, number: u32) -> Option<&'static FieldDescriptor> {
+        self.find(&mut |field| field.number == number)
+    }
+
+    pub fn field_by_name(&'static self, name: &str) -> Option<&'static FieldDescriptor> {
+        self.find(&mut |field| field.name == name)
+    }
+
+    fn find(&'static self, matches: &mut dyn FnMut(&'static FieldDescriptor) -> bool) -> Option<&'static FieldDescriptor> {
+        if let Some(field) = self.fields.iter().find(|&field| matches(field)) {
+            return Some(field);
+        }
+        if let Some(field) = self.oneofs.iter().find_map(|(_, oneof)| oneof.find(matches)) {
+            return Some(field);
+        }
+        self.flattened.iter().find_map(|message| message.find(matches))
+    }
+}
+
+impl OneofDescriptor {
+    fn find(&'static self, matches: &mut dyn FnMut(&'static FieldDescriptor) -> bool) -> Option<&'static FieldDescriptor> {
+        if let Some(field) = self.fields.iter().find(|&field| matches(field)) {
+            return Some(field);
+        }
+        self.nested.iter().find_map(|oneof| oneof.find(matches))
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Format)]
+pub enum ReflectError {
+    // not a field of the message, or not one that can be used this way,
+    // e.g. `set` on a repeated field
+    UnknownField(u32),
+    // the value is of another type than the field
+    TypeMismatch(&'static str),
+    ValueOutOfRange(&'static str),
+    // too long for a string, bytes or repeated field
+    FieldOverflow(&'static str),
+    // past the end of a repeated field
+    IndexOutOfRange(&'static str),
+    // a fixed size array can't change its length
+    FixedSize(&'static str),
+    // no room in the pool for a boxed message
+    AllocationFailed(&'static str),
+    // a #[twpb(codec = "..")] field where it can't be used, e.g. in a mask
+    CustomCodec(&'static str),
+}
+
+// The value of a field, in the Rust type of its proto type.
+// Signed integers are `Int32` and `Int64`, whether int, sint or sfixed.
+#[derive(Copy, Clone)]
+pub enum Value<'a> {
+    Int32(i32),
+    Int64(i64),
+    UInt32(u32),
+    UInt64(u64),
+    Float(f32),
+    Double(f64),
+    Bool(bool),
+    Enum(i32),
+    String(&'a str),
+    Bytes(&'a [u8]),
+    Message(&'a dyn Reflect),
+}
+
+impl core::fmt::Debug for Value<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Value::Int32(v) => f.debug_tuple("Int32").field(v).finish(),
+            Value::Int64(v) => f.debug_tuple("Int64").field(v).finish(),
+            Value::UInt32(v) => f.debug_tuple("UInt32").field(v).finish(),
+            Value::UInt64(v) => f.debug_tuple("UInt64").field(v).finish(),
+            Value::Float(v) => f.debug_tuple("Float").field(v).finish(),
+            Value::Double(v) => f.debug_tuple("Double").field(v).finish(),
+            Value::Bool(v) => f.debug_tuple("Bool").field(v).finish(),
+            Value::Enum(v) => f.debug_tuple("Enum").field(v).finish(),
+            Value::String(v) => f.debug_tuple("String").field(v).finish(),
+            Value::Bytes(v) => f.debug_tuple("Bytes").field(v).finish(),
+            Value::Message(v) => f.debug_tuple("Message").field(&v.descriptor().name).finish(),
+        }
+    }
+}
+
+// Messages are equal if they are the same one.
+impl PartialEq for Value<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int32(a), Value::Int32(b)) => a == b,
+            (Value::Int64(a), Value::Int64(b)) => a == b,
+            (Value::UInt32(a), Value::UInt32(b)) => a == b,
+            (Value::UInt64(a), Value::UInt64(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Double(a), Value::Double(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Enum(a), Value::Enum(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bytes(a), Value::Bytes(b)) => a == b,
+            (Value::Message(a), Value::Message(b)) => core::ptr::addr_eq(*a, *b),
+            _ => false,
+        }
+    }
+}
+
+// Fields by number, see `MessageDescriptor` for the numbers and types.
+pub trait Reflect {
+    fn descriptor(&self) -> &'static MessageDescriptor;
+
+    // The value of a singular field, or of the selected oneof variant.
+    // None for unset messages and oneof variants, and for repeated fields.
+    fn get(&self, number: u32) -> Option<Value<'_>>;
+
+    // Set a singular scalar field, or select a oneof variant.
+    // Messages are changed in place through `get_mut`, except unit variants of a oneof
+    // which have no fields, those are selected by setting them to any `Value::Message`.
+    fn set(&mut self, number: u32, value: Value) -> Result<(), ReflectError>;
+
+    // A message field or variant to change, set to its default value first if it was unset.
+    // None if it can't be allocated, or isn't a message field.
+    fn get_mut(&mut self, number: u32) -> Option<&mut dyn Reflect>;
+
+    // The amount of values in a repeated field, 0 for other fields.
+    fn repeated_len(&self, number: u32) -> usize;
+
+    // One value of a repeated field.
+    fn get_index(&self, number: u32, index: usize) -> Option<Value<'_>>;
+
+    // Replace one value of a repeated field, or add one if `index` is its length.
+    fn set_index(&mut self, number: u32, index: usize, value: Value) -> Result<(), ReflectError>;
+
+    // Remove the values of a repeated field from `len` on, fixed size arrays can't be shortened.
+    fn truncate(&mut self, number: u32, len: usize) -> Result<(), ReflectError>;
+
+    // Reset a field to its default: unset messages, no values in repeated fields and default scalars.
+    // A oneof variant is only cleared if it is the selected one. Fixed size arrays get default values.
+    fn clear(&mut self, number: u32) -> Result<(), ReflectError>;
+}
+
+// The empty message of a unit variant.
+impl Reflect for () {
+    fn descriptor(&self) -> &'static MessageDescriptor {
+        &EMPTY
+    }
+
+    fn get(&self, _number: u32) -> Option<Value<'_>> {
+        None
+    }
+
+    fn set(&mut self, number: u32, _value: Value) -> Result<(), ReflectError> {
+        Err(ReflectError::UnknownField(number))
+    }
+
+    fn get_mut(&mut self, _number: u32) -> Option<&mut dyn Reflect> {
+        None
+    }
+
+    fn repeated_len(&self, _number: u32) -> usize {
+        0
+    }
+
+    fn get_index(&self, _number: u32, _index: usize) -> Option<Value<'_>> {
+        None
+    }
+
+    fn set_index(&mut self, number: u32, _index: usize, _value: Value) -> Result<(), ReflectError> {
+        Err(ReflectError::UnknownField(number))
+    }
+
+    fn truncate(&mut self, number: u32, _len: usize) -> Result<(), ReflectError> {
+        Err(ReflectError::UnknownField(number))
+    }
+
+    fn clear(&mut self, number: u32) -> Result<(), ReflectError> {
+        Err(ReflectError::UnknownField(number))
+    }
+}
+
+pub trait ReflectCodec<T> {
+    fn to_value(value: &T) -> Value<'_>;
+
+    fn from_value(value: Value, field_name: &'static str) -> Result<T, ReflectError>;
+}
+
+macro_rules! reflect_scalar {
+    ($marker:ident, $variant:ident, $rust_type:ty) => {
+        impl ReflectCodec<$rust_type> for $marker {
+            fn to_value(value: &$rust_type) -> Value<'_> {
+                Value::$variant(*value)
+            }
+
+            fn from_value(value: Value, field_name: &'static str) -> Result<$rust_type, ReflectError> {
+                match value {
+                    Value::$variant(v) => Ok(v),
+                    _ => Err(ReflectError::TypeMismatch(field_name)),
+                }
+            }
+        }
+    };
+}
+
+reflect_scalar!(Int32, Int32, i32);
+reflect_scalar!(SInt32, Int32, i32);
+reflect_scalar!(SFixed32, Int32, i32);
+reflect_scalar!(UInt32, UInt32, u32);
+reflect_scalar!(Fixed32, UInt32, u32);
+reflect_scalar!(Int64, Int64, i64);
+reflect_scalar!(SInt64, Int64, i64);
+reflect_scalar!(SFixed64, Int64, i64);
+reflect_scalar!(UInt64, UInt64, u64);
+reflect_scalar!(Fixed64, UInt64, u64);
+reflect_scalar!(Float, Float, f32);
+reflect_scalar!(Double, Double, f64);
+reflect_scalar!(Bool, Bool, bool);
+
+// Narrower Rust integers for 32 bit proto types, range checked when set.
+macro_rules! reflect_narrow {
+    ($marker:ident, $variant:ident, $rust_type:ty) => {
+        impl ReflectCodec<$rust_type> for $marker {
+            fn to_value(value: &$rust_type) -> Value<'_> {
+                Value::$variant((*value).into())
+            }
+
+            fn from_value(value: Value, field_name: &'static str) -> Result<$rust_type, ReflectError> {
+                match value {
+                    Value::$variant(v) => <$rust_type>::try_from(v).map_err(|_| ReflectError::ValueOutOfRange(field_name)),
+                    _ => Err(ReflectError::TypeMismatch(field_name)),
+                }
+            }
+        }
+    };
+}
+
+reflect_narrow!(UInt32, UInt32, u8);
+reflect_narrow!(UInt32, UInt32, u16);
+reflect_narrow!(Int32, Int32, i8);
+reflect_narrow!(Int32, Int32, i16);
+reflect_narrow!(SInt32, Int32, i8);
+reflect_narrow!(SInt32, Int32, i16);
+
+impl<T> ReflectCodec<T> for Enum
+where T: Copy + Into<i32> + TryFrom<i32> {
+    fn to_value(value: &T) -> Value<'_> {
+        Value::Enum((*value).into())
+    }
+
+    fn from_value(value: Value, field_name: &'static str) -> Result<T, ReflectError> {
+        match value {
+            Value::Enum(v) => T::try_from(v).map_err(|_| ReflectError::ValueOutOfRange(field_name)),
+            _ => Err(ReflectError::TypeMismatch(field_name)),
+        }
+    }
+}
+
+impl<const SIZE: usize> ReflectCodec<heapless::String<SIZE>> for String {
+    fn to_value(value: &heapless::String<SIZE>) -> Value<'_> {
+        Value::String(value.as_str())
+    }
+
+    fn from_value(value: Value, field_name: &'static str) -> Result<heapless::String<SIZE>, ReflectError> {
+        match value {
+            Value::String(v) => {
+                let mut result = heapless::String::new();
+                result.push_str(v).map_err(|_| ReflectError::FieldOverflow(field_name))?;
+                Ok(result)
+            },
+            _ => Err(ReflectError::TypeMismatch(field_name)),
+        }
+    }
+}
+
+impl<const SIZE: usize> ReflectCodec<heapless::Vec<u8, SIZE>> for Bytes {
+    fn to_value(value: &heapless::Vec<u8, SIZE>) -> Value<'_> {
+        Value::Bytes(value)
+    }
+
+    fn from_value(value: Value, field_name: &'static str) -> Result<heapless::Vec<u8, SIZE>, ReflectError> {
+        match value {
+            Value::Bytes(v) => heapless::Vec::from_slice(v).map_err(|_| ReflectError::FieldOverflow(field_name)),
+            _ => Err(ReflectError::TypeMismatch(field_name)),
+        }
+    }
+}
+
+impl<const SIZE: usize> ReflectCodec<[u8; SIZE]> for Bytes {
+    fn to_value(value: &[u8; SIZE]) -> Value<'_> {
+        Value::Bytes(value)
+    }
+
+    // fixed size fields must match exactly
+    fn from_value(value: Value, field_name: &'static str) -> Result<[u8; SIZE], ReflectError> {
+        match value {
+            Value::Bytes(v) => v.try_into().map_err(|_| ReflectError::ValueOutOfRange(field_name)),
+            _ => Err(ReflectError::TypeMismatch(field_name)),
+        }
+    }
+}
+
+// `set_index` for a heapless::Vec field.
+pub fn set_index<T, const N: usize>(values: &mut heapless::Vec<T, N>, index: usize, value: T, field_name: &'static str) -> Result<(), ReflectError> {
+    if index == values.len() {
+        values.push(value).map_err(|_| ReflectError::FieldOverflow(field_name))
+    } else {
+        let slot = values.get_mut(index).ok_or(ReflectError::IndexOutOfRange(field_name))?;
+        *slot = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codecs() {
+        assert_eq!(<SInt32 as ReflectCodec<i16>>::to_value(&-3), Value::Int32(-3));
+        assert_eq!(<UInt32 as ReflectCodec<u8>>::from_value(Value::UInt32(255), "f"), Ok(255));
+        assert_eq!(<UInt32 as ReflectCodec<u8>>::from_value(Value::UInt32(256), "f"), Err(ReflectError::ValueOutOfRange("f")));
+        assert_eq!(<Fixed64 as ReflectCodec<u64>>::from_value(Value::Int64(1), "f"), Err(ReflectError::TypeMismatch("f")));
+        assert_eq!(<Bytes as ReflectCodec<heapless::Vec<u8, 2>>>::from_value(Value::Bytes(&[1, 2, 3]), "f"),
+            Err(ReflectError::FieldOverflow("f")));
+
+        let mut values: heapless::Vec<u8, 2> = heapless::Vec::new();
+        set_index(&mut values, 0, 1, "f").unwrap();
+        set_index(&mut values, 0, 2, "f").unwrap();
+        assert_eq!(set_index(&mut values, 2, 3, "f"), Err(ReflectError::IndexOutOfRange("f")));
+        assert_eq!(values, [2]);
+    }
+
+    #[test]
+    fn test_value_eq() {
+        assert_ne!(Value::Int32(1), Value::Enum(1));
+        let a = ();
+        assert_eq!(Value::Message(&a), Value::Message(&a));
+    }
+}
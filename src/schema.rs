@@ -0,0 +1,305 @@
+// Writing the .proto file of derived messages, the reverse of generating code from it.
+// Messages are taken from their `DESCRIPTOR`, starting at the given roots and following message and enum fields,
+// so the whole message tree of a package ends up in one file:
+//   - messages, enums and fields have their proto name, see #[twpb(name = "..")]
+//   - messages and enums of other packages are referred to by their full name, they come from an import
+//   - nested messages and enums, e.g. `Request.EmptyRequest`, are written inside their parent,
+//     a parent that isn't in the file is written as an empty message holding them
+//   - flattened messages are written as fields of the message they're flattened into
+//   - nested oneofs are written as a single oneof, named after the oneof field
+//   - unit variants of a oneof are `google.protobuf.Empty`, which is what they are on the wire
+//   - repeated scalars are left packed, decoders accept both encodings, even though twpb writes them unpacked
+// Fields are written in the order of their numbers, messages and enums in the order they're first used.
+// Like in a .proto file, messages with the same name are the same message.
+// Codec fields can be anything, they're written as bytes with a comment to check them.
+use heapless::Vec;
+use crate::json::format;
+use crate::reflect::{EnumDescriptor, FieldDescriptor, Label, MessageDescriptor, OneofDescriptor, ProtoType, EMPTY};
+use crate::traits::*;
+
+// Limits of the no_std bookkeeping, the number of messages and enums in a file and fields in a message.
+pub const MAX_TYPES: usize = 64;
+pub const MAX_FIELDS: usize = 128;
+
+#[derive(Copy, Clone)]
+enum Entry {
+    Field(&'static FieldDescriptor),
+    Oneof(&'static str, &'static OneofDescriptor),
+}
+
+impl Entry {
+    fn number(&self) -> u32 {
+        match self {
+            Entry::Field(field) => field.number,
+            Entry::Oneof(_, oneof) => first_number(oneof),
+        }
+    }
+}
+
+fn first_number(oneof: &OneofDescriptor) -> u32 {
+    let fields = oneof.fields.iter().map(|field| field.number);
+    fields.chain(oneof.nested.iter().map(|nested| first_number(nested))).min().unwrap_or(u32::MAX)
+}
+
+// A message or enum written to the file.
+#[derive(Copy, Clone)]
+enum Type {
+    Message(&'static MessageDescriptor),
+    Enum(&'static EnumDescriptor),
+}
+
+impl Type {
+    fn name(&self) -> &'static str {
+        match self {
+            Type::Message(message) => message.name,
+            Type::Enum(enumeration) => enumeration.name,
+        }
+    }
+}
+
+// The name a message or enum is declared with, e.g. `EmptyRequest` of `Request.EmptyRequest`.
+fn last(name: &str) -> &str {
+    name.rsplit_once('.').map_or(name, |(_, last)| last)
+}
+
+// The messages and enums directly inside `scope`, the file if it's empty, in the order they're first used.
+// These are names of `types` or of the messages they're nested in, e.g. `Request` for `Request.EmptyRequest`.
+fn children(scope: &str, types: &[Type]) -> Result<Vec<&'static str, MAX_TYPES>, WriterError> {
+    let mut children: Vec<&'static str, MAX_TYPES> = Vec::new();
+    for known in types {
+        let name = known.name();
+        let rest = match scope {
+            "" => Some(name),
+            _ => name.strip_prefix(scope).and_then(|rest| rest.strip_prefix('.')),
+        };
+        if let Some(rest) = rest {
+            let child = &name[..name.len() - rest.len() + rest.find('.').unwrap_or(rest.len())];
+            if !children.contains(&child) {
+                children.push(child).map_err(|_| WriterError::BufferOverflow)?;
+            }
+        }
+    }
+    Ok(children)
+}
+
+// The fields and oneofs of a message and of the messages flattened into it, by number.
+fn entries(message: &'static MessageDescriptor) -> Result<Vec<Entry, MAX_FIELDS>, WriterError> {
+    fn add(message: &'static MessageDescriptor, entries: &mut Vec<Entry, MAX_FIELDS>) -> Result<(), WriterError> {
+        for field in message.fields {
+            entries.push(Entry::Field(field)).map_err(|_| WriterError::BufferOverflow)?;
+        }
+        for (name, oneof) in message.oneofs {
+            entries.push(Entry::Oneof(name, oneof)).map_err(|_| WriterError::BufferOverflow)?;
+        }
+        for flattened in message.flattened {
+            add(flattened, entries)?;
+        }
+        Ok(())
+    }
+    let mut entries = Vec::new();
+    add(message, &mut entries)?;
+    entries.sort_unstable_by_key(Entry::number);
+    Ok(entries)
+}
+
+// The variants of a oneof and of the oneofs nested in it, by number.
+fn variants(oneof: &'static OneofDescriptor) -> Result<Vec<&'static FieldDescriptor, MAX_FIELDS>, WriterError> {
+    fn add(oneof: &'static OneofDescriptor, variants: &mut Vec<&'static FieldDescriptor, MAX_FIELDS>) -> Result<(), WriterError> {
+        for field in oneof.fields {
+            variants.push(field).map_err(|_| WriterError::BufferOverflow)?;
+        }
+        for nested in oneof.nested {
+            add(nested, variants)?;
+        }
+        Ok(())
+    }
+    let mut variants = Vec::new();
+    add(oneof, &mut variants)?;
+    variants.sort_unstable_by_key(|field| field.number);
+    Ok(variants)
+}
+
+fn add(found: Type, types: &mut Vec<Type, MAX_TYPES>) -> Result<bool, WriterError> {
+    if types.iter().any(|known| known.name() == found.name()) {
+        return Ok(false);
+    }
+    types.push(found).map_err(|_| WriterError::BufferOverflow)?;
+    Ok(true)
+}
+
+// Depth first, so messages follow the message that uses them first. Those of other packages are left out.
+// Returns whether a unit variant was seen, which needs google/protobuf/empty.proto.
+// Messages are known by name, `DESCRIPTOR` is a const and doesn't have a single address.
+fn collect(message: &'static MessageDescriptor, package: &str, types: &mut Vec<Type, MAX_TYPES>) -> Result<bool, WriterError> {
+    if message.package != package || !add(Type::Message(message), types)? {
+        return Ok(false);
+    }
+    let mut uses_empty = false;
+    for entry in entries(message)? {
+        match entry {
+            Entry::Field(field) => uses_empty |= collect_field(field, package, types)?,
+            Entry::Oneof(_, oneof) => for variant in variants(oneof)? {
+                uses_empty |= collect_field(variant, package, types)?;
+            },
+        }
+    }
+    Ok(uses_empty)
+}
+
+fn collect_field(field: &'static FieldDescriptor, package: &str, types: &mut Vec<Type, MAX_TYPES>) -> Result<bool, WriterError> {
+    if let Some(enumeration) = field.enum_descriptor() {
+        if enumeration.package == package {
+            add(Type::Enum(enumeration), types)?;
+        }
+    }
+    match field.message_descriptor() {
+        Some(message) if core::ptr::eq(message, &EMPTY) => Ok(true),
+        Some(message) => collect(message, package, types),
+        None => Ok(false),
+    }
+}
+
+fn scalar_name(proto_type: ProtoType) -> &'static str {
+    match proto_type {
+        ProtoType::Int32 => "int32",
+        ProtoType::Int64 => "int64",
+        ProtoType::UInt32 => "uint32",
+        ProtoType::UInt64 => "uint64",
+        ProtoType::SInt32 => "sint32",
+        ProtoType::SInt64 => "sint64",
+        ProtoType::Fixed32 => "fixed32",
+        ProtoType::Fixed64 => "fixed64",
+        ProtoType::SFixed32 => "sfixed32",
+        ProtoType::SFixed64 => "sfixed64",
+        ProtoType::Float => "float",
+        ProtoType::Double => "double",
+        ProtoType::Bool => "bool",
+        ProtoType::String => "string",
+        ProtoType::Bytes | ProtoType::Custom => "bytes",
+        ProtoType::Enum | ProtoType::Message => "",
+    }
+}
+
+// The name of a message or enum as seen from inside the message `scope`,
+// e.g. `EmptyRequest` for `Request.EmptyRequest` used in `Request`, like protoc looks names up.
+fn relative<'a>(name: &'a str, mut scope: &str) -> &'a str {
+    loop {
+        if let Some(rest) = name.strip_prefix(scope).and_then(|rest| rest.strip_prefix('.')) {
+            return rest;
+        }
+        match scope.rsplit_once('.') {
+            Some((outer, _)) => scope = outer,
+            None => return name,
+        }
+    }
+}
+
+// Writes the name of a message or enum used by a field of `scope`, in full if it's of another package.
+fn type_name(buffer: &mut impl Writer, name: &str, package: &str, scope: &MessageDescriptor) -> Result<usize, WriterError> {
+    if package == scope.package {
+        buffer.write_all(relative(name, scope.name).as_bytes())
+    } else if package.is_empty() {
+        format(buffer, format_args!(".{}", name))
+    } else {
+        format(buffer, format_args!("{}.{}", package, name))
+    }
+}
+
+fn field(buffer: &mut impl Writer, field: &FieldDescriptor, scope: &MessageDescriptor, indent: usize) -> Result<usize, WriterError> {
+    let label = if field.label == Label::Repeated { "repeated " } else { "" };
+    let mut bytes_written = format(buffer, format_args!("{:2$}{}", "", label, indent))?;
+    bytes_written += match (field.proto_type, field.message_descriptor(), field.enum_descriptor()) {
+        (ProtoType::Message, Some(message), _) if core::ptr::eq(message, &EMPTY) => buffer.write_all(b"google.protobuf.Empty")?,
+        (ProtoType::Message, Some(message), _) => type_name(buffer, message.name, message.package, scope)?,
+        (ProtoType::Enum, _, Some(enumeration)) => type_name(buffer, enumeration.name, enumeration.package, scope)?,
+        (ProtoType::Message | ProtoType::Enum, _, _) => buffer.write_all(field.type_name.as_bytes())?,
+        (proto_type, _, _) => buffer.write_all(scalar_name(proto_type).as_bytes())?,
+    };
+    let comment = if field.proto_type == ProtoType::Custom { " // custom codec, check its encoding" } else { "" };
+    bytes_written += format(buffer, format_args!(" {} = {};{}\n", field.name, field.number, comment))?;
+    Ok(bytes_written)
+}
+
+// The messages and enums inside `scope`, top level ones separated by a blank line.
+// A message that only holds nested ones is written empty, with just those.
+fn write_children(buffer: &mut impl Writer, scope: &str, types: &[Type], indent: usize) -> Result<usize, WriterError> {
+    let mut bytes_written = 0;
+    for child in children(scope, types)? {
+        if indent == 0 {
+            bytes_written += buffer.write_all(b"\n")?;
+        }
+        bytes_written += match types.iter().find(|known| known.name() == child) {
+            Some(written) => write_type(buffer, *written, types, indent)?,
+            None => {
+                let mut shell = format(buffer, format_args!("{:2$}message {} {{\n", "", last(child), indent))?;
+                shell += write_children(buffer, child, types, indent + 2)?;
+                shell + format(buffer, format_args!("{:1$}}}\n", "", indent))?
+            },
+        };
+    }
+    Ok(bytes_written)
+}
+
+// A message or enum, preceded by the ones nested in it.
+fn write_type(buffer: &mut impl Writer, written: Type, types: &[Type], indent: usize) -> Result<usize, WriterError> {
+    let name = last(written.name());
+    let message = match written {
+        Type::Message(message) => message,
+        Type::Enum(enumeration) => {
+            let mut bytes_written = format(buffer, format_args!("{:2$}enum {} {{\n", "", name, indent))?;
+            for (value_name, value) in enumeration.values.iter() {
+                bytes_written += format(buffer, format_args!("{:3$}{} = {};\n", "", value_name, value, indent + 2))?;
+            }
+            bytes_written += format(buffer, format_args!("{:1$}}}\n", "", indent))?;
+            return Ok(bytes_written);
+        },
+    };
+
+    let entries = entries(message)?;
+    if entries.is_empty() && children(message.name, types)?.is_empty() {
+        return format(buffer, format_args!("{:2$}message {} {{}}\n", "", name, indent));
+    }
+    let mut bytes_written = format(buffer, format_args!("{:2$}message {} {{\n", "", name, indent))?;
+    bytes_written += write_children(buffer, message.name, types, indent + 2)?;
+    for entry in entries {
+        match entry {
+            Entry::Field(descriptor) => bytes_written += field(buffer, descriptor, message, indent + 2)?,
+            Entry::Oneof(name, oneof) => {
+                bytes_written += format(buffer, format_args!("{:2$}oneof {} {{\n", "", name, indent + 2))?;
+                for variant in variants(oneof)? {
+                    bytes_written += field(buffer, variant, message, indent + 4)?;
+                }
+                bytes_written += format(buffer, format_args!("{:1$}}}\n", "", indent + 2))?;
+            },
+        }
+    }
+    bytes_written += format(buffer, format_args!("{:1$}}}\n", "", indent))?;
+    Ok(bytes_written)
+}
+
+// Write a proto3 file with the `roots` and all messages and enums of their package they use.
+// The package is that of the roots, `imports` are the files with the messages of other packages,
+// e.g. `&["api/v1/v1.proto"]`, google/protobuf/empty.proto is added when needed.
+pub fn write(buffer: &mut impl Writer, imports: &[&str], roots: &[&'static MessageDescriptor]) -> Result<usize, WriterError> {
+    let package = roots.first().map(|root| root.package).unwrap_or_default();
+    let mut types = Vec::new();
+    let mut uses_empty = false;
+    for root in roots {
+        uses_empty |= collect(root, package, &mut types)?;
+    }
+
+    let mut bytes_written = buffer.write_all(b"syntax = \"proto3\";\n")?;
+    if !package.is_empty() {
+        bytes_written += format(buffer, format_args!("\npackage {};\n", package))?;
+    }
+    let empty = "google/protobuf/empty.proto";
+    let uses_empty = uses_empty && !imports.contains(&empty);
+    if !imports.is_empty() || uses_empty {
+        bytes_written += buffer.write_all(b"\n")?;
+    }
+    for import in imports.iter().chain(uses_empty.then_some(&empty)) {
+        bytes_written += format(buffer, format_args!("import \"{}\";\n", import))?;
+    }
+    bytes_written += write_children(buffer, "", &types, 0)?;
+    Ok(bytes_written)
+}
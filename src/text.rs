@@ -0,0 +1,757 @@
+// Protobuf text format, e.g. `serial: "abc" content { ss { vendor: "x" } }`
+//
+// Messages deriving `Message` with `#[twpb(text)]` implement `TextEncoder` and `TextDecoder`.
+// Scalar fields go through `TextCodec`, implemented by the same marker types as `ScalarCodec`.
+// The printer writes everything on one line, leaves out fields at their default value,
+// writes repeated scalars as a list (`values: [1, 2]`) and enums by name, see `EnumNames`.
+// The parser also accepts repeated fields as separate entries, `<..>` for messages,
+// `,` or `;` after fields, `#` comments, hex and octal integers and enums by number.
+// Unlike binary and JSON decoding, unknown fields are an error, they are likely typos.
+use core::str::FromStr;
+
+use crate::codec::{Bool, Bytes, Double, Enum, Fixed32, Fixed64, Float, Int32, Int64,
+    SFixed32, SFixed64, SInt32, SInt64, String, UInt32, UInt64};
+use crate::decoder::{DecodeError, RECURSION_LIMIT};
+use crate::json::{format, EnumNames};
+use crate::traits::{Writer, WriterError};
+
+pub trait TextCodec<T> {
+    // Write the value as text, returns the amount of bytes written.
+    fn encode_text(buffer: &mut impl Writer, value: &T) -> Result<usize, WriterError>;
+
+    // Read a value, the field name and colon have been read already.
+    fn decode_text(parser: &mut TextParser, field_name: &'static str) -> Result<T, DecodeError>;
+
+    // Fields at their default value are not printed.
+    fn is_default(value: &T) -> bool;
+}
+
+pub trait TextEncoder {
+    // Write all fields, separated by spaces.
+    // `first` is whether nothing was written yet, otherwise a space comes first.
+    fn twpb_encode_text_fields(&self, buffer: &mut impl Writer, first: &mut bool) -> Result<usize, WriterError>;
+
+    fn twpb_encode_text(&self, buffer: &mut impl Writer) -> Result<usize, WriterError> {
+        self.twpb_encode_text_fields(buffer, &mut true)
+    }
+}
+
+pub trait TextDecoder: Sized {
+    fn twpb_decode_text(buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut parser = TextParser::new(buf);
+        let value = Self::twpb_decode_text_message(&mut parser, RECURSION_LIMIT, None)?;
+        parser.end()?;
+        Ok(value)
+    }
+
+    // Read fields until `close` (`}` or `>`), or the end of the input if None.
+    // `recursion_limit` is the maximum depth of embedded messages.
+    fn twpb_decode_text_message(parser: &mut TextParser, recursion_limit: u32, close: Option<u8>) -> Result<Self, DecodeError>;
+
+    // Read the field `name` into self, returns false if it is not a field of this message.
+    fn twpb_decode_text_field(&mut self, name: &str, parser: &mut TextParser, recursion_limit: u32) -> Result<bool, DecodeError>;
+}
+
+// An empty message, for oneof variants without a Rust type.
+impl TextEncoder for () {
+    fn twpb_encode_text_fields(&self, _buffer: &mut impl Writer, _first: &mut bool) -> Result<usize, WriterError> {
+        Ok(0)
+    }
+}
+
+impl TextDecoder for () {
+    fn twpb_decode_text_message(parser: &mut TextParser, _recursion_limit: u32, close: Option<u8>) -> Result<Self, DecodeError> {
+        parser.fields(close, |parser, name| Err(parser.unknown_field(name)))
+    }
+
+    fn twpb_decode_text_field(&mut self, _name: &str, _parser: &mut TextParser, _recursion_limit: u32) -> Result<bool, DecodeError> {
+        Ok(false)
+    }
+}
+
+// Write the field name, preceded by a space unless it's the first field.
+fn name(buffer: &mut impl Writer, first: &mut bool, name: &str) -> Result<usize, WriterError> {
+    let mut bytes_written = 0;
+    if !*first {
+        bytes_written += buffer.write_all(b" ")?;
+    }
+    *first = false;
+    bytes_written += buffer.write_all(name.as_bytes())?;
+    Ok(bytes_written)
+}
+
+// Write `name: `, the value of the scalar field comes next.
+pub fn field(buffer: &mut impl Writer, first: &mut bool, field_name: &str) -> Result<usize, WriterError> {
+    Ok(name(buffer, first, field_name)? + buffer.write_all(b": ")?)
+}
+
+// Write an embedded message as `name { fields }`.
+pub fn message(buffer: &mut impl Writer, first: &mut bool, field_name: &str, value: &impl TextEncoder) -> Result<usize, WriterError> {
+    let mut bytes_written = name(buffer, first, field_name)?;
+    bytes_written += buffer.write_all(b" {")?;
+    let len = value.twpb_encode_text_fields(buffer, &mut false)?;
+    bytes_written += len;
+    bytes_written += buffer.write_all(if len > 0 { b" }" } else { b"}" })?;
+    Ok(bytes_written)
+}
+
+// Write a quoted string, non-printable bytes are escaped.
+pub fn bytes(buffer: &mut impl Writer, value: &[u8]) -> Result<usize, WriterError> {
+    let mut bytes_written = buffer.write_all(b"\"")?;
+    for &byte in value {
+        bytes_written += escape(buffer, byte)?;
+    }
+    bytes_written += buffer.write_all(b"\"")?;
+    Ok(bytes_written)
+}
+
+// Same as `bytes`, but non-ASCII UTF-8 is kept readable.
+pub fn string(buffer: &mut impl Writer, value: &str) -> Result<usize, WriterError> {
+    let mut bytes_written = buffer.write_all(b"\"")?;
+    for c in value.chars() {
+        bytes_written += if c.is_ascii() {
+            escape(buffer, c as u8)?
+        } else {
+            buffer.write_all(c.encode_utf8(&mut [0; 4]).as_bytes())?
+        };
+    }
+    bytes_written += buffer.write_all(b"\"")?;
+    Ok(bytes_written)
+}
+
+fn escape(buffer: &mut impl Writer, byte: u8) -> Result<usize, WriterError> {
+    match byte {
+        b'"' => buffer.write_all(b"\\\""),
+        b'\\' => buffer.write_all(b"\\\\"),
+        b'\n' => buffer.write_all(b"\\n"),
+        b'\r' => buffer.write_all(b"\\r"),
+        b'\t' => buffer.write_all(b"\\t"),
+        0x20..=0x7e => buffer.write_all(&[byte]),
+        _ => format(buffer, format_args!("\\{:03o}", byte)),
+    }
+}
+
+fn float(buffer: &mut impl Writer, value: f64, text: core::fmt::Arguments) -> Result<usize, WriterError> {
+    if value.is_nan() {
+        buffer.write_all(b"nan")
+    } else if value == f64::INFINITY {
+        buffer.write_all(b"inf")
+    } else if value == f64::NEG_INFINITY {
+        buffer.write_all(b"-inf")
+    } else {
+        format(buffer, text)
+    }
+}
+
+// Reads text format from a byte slice, as needed by the derived `TextDecoder`s.
+// Errors are `DecodeError::InvalidText` with the offset where the input went wrong.
+pub struct TextParser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+// The contents of a quoted string, escape sequences are resolved by `bytes`.
+#[derive(Debug, Clone, Copy)]
+pub struct TextStr<'a> {
+    raw: &'a [u8],
+    // offset of the string in the input, for errors
+    position: usize,
+}
+
+impl<'a> TextStr<'a> {
+    pub fn bytes(&self) -> TextBytes<'a> {
+        TextBytes { raw: self.raw, pending: [0; 4], pending_len: 0 }
+    }
+
+    pub fn to_string<const SIZE: usize>(&self, field_name: &'static str) -> Result<heapless::String<SIZE>, DecodeError> {
+        let bytes = self.to_bytes::<SIZE>(field_name)?;
+        let s = core::str::from_utf8(&bytes).or(Err(DecodeError::InvalidText(self.position)))?;
+        Ok(heapless::String::from(s))
+    }
+
+    pub fn to_bytes<const SIZE: usize>(&self, field_name: &'static str) -> Result<heapless::Vec<u8, SIZE>, DecodeError> {
+        let mut result = heapless::Vec::new();
+        for byte in self.bytes() {
+            result.push(byte).map_err(|_| DecodeError::FieldOverflow(field_name))?;
+        }
+        Ok(result)
+    }
+}
+
+pub struct TextBytes<'a> {
+    raw: &'a [u8],
+    // the UTF-8 encoding of a \u escape, in reverse
+    pending: [u8; 4],
+    pending_len: usize,
+}
+
+impl TextBytes<'_> {
+    // Up to `max` digits in `radix`.
+    fn digits(&mut self, radix: u32, max: usize) -> u32 {
+        let mut value = 0;
+        for _ in 0..max {
+            match self.raw.first().and_then(|&b| (b as char).to_digit(radix)) {
+                Some(digit) => {
+                    value = value * radix + digit;
+                    self.raw = &self.raw[1..];
+                },
+                None => break,
+            }
+        }
+        value
+    }
+}
+
+impl Iterator for TextBytes<'_> {
+    type Item = u8;
+
+    // The parser checked the escape sequences, invalid code points become U+FFFD.
+    fn next(&mut self) -> Option<u8> {
+        if self.pending_len > 0 {
+            self.pending_len -= 1;
+            return Some(self.pending[self.pending_len]);
+        }
+        let (&byte, rest) = self.raw.split_first()?;
+        self.raw = rest;
+        if byte != b'\\' {
+            return Some(byte);
+        }
+        let (&escape, rest) = self.raw.split_first()?;
+        self.raw = rest;
+        Some(match escape {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'v' => 0x0b,
+            b'x' | b'X' => self.digits(16, 2) as u8,
+            b'0'..=b'7' => {
+                let mut value = (escape - b'0') as u32;
+                for _ in 0..2 {
+                    match self.raw.first() {
+                        Some(&digit @ b'0'..=b'7') => {
+                            value = value * 8 + (digit - b'0') as u32;
+                            self.raw = &self.raw[1..];
+                        },
+                        _ => break,
+                    }
+                }
+                value as u8
+            },
+            b'u' | b'U' => {
+                let c = self.digits(16, if escape == b'u' { 4 } else { 8 });
+                let c = char::from_u32(c).unwrap_or('\u{fffd}');
+                let mut utf8 = [0; 4];
+                let len = c.encode_utf8(&mut utf8).len();
+                self.pending[..len - 1].copy_from_slice(&utf8[1..len]);
+                self.pending[..len - 1].reverse();
+                self.pending_len = len - 1;
+                utf8[0]
+            },
+            // '"', '\'', '\\' and '?'
+            other => other,
+        })
+    }
+}
+
+impl<'a> TextParser<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        TextParser { input, position: 0 }
+    }
+
+    // The offset of the next byte to be read.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    fn error(&self) -> DecodeError {
+        DecodeError::InvalidText(self.position)
+    }
+
+    // Skips whitespace and comments.
+    fn peek(&mut self) -> Option<u8> {
+        loop {
+            match self.input.get(self.position) {
+                Some(b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c) => self.position += 1,
+                Some(b'#') => {
+                    while !matches!(self.input.get(self.position), Some(b'\n') | None) {
+                        self.position += 1;
+                    }
+                },
+                other => return other.copied(),
+            }
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), DecodeError> {
+        if self.peek() != Some(byte) {
+            return Err(self.error());
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    // Only whitespace and comments may follow the message.
+    pub fn end(&mut self) -> Result<(), DecodeError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error()),
+        }
+    }
+
+    // The colon between a scalar field name and its value.
+    pub fn colon(&mut self) -> Result<(), DecodeError> {
+        self.expect(b':')
+    }
+
+    // Messages may have a colon after their name, but don't need one.
+    pub fn optional_colon(&mut self) {
+        if self.peek() == Some(b':') {
+            self.position += 1;
+        }
+    }
+
+    // Reads fields until `close`, or the end of the input if None,
+    // calling `field` for every name, which must read the value.
+    pub fn fields<F>(&mut self, close: Option<u8>, mut field: F) -> Result<(), DecodeError>
+    where F: FnMut(&mut Self, &'a str) -> Result<(), DecodeError> {
+        loop {
+            match self.peek() {
+                next if next == close => {
+                    if close.is_some() {
+                        self.position += 1;
+                    }
+                    return Ok(());
+                },
+                None => return Err(self.error()),
+                Some(_) => (),
+            }
+            let start = self.position;
+            while self.input.get(self.position).is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_') {
+                self.position += 1;
+            }
+            if self.position == start {
+                return Err(self.error());
+            }
+            // only ASCII was consumed
+            let name = core::str::from_utf8(&self.input[start..self.position]).unwrap();
+            field(self, name)?;
+            if let Some(b',' | b';') = self.peek() {
+                self.position += 1;
+            }
+        }
+    }
+
+    // Reads a list `[a, b]`, or a single value, calling `item` for every value, which must read it.
+    pub fn list<F>(&mut self, mut item: F) -> Result<(), DecodeError>
+    where F: FnMut(&mut Self) -> Result<(), DecodeError> {
+        if self.peek() != Some(b'[') {
+            return item(self);
+        }
+        self.position += 1;
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(());
+        }
+        loop {
+            item(self)?;
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(());
+                },
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    // Reads an embedded message in `{..}` or `<..>`, the name and colon have been read already.
+    pub fn message<T: TextDecoder>(&mut self, recursion_limit: u32) -> Result<T, DecodeError> {
+        let close = match self.peek() {
+            Some(b'{') => b'}',
+            Some(b'<') => b'>',
+            _ => return Err(self.error()),
+        };
+        if recursion_limit == 0 {
+            return Err(DecodeError::RecursionLimit);
+        }
+        self.position += 1;
+        T::twpb_decode_text_message(self, recursion_limit - 1, Some(close))
+    }
+
+    // A field of the message being read that it doesn't know, `name` was just read.
+    pub fn unknown_field(&self, name: &str) -> DecodeError {
+        DecodeError::InvalidText(self.position - name.len())
+    }
+
+    pub fn string(&mut self) -> Result<TextStr<'a>, DecodeError> {
+        let quote = match self.peek() {
+            Some(quote @ (b'"' | b'\'')) => quote,
+            _ => return Err(self.error()),
+        };
+        self.position += 1;
+        let start = self.position;
+        loop {
+            match self.input.get(self.position) {
+                Some(&byte) if byte == quote => break,
+                Some(b'\\') => {
+                    // the value of the digits at `from`, at most `max` of them, and how many there are
+                    let digits = |parser: &Self, from: usize, radix: u32, max: usize| {
+                        let mut value = 0;
+                        let mut len = 0;
+                        while let Some(digit) = parser.input.get(from + len).filter(|_| len < max)
+                            .and_then(|&byte| (byte as char).to_digit(radix)) {
+                            value = value * radix + digit;
+                            len += 1;
+                        }
+                        (value, len)
+                    };
+                    // exactly `len` hex digits naming a unicode scalar value, no surrogate or above U+10FFFF
+                    let unicode = |parser: &Self, from: usize, len: usize| {
+                        let (value, count) = digits(parser, from, 16, len);
+                        count == len && char::from_u32(value).is_some()
+                    };
+                    let next = self.position + 2;
+                    self.position = match self.input.get(self.position + 1) {
+                        Some(b'a' | b'b' | b'f' | b'n' | b'r' | b't' | b'v' | b'\\' | b'\'' | b'"' | b'?') => next,
+                        // up to three digits, a single byte so at most \377
+                        Some(b'0'..=b'7') if digits(self, self.position + 1, 8, 3).0 <= 0o377 => next,
+                        Some(b'x' | b'X') if digits(self, next, 16, 1).1 == 1 => next + 1,
+                        Some(b'u') if unicode(self, next, 4) => next + 4,
+                        Some(b'U') if unicode(self, next, 8) => next + 8,
+                        _ => return Err(self.error()),
+                    };
+                },
+                Some(b'\n') | None => return Err(self.error()),
+                Some(_) => self.position += 1,
+            }
+        }
+        let raw = &self.input[start..self.position];
+        self.position += 1;
+        Ok(TextStr { raw, position: start - 1 })
+    }
+
+    // A number or identifier as written in the input, e.g. "-0x1f", "1.5e-3" or "inf".
+    fn token(&mut self) -> Result<(&'a str, usize), DecodeError> {
+        self.peek();
+        let start = self.position;
+        if self.input.get(self.position) == Some(&b'-') {
+            self.position += 1;
+        }
+        let value_start = self.position;
+        while let Some(&byte) = self.input.get(self.position) {
+            let exponent_sign = (byte == b'+' || byte == b'-') && self.position > value_start
+                && matches!(self.input[self.position - 1], b'e' | b'E')
+                && self.input[value_start].is_ascii_digit()
+                && !self.input[value_start..].starts_with(b"0x");
+            if !(byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'.' || exponent_sign) {
+                break;
+            }
+            self.position += 1;
+        }
+        if self.position == value_start {
+            return Err(self.error());
+        }
+        // only ASCII was consumed
+        Ok((core::str::from_utf8(&self.input[start..self.position]).unwrap(), start))
+    }
+
+    // Decimal, hexadecimal (0x1f) or octal (017) integers.
+    fn integer<T>(&mut self, field_name: &'static str) -> Result<T, DecodeError>
+    where T: TryFrom<i128> {
+        let (text, position) = self.token()?;
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text),
+        };
+        let (digits, radix) = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            (hex, 16)
+        } else if text.len() > 1 && text.starts_with('0') {
+            (&text[1..], 8)
+        } else {
+            (text, 10)
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return Err(DecodeError::InvalidText(position));
+        }
+        let value = u64::from_str_radix(digits, radix).map_err(|_| DecodeError::ValueOutOfRange(field_name))?;
+        let value = if negative { -(value as i128) } else { value as i128 };
+        T::try_from(value).map_err(|_| DecodeError::ValueOutOfRange(field_name))
+    }
+
+    // Floats may also be "inf", "infinity" or "nan" in any case, and have an "f" suffix.
+    fn float(&mut self, field_name: &'static str) -> Result<f64, DecodeError> {
+        let (text, position) = self.token()?;
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text),
+        };
+        let value = if text.eq_ignore_ascii_case("inf") || text.eq_ignore_ascii_case("infinity") {
+            f64::INFINITY
+        } else if text.eq_ignore_ascii_case("nan") {
+            f64::NAN
+        } else {
+            let number = text.strip_suffix(['f', 'F']).unwrap_or(text);
+            // f64::from_str also accepts "inf" and friends, the checks above cover those
+            if !number.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+                return Err(DecodeError::InvalidText(position));
+            }
+            let value = f64::from_str(number).map_err(|_| DecodeError::InvalidText(position))?;
+            if value.is_infinite() {
+                return Err(DecodeError::ValueOutOfRange(field_name));
+            }
+            value
+        };
+        Ok(if negative { -value } else { value })
+    }
+
+    pub fn bool(&mut self) -> Result<bool, DecodeError> {
+        match self.token()? {
+            ("true" | "True" | "t" | "1", _) => Ok(true),
+            ("false" | "False" | "f" | "0", _) => Ok(false),
+            (_, position) => Err(DecodeError::InvalidText(position)),
+        }
+    }
+
+    // An enum value by name, or by number.
+    fn enum_value<T: EnumNames>(&mut self, field_name: &'static str) -> Result<i32, DecodeError> {
+        let position = self.position;
+        let (text, _) = self.token()?;
+        if let Some((_, value)) = T::NAMES.iter().find(|(name, _)| *name == text) {
+            return Ok(*value);
+        }
+        self.position = position;
+        self.integer(field_name)
+    }
+}
+
+// Integers, written as decimal numbers.
+macro_rules! text_integer {
+    ($marker:ident, $rust_type:ty) => {
+        impl TextCodec<$rust_type> for $marker {
+            fn encode_text(buffer: &mut impl Writer, value: &$rust_type) -> Result<usize, WriterError> {
+                format(buffer, format_args!("{}", value))
+            }
+
+            fn decode_text(parser: &mut TextParser, field_name: &'static str) -> Result<$rust_type, DecodeError> {
+                parser.integer(field_name)
+            }
+
+            fn is_default(value: &$rust_type) -> bool {
+                *value == 0
+            }
+        }
+    };
+}
+
+text_integer!(Int32, i32);
+text_integer!(Int32, i16);
+text_integer!(Int32, i8);
+text_integer!(SInt32, i32);
+text_integer!(SInt32, i16);
+text_integer!(SInt32, i8);
+text_integer!(SFixed32, i32);
+text_integer!(UInt32, u32);
+text_integer!(UInt32, u16);
+text_integer!(UInt32, u8);
+text_integer!(Fixed32, u32);
+text_integer!(Int64, i64);
+text_integer!(SInt64, i64);
+text_integer!(SFixed64, i64);
+text_integer!(UInt64, u64);
+text_integer!(Fixed64, u64);
+
+impl TextCodec<f32> for Float {
+    fn encode_text(buffer: &mut impl Writer, value: &f32) -> Result<usize, WriterError> {
+        float(buffer, *value as f64, format_args!("{}", value))
+    }
+
+    fn decode_text(parser: &mut TextParser, field_name: &'static str) -> Result<f32, DecodeError> {
+        let value = parser.float(field_name)?;
+        // too large for a float, not an actual infinity
+        if value.is_finite() && (value as f32).is_infinite() {
+            return Err(DecodeError::ValueOutOfRange(field_name));
+        }
+        Ok(value as f32)
+    }
+
+    fn is_default(value: &f32) -> bool {
+        *value == 0.0 && value.is_sign_positive()
+    }
+}
+
+impl TextCodec<f64> for Double {
+    fn encode_text(buffer: &mut impl Writer, value: &f64) -> Result<usize, WriterError> {
+        float(buffer, *value, format_args!("{}", value))
+    }
+
+    fn decode_text(parser: &mut TextParser, field_name: &'static str) -> Result<f64, DecodeError> {
+        parser.float(field_name)
+    }
+
+    fn is_default(value: &f64) -> bool {
+        *value == 0.0 && value.is_sign_positive()
+    }
+}
+
+impl TextCodec<bool> for Bool {
+    fn encode_text(buffer: &mut impl Writer, value: &bool) -> Result<usize, WriterError> {
+        buffer.write_all(if *value { b"true" } else { b"false" })
+    }
+
+    fn decode_text(parser: &mut TextParser, _field_name: &'static str) -> Result<bool, DecodeError> {
+        parser.bool()
+    }
+
+    fn is_default(value: &bool) -> bool {
+        !*value
+    }
+}
+
+impl<T> TextCodec<T> for Enum
+where T: Copy + Into<i32> + TryFrom<i32> + EnumNames {
+    fn encode_text(buffer: &mut impl Writer, value: &T) -> Result<usize, WriterError> {
+        let value: i32 = (*value).into();
+        match T::NAMES.iter().find(|(_, v)| *v == value) {
+            Some((name, _)) => buffer.write_all(name.as_bytes()),
+            None => format(buffer, format_args!("{}", value)),
+        }
+    }
+
+    fn decode_text(parser: &mut TextParser, field_name: &'static str) -> Result<T, DecodeError> {
+        let value = parser.enum_value::<T>(field_name)?;
+        T::try_from(value).map_err(|_| DecodeError::ValueOutOfRange(field_name))
+    }
+
+    fn is_default(value: &T) -> bool {
+        (*value).into() == 0
+    }
+}
+
+impl<const SIZE: usize> TextCodec<heapless::String<SIZE>> for String {
+    fn encode_text(buffer: &mut impl Writer, value: &heapless::String<SIZE>) -> Result<usize, WriterError> {
+        string(buffer, value)
+    }
+
+    fn decode_text(parser: &mut TextParser, field_name: &'static str) -> Result<heapless::String<SIZE>, DecodeError> {
+        parser.string()?.to_string(field_name)
+    }
+
+    fn is_default(value: &heapless::String<SIZE>) -> bool {
+        value.is_empty()
+    }
+}
+
+impl<const SIZE: usize> TextCodec<heapless::Vec<u8, SIZE>> for Bytes {
+    fn encode_text(buffer: &mut impl Writer, value: &heapless::Vec<u8, SIZE>) -> Result<usize, WriterError> {
+        bytes(buffer, value)
+    }
+
+    fn decode_text(parser: &mut TextParser, field_name: &'static str) -> Result<heapless::Vec<u8, SIZE>, DecodeError> {
+        parser.string()?.to_bytes(field_name)
+    }
+
+    fn is_default(value: &heapless::Vec<u8, SIZE>) -> bool {
+        value.is_empty()
+    }
+}
+
+impl<const SIZE: usize> TextCodec<[u8; SIZE]> for Bytes {
+    fn encode_text(buffer: &mut impl Writer, value: &[u8; SIZE]) -> Result<usize, WriterError> {
+        bytes(buffer, value)
+    }
+
+    fn decode_text(parser: &mut TextParser, field_name: &'static str) -> Result<[u8; SIZE], DecodeError> {
+        let mut result = [0u8; SIZE];
+        let mut len = 0;
+        for byte in parser.string()?.bytes() {
+            let slot = result.get_mut(len).ok_or(DecodeError::LengthMismatch(field_name))?;
+            *slot = byte;
+            len += 1;
+        }
+        // fixed size fields must match exactly
+        if len != SIZE {
+            return Err(DecodeError::LengthMismatch(field_name));
+        }
+        Ok(result)
+    }
+
+    // The proto default is empty bytes, which a fixed size field can't hold, so not even
+    // [0; SIZE] is left out. The binary encoding always writes it as well.
+    fn is_default(_value: &[u8; SIZE]) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode<C: TextCodec<T>, T>(value: T) -> heapless::String<64> {
+        let mut buffer = [0u8; 64];
+        let len = C::encode_text(&mut buffer.as_mut(), &value).unwrap();
+        heapless::String::from(core::str::from_utf8(&buffer[..len]).unwrap())
+    }
+
+    fn decode<C: TextCodec<T>, T>(text: &str) -> Result<T, DecodeError> {
+        let mut parser = TextParser::new(text.as_bytes());
+        let value = C::decode_text(&mut parser, "field")?;
+        parser.end()?;
+        Ok(value)
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(encode::<Int64, _>(i64::MIN), "-9223372036854775808");
+        assert_eq!(decode::<Fixed64, u64>("18446744073709551615"), Ok(u64::MAX));
+        assert_eq!(decode::<Int32, i32>("-0x10"), Ok(-16));
+        assert_eq!(decode::<UInt32, u32>("017"), Ok(15));
+        assert_eq!(decode::<UInt32, u32>("0"), Ok(0));
+        assert_eq!(decode::<Int32, i32>("2147483648"), Err(DecodeError::ValueOutOfRange("field")));
+        assert_eq!(decode::<UInt32, u8>("256"), Err(DecodeError::ValueOutOfRange("field")));
+        assert_eq!(decode::<UInt64, u64>("-1"), Err(DecodeError::ValueOutOfRange("field")));
+        assert_eq!(decode::<Int32, i32>("08"), Err(DecodeError::InvalidText(0)));
+        assert_eq!(decode::<Int32, i32>("1.5"), Err(DecodeError::InvalidText(0)));
+    }
+
+    #[test]
+    fn test_floats() {
+        assert_eq!(encode::<Float, _>(0.1), "0.1");
+        assert_eq!(encode::<Double, _>(f64::NEG_INFINITY), "-inf");
+        assert_eq!(encode::<Double, _>(f64::NAN), "nan");
+        assert_eq!(decode::<Float, f32>("0.1f"), Ok(0.1));
+        assert_eq!(decode::<Double, f64>("-1.5e-3"), Ok(-0.0015));
+        assert_eq!(decode::<Double, f64>(".5"), Ok(0.5));
+        assert_eq!(decode::<Double, f64>("-Infinity"), Ok(f64::NEG_INFINITY));
+        assert!(decode::<Double, f64>("NaN").unwrap().is_nan());
+        assert_eq!(decode::<Float, f32>("1e39"), Err(DecodeError::ValueOutOfRange("field")));
+        assert_eq!(decode::<Double, f64>("infinite"), Err(DecodeError::InvalidText(0)));
+    }
+
+    #[test]
+    fn test_bools() {
+        assert_eq!(decode::<Bool, bool>("True"), Ok(true));
+        assert_eq!(decode::<Bool, bool>("t"), Ok(true));
+        assert_eq!(decode::<Bool, bool>("0"), Ok(false));
+        assert_eq!(decode::<Bool, bool>("yes"), Err(DecodeError::InvalidText(0)));
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(encode::<String, _>(heapless::String::<16>::from("a\"b\\\n\u{1}🐉")), "\"a\\\"b\\\\\\n\\001🐉\"");
+        assert_eq!(encode::<Bytes, _>([0xffu8, b'\'']), "\"\\377'\"");
+        assert_eq!(decode::<String, heapless::String<16>>("'a\\'b' "), Ok(heapless::String::from("a'b")));
+        assert_eq!(decode::<String, heapless::String<16>>("\"\\u00e9\\U0001f409\\x41\\101\\0\""),
+            Ok(heapless::String::from("é🐉AA\0")));
+        assert_eq!(decode::<Bytes, heapless::Vec<u8, 4>>("\"\\377\\a\""), Ok(heapless::Vec::from_slice(&[0xff, 7]).unwrap()));
+        assert_eq!(decode::<String, heapless::String<4>>("\"\\377\""), Err(DecodeError::InvalidText(0)));
+        assert_eq!(decode::<String, heapless::String<2>>("\"abc\""), Err(DecodeError::FieldOverflow("field")));
+        assert_eq!(decode::<String, heapless::String<4>>("\"\\q\""), Err(DecodeError::InvalidText(1)));
+        assert_eq!(decode::<String, heapless::String<4>>("\"a\nb\""), Err(DecodeError::InvalidText(2)));
+        assert_eq!(decode::<Bytes, [u8; 2]>("\"abc\""), Err(DecodeError::LengthMismatch("field")));
+        assert!(!<Bytes as TextCodec<[u8; 2]>>::is_default(&[0; 2]));
+    }
+}
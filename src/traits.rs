@@ -8,6 +8,7 @@ pub enum WriterError {
 pub trait Writer {
     fn write(&mut self, byte: u8) -> Result<(), WriterError>;
 
+    #[allow(clippy::question_mark)]
     fn write_all(&mut self, bytes: &[u8]) -> Result<usize, WriterError> {
         for byte in bytes {
             if let Err(err) = self.write(*byte) {
@@ -20,18 +21,73 @@ pub trait Writer {
 
 pub trait MessageEncoder {
     fn twpb_encode(&self, buffer: &mut impl Writer) -> Result<usize, WriterError>;
+
+    // The amount of bytes twpb_encode would write.
+    fn twpb_encoded_len(&self) -> usize {
+        let mut nullbuffer = crate::iterators::NullCounterBuffer::new();
+        self.twpb_encode(&mut nullbuffer).unwrap_or(0)
+    }
 }
+
 pub trait MessageDecoder: Sized {
+    #[allow(clippy::map_clone)]
     fn twpb_decode(buf: &[u8]) -> Result<Self, crate::decoder::DecodeError> {
         Self::twpb_decode_iter(buf.iter().map(|x| *x))
     }
 
     fn twpb_decode_iter<I>(bytes: I) -> Result<Self, crate::decoder::DecodeError>
     where I: Iterator<Item = u8>;
+
+    // Same as twpb_decode, but with a custom maximum depth of embedded messages.
+    fn twpb_decode_with_limit(buf: &[u8], recursion_limit: u32) -> Result<Self, crate::decoder::DecodeError> {
+        Self::twpb_decode_iter_with_limit(buf.iter().copied(), recursion_limit)
+    }
+
+    // Decoders that don't embed messages have no depth to limit. The derived
+    // decoders implement it and check the limit for every embedded message.
+    fn twpb_decode_iter_with_limit<I>(bytes: I, _recursion_limit: u32) -> Result<Self, crate::decoder::DecodeError>
+    where I: Iterator<Item = u8> {
+        Self::twpb_decode_iter(bytes)
+    }
+}
+
+// The type URL of a message as `Any` holds it, e.g. "type.googleapis.com/api.v1.Request".
+// Derived from the message name and its #[twpb(package = "..")].
+pub trait TypeName {
+    const TYPE_URL: &'static str;
+}
+
+// Pointers that can hold an embedded message, used for recursive messages.
+// Allocation can fail (e.g. an exhausted memory pool), in which case None is returned.
+pub trait MessageBox: core::ops::Deref + Sized
+where Self::Target: Sized {
+    fn new_box(value: Self::Target) -> Option<Self>;
+}
+
+// Messages stored in a heapless::pool::Box get their memory from this pool.
+#[cfg(feature = "pool")]
+pub trait BoxPool: Sized + 'static {
+    fn pool() -> &'static heapless::pool::Pool<Self>;
+}
+
+#[cfg(feature = "pool")]
+impl<T> MessageBox for heapless::pool::Box<T>
+where T: BoxPool {
+    fn new_box(value: T) -> Option<Self> {
+        T::pool().alloc().map(|b| b.init(value))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> MessageBox for alloc::boxed::Box<T> {
+    fn new_box(value: T) -> Option<Self> {
+        Some(alloc::boxed::Box::new(value))
+    }
 }
 
 impl Writer for &mut [u8] {
     #[inline]
+    #[allow(clippy::mem_replace_with_default)]
     fn write(&mut self, byte: u8) -> Result<(), WriterError> {
         if self.is_empty() {
             return Err(WriterError::BufferOverflow);
@@ -52,4 +108,30 @@ impl Writer for &mut [u8] {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl<const N: usize> Writer for heapless::Vec<u8, N> {
+    #[inline]
+    fn write(&mut self, byte: u8) -> Result<(), WriterError> {
+        self.push(byte).map_err(|_| WriterError::BufferOverflow)
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<usize, WriterError> {
+        self.extend_from_slice(bytes).map_err(|_| WriterError::BufferOverflow)?;
+        Ok(bytes.len())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Writer for alloc::vec::Vec<u8> {
+    #[inline]
+    fn write(&mut self, byte: u8) -> Result<(), WriterError> {
+        self.push(byte);
+        Ok(())
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<usize, WriterError> {
+        self.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+}
@@ -0,0 +1,540 @@
+// Well-known types, the messages of google/protobuf/*.proto that APIs commonly use.
+//
+// Timestamp, Duration, Empty, FieldMask and Any are derived messages like any other,
+// use them as `#[twpb(message, nr=..)] Option<twpb::wkt::Timestamp>`.
+// In proto3 JSON, Timestamp, Duration and FieldMask are a string instead of an object:
+// RFC 3339 in UTC like "1972-01-01T10:00:20.021Z", seconds like "-1.500s" and camelCase paths
+// like "settings.lowPower,name". Having no members, they can't be flattened in JSON.
+// Any has no JSON, its proto3 form needs the type of the message it holds.
+//
+// The wrapper types (Int32Value, StringValue, ...) are a message holding one scalar,
+// a nullable scalar on the wire. Here they're codecs mapping that to an Option:
+//   #[twpb(codec = "twpb::wkt::Int32Value", nr=3)]
+//   pub limit: Option<i32>,
+// None leaves the field out, Some is always written, also when it holds the default value.
+// Wrappers can't be repeated, protobuf has no nullable values in a list.
+use defmt::Format;
+use crate::codec::{self, ScalarCodec};
+use crate::decoder::{self, DecodeError};
+use crate::encoder;
+use crate::iterators::LimitedIterator;
+use crate::json::{self, format, JsonCodec, JsonDecoder, JsonEncoder, JsonParser, JsonStr};
+use crate::traits::{MessageDecoder, MessageEncoder, TypeName, Writer, WriterError};
+use crate::wiretypes::wire_types;
+
+const NANOS_PER_SECOND: i32 = 1_000_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+// 0001-01-01T00:00:00Z and 9999-12-31T23:59:59Z, the range of RFC 3339
+const MIN_SECONDS: i64 = -62_135_596_800;
+const MAX_SECONDS: i64 = 253_402_300_799;
+// about 10000 years, the range of Duration
+const MAX_DURATION: i64 = 315_576_000_000;
+
+// The value doesn't fit in the type converted to,
+// or isn't valid, e.g. nanos of a second that are a second or more.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Format)]
+pub struct OutOfRange;
+
+// A point in time, independent of time zones, as time since the Unix epoch.
+// `nanos` is never negative, times before the epoch count forward from a negative second.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ::twpb_derive::Message)]
+#[twpb(package = "google.protobuf", text, reflect)]
+pub struct Timestamp {
+    #[twpb(int64, nr=1)]
+    pub seconds: i64,
+    #[twpb(int32, nr=2)]
+    pub nanos: i32,
+}
+
+impl Timestamp {
+    pub fn from_unix_epoch(since: core::time::Duration) -> Result<Self, OutOfRange> {
+        let seconds = i64::try_from(since.as_secs()).map_err(|_| OutOfRange)?;
+        Ok(Timestamp { seconds, nanos: since.subsec_nanos() as i32 })
+    }
+
+    // The time since the Unix epoch, which fails for earlier times.
+    pub fn since_unix_epoch(&self) -> Result<core::time::Duration, OutOfRange> {
+        if !(0..NANOS_PER_SECOND).contains(&self.nanos) {
+            return Err(OutOfRange);
+        }
+        let seconds = u64::try_from(self.seconds).map_err(|_| OutOfRange)?;
+        Ok(core::time::Duration::new(seconds, self.nanos as u32))
+    }
+}
+
+// A signed span of time. `seconds` and `nanos` have the same sign, or are zero.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ::twpb_derive::Message)]
+#[twpb(package = "google.protobuf", text, reflect)]
+pub struct Duration {
+    #[twpb(int64, nr=1)]
+    pub seconds: i64,
+    #[twpb(int32, nr=2)]
+    pub nanos: i32,
+}
+
+impl Duration {
+    fn is_valid(&self) -> bool {
+        self.nanos.abs() < NANOS_PER_SECOND
+            && !(self.seconds > 0 && self.nanos < 0)
+            && !(self.seconds < 0 && self.nanos > 0)
+    }
+}
+
+impl TryFrom<core::time::Duration> for Duration {
+    type Error = OutOfRange;
+    fn try_from(duration: core::time::Duration) -> Result<Self, OutOfRange> {
+        let seconds = i64::try_from(duration.as_secs()).map_err(|_| OutOfRange)?;
+        Ok(Duration { seconds, nanos: duration.subsec_nanos() as i32 })
+    }
+}
+
+// core::time::Duration can't be negative.
+impl TryFrom<Duration> for core::time::Duration {
+    type Error = OutOfRange;
+    fn try_from(duration: Duration) -> Result<Self, OutOfRange> {
+        if !duration.is_valid() || duration.nanos < 0 {
+            return Err(OutOfRange);
+        }
+        let seconds = u64::try_from(duration.seconds).map_err(|_| OutOfRange)?;
+        Ok(core::time::Duration::new(seconds, duration.nanos as u32))
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for Timestamp {
+    fn from(time: time::OffsetDateTime) -> Self {
+        Timestamp { seconds: time.unix_timestamp(), nanos: time.nanosecond() as i32 }
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<Timestamp> for time::OffsetDateTime {
+    type Error = OutOfRange;
+    fn try_from(timestamp: Timestamp) -> Result<Self, OutOfRange> {
+        if !(0..NANOS_PER_SECOND).contains(&timestamp.nanos) {
+            return Err(OutOfRange);
+        }
+        let nanos = timestamp.seconds as i128 * NANOS_PER_SECOND as i128 + timestamp.nanos as i128;
+        time::OffsetDateTime::from_unix_timestamp_nanos(nanos).map_err(|_| OutOfRange)
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::Duration> for Duration {
+    fn from(duration: time::Duration) -> Self {
+        Duration { seconds: duration.whole_seconds(), nanos: duration.subsec_nanoseconds() }
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<Duration> for time::Duration {
+    type Error = OutOfRange;
+    fn try_from(duration: Duration) -> Result<Self, OutOfRange> {
+        if !duration.is_valid() {
+            return Err(OutOfRange);
+        }
+        Ok(time::Duration::new(duration.seconds, duration.nanos))
+    }
+}
+
+// A message without fields, e.g. for requests without arguments.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, ::twpb_derive::Message)]
+#[twpb(package = "google.protobuf", json, text, reflect)]
+pub struct Empty {}
+
+// Paths of fields, e.g. "settings.mode", at most `PATHS` of at most `LEN` bytes.
+#[derive(Debug, Default, Clone, PartialEq, Eq, ::twpb_derive::Message)]
+#[twpb(package = "google.protobuf", text, reflect)]
+pub struct FieldMask<const PATHS: usize, const LEN: usize> {
+    #[twpb(string, repeated, nr=1)]
+    pub paths: heapless::Vec<heapless::String<LEN>, PATHS>,
+}
+
+// Any message, with the type URL it's known by, at most `URL` and `VALUE` bytes long.
+// Receivers dispatch on the type with `is`, or `unpack` each type they handle in turn.
+#[derive(Debug, Default, Clone, PartialEq, Eq, ::twpb_derive::Message)]
+#[twpb(package = "google.protobuf", text, reflect)]
+pub struct Any<const URL: usize, const VALUE: usize> {
+    #[twpb(string, nr=1)]
+    pub type_url: heapless::String<URL>,
+    #[twpb(bytes, nr=2)]
+    pub value: heapless::Vec<u8, VALUE>,
+}
+
+impl<const URL: usize, const VALUE: usize> Any<URL, VALUE> {
+    // Fails if the type URL or the encoded message doesn't fit.
+    pub fn pack<T: MessageEncoder + TypeName>(message: &T) -> Result<Self, WriterError> {
+        let mut any = Any::default();
+        any.type_url.push_str(T::TYPE_URL).map_err(|_| WriterError::BufferOverflow)?;
+        message.twpb_encode(&mut any.value)?;
+        Ok(any)
+    }
+
+    // Whether this holds a `T`. Only the full name after the last '/' of the URL counts,
+    // other implementations use other hosts.
+    pub fn is<T: TypeName>(&self) -> bool {
+        full_name(&self.type_url) == full_name(T::TYPE_URL)
+    }
+
+    // None if this holds another type than `T`.
+    pub fn unpack<T: MessageDecoder + TypeName>(&self) -> Result<Option<T>, DecodeError> {
+        if !self.is::<T>() {
+            return Ok(None);
+        }
+        T::twpb_decode(&self.value).map(Some)
+    }
+}
+
+fn full_name(type_url: &str) -> &str {
+    type_url.rsplit_once('/').map_or(type_url, |(_, name)| name)
+}
+
+// Days since 1970-01-01 of a date of the proleptic Gregorian calendar, and back.
+// https://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year as i64;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = (if month_index < 10 { month_index + 3 } else { month_index - 9 }) as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// The nanoseconds as fraction of a second, with 0, 3, 6 or 9 digits like the reference implementation.
+fn write_nanos(buffer: &mut impl Writer, nanos: u32) -> Result<usize, WriterError> {
+    if nanos == 0 {
+        Ok(0)
+    } else if nanos.is_multiple_of(1_000_000) {
+        format(buffer, format_args!(".{:03}", nanos / 1_000_000))
+    } else if nanos.is_multiple_of(1_000) {
+        format(buffer, format_args!(".{:06}", nanos / 1_000))
+    } else {
+        format(buffer, format_args!(".{:09}", nanos))
+    }
+}
+
+// Reads the strings of the JSON forms, None where they're malformed.
+struct Scanner<'a> {
+    input: &'a [u8],
+}
+
+impl Scanner<'_> {
+    fn byte(&mut self, expected: u8) -> Option<()> {
+        let (&byte, rest) = self.input.split_first()?;
+        self.input = rest;
+        (byte == expected).then_some(())
+    }
+
+    fn peek(&self, expected: u8) -> bool {
+        self.input.first() == Some(&expected)
+    }
+
+    // A number of exactly `len` digits.
+    fn digits(&mut self, len: usize) -> Option<u32> {
+        let digits = self.input.get(..len).filter(|digits| digits.iter().all(u8::is_ascii_digit))?;
+        self.input = &self.input[len..];
+        Some(digits.iter().fold(0, |value, digit| value * 10 + (digit - b'0') as u32))
+    }
+
+    // A number of one or more digits.
+    fn number(&mut self) -> Option<i64> {
+        let len = self.input.iter().take_while(|byte| byte.is_ascii_digit()).count();
+        let digits = core::str::from_utf8(&self.input[..len]).ok().filter(|digits| !digits.is_empty())?;
+        self.input = &self.input[len..];
+        digits.parse().ok()
+    }
+
+    // An optional fraction of a second of one to nine digits, as nanoseconds.
+    fn nanos(&mut self) -> Option<i32> {
+        if !self.peek(b'.') {
+            return Some(0);
+        }
+        self.input = &self.input[1..];
+        let len = self.input.iter().take_while(|byte| byte.is_ascii_digit()).count();
+        if !(1..=9).contains(&len) {
+            return None;
+        }
+        let fraction = self.digits(len)?;
+        Some((fraction * 10u32.pow(9 - len as u32)) as i32)
+    }
+
+    fn end(&self) -> Option<()> {
+        self.input.is_empty().then_some(())
+    }
+}
+
+// The string a well-known type is in JSON, as given to `parse`. Errors are at the start of the string.
+fn decode_json_string<T>(parser: &mut JsonParser, parse: impl FnOnce(&mut Scanner) -> Option<T>) -> Result<T, DecodeError> {
+    if !parser.is_string() {
+        return Err(DecodeError::InvalidJson(parser.position()));
+    }
+    let position = parser.position();
+    // the longest of these is a timestamp with nanoseconds and an offset
+    let mut value = heapless::String::<40>::new();
+    for c in parser.string()?.chars() {
+        value.push(c).map_err(|_| DecodeError::InvalidJson(position))?;
+    }
+    parse(&mut Scanner { input: value.as_bytes() }).ok_or(DecodeError::InvalidJson(position))
+}
+
+impl JsonEncoder for Timestamp {
+    fn twpb_encode_json_members(&self, _buffer: &mut impl Writer, _first: &mut bool) -> Result<usize, WriterError> {
+        Ok(0)
+    }
+
+    // Times outside of the years 1 to 9999 are written as well, but aren't valid RFC 3339.
+    fn twpb_encode_json(&self, buffer: &mut impl Writer) -> Result<usize, WriterError> {
+        let total = self.seconds as i128 * NANOS_PER_SECOND as i128 + self.nanos as i128;
+        let seconds = total.div_euclid(NANOS_PER_SECOND as i128) as i64;
+        let nanos = total.rem_euclid(NANOS_PER_SECOND as i128) as u32;
+        let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+        let time = seconds.rem_euclid(SECONDS_PER_DAY);
+        let mut bytes_written = format(buffer, format_args!("\"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year, month, day, time / 3600, time / 60 % 60, time % 60))?;
+        bytes_written += write_nanos(buffer, nanos)?;
+        bytes_written += buffer.write_all(b"Z\"")?;
+        Ok(bytes_written)
+    }
+}
+
+impl JsonDecoder for Timestamp {
+    // Any offset is accepted, the timestamp is in UTC.
+    fn twpb_decode_json_value(parser: &mut JsonParser, _recursion_limit: u32) -> Result<Self, DecodeError> {
+        decode_json_string(parser, |scanner| {
+            let year = scanner.digits(4)? as i64;
+            scanner.byte(b'-')?;
+            let month = scanner.digits(2)?;
+            scanner.byte(b'-')?;
+            let day = scanner.digits(2)?;
+            scanner.byte(b'T')?;
+            let hour = scanner.digits(2)?;
+            scanner.byte(b':')?;
+            let minute = scanner.digits(2)?;
+            scanner.byte(b':')?;
+            let second = scanner.digits(2)?;
+            let nanos = scanner.nanos()?;
+            let offset = if scanner.peek(b'+') || scanner.peek(b'-') {
+                let sign = if scanner.peek(b'-') { -1 } else { 1 };
+                scanner.input = &scanner.input[1..];
+                let hours = scanner.digits(2)?;
+                scanner.byte(b':')?;
+                let minutes = scanner.digits(2)?;
+                if hours > 23 || minutes > 59 {
+                    return None;
+                }
+                sign * (hours * 3600 + minutes * 60) as i64
+            } else {
+                scanner.byte(b'Z')?;
+                0
+            };
+            scanner.end()?;
+            if year == 0 || !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month)
+                || hour > 23 || minute > 59 || second > 59 {
+                return None;
+            }
+            let seconds = days_from_civil(year, month, day) * SECONDS_PER_DAY
+                + (hour * 3600 + minute * 60 + second) as i64 - offset;
+            (MIN_SECONDS..=MAX_SECONDS).contains(&seconds).then_some(Timestamp { seconds, nanos })
+        })
+    }
+
+    fn twpb_decode_json_member(&mut self, _key: &JsonStr, _parser: &mut JsonParser, _recursion_limit: u32) -> Result<bool, DecodeError> {
+        Ok(false)
+    }
+}
+
+impl JsonEncoder for Duration {
+    fn twpb_encode_json_members(&self, _buffer: &mut impl Writer, _first: &mut bool) -> Result<usize, WriterError> {
+        Ok(0)
+    }
+
+    fn twpb_encode_json(&self, buffer: &mut impl Writer) -> Result<usize, WriterError> {
+        let total = self.seconds as i128 * NANOS_PER_SECOND as i128 + self.nanos as i128;
+        let sign = if total < 0 { "-" } else { "" };
+        let total = total.unsigned_abs();
+        let mut bytes_written = format(buffer, format_args!("\"{}{}", sign, total / NANOS_PER_SECOND as u128))?;
+        bytes_written += write_nanos(buffer, (total % NANOS_PER_SECOND as u128) as u32)?;
+        bytes_written += buffer.write_all(b"s\"")?;
+        Ok(bytes_written)
+    }
+}
+
+impl JsonDecoder for Duration {
+    fn twpb_decode_json_value(parser: &mut JsonParser, _recursion_limit: u32) -> Result<Self, DecodeError> {
+        decode_json_string(parser, |scanner| {
+            let sign = if scanner.peek(b'-') {
+                scanner.input = &scanner.input[1..];
+                -1
+            } else {
+                1
+            };
+            let seconds = scanner.number().filter(|seconds| *seconds <= MAX_DURATION)?;
+            let nanos = scanner.nanos()?;
+            scanner.byte(b's')?;
+            scanner.end()?;
+            Some(Duration { seconds: sign * seconds, nanos: sign as i32 * nanos })
+        })
+    }
+
+    fn twpb_decode_json_member(&mut self, _key: &JsonStr, _parser: &mut JsonParser, _recursion_limit: u32) -> Result<bool, DecodeError> {
+        Ok(false)
+    }
+}
+
+impl<const PATHS: usize, const LEN: usize> JsonEncoder for FieldMask<PATHS, LEN> {
+    fn twpb_encode_json_members(&self, _buffer: &mut impl Writer, _first: &mut bool) -> Result<usize, WriterError> {
+        Ok(0)
+    }
+
+    // The paths in lowerCamelCase, separated by commas: "low_power" is "lowPower".
+    fn twpb_encode_json(&self, buffer: &mut impl Writer) -> Result<usize, WriterError> {
+        let mut bytes_written = buffer.write_all(b"\"")?;
+        for (index, path) in self.paths.iter().enumerate() {
+            if index > 0 {
+                bytes_written += buffer.write_all(b",")?;
+            }
+            let mut chars = path.chars().peekable();
+            while let Some(c) = chars.next() {
+                bytes_written += match chars.peek() {
+                    Some(next) if c == '_' && next.is_ascii_lowercase() => {
+                        let upper = next.to_ascii_uppercase();
+                        chars.next();
+                        json::escaped(buffer, upper)?
+                    },
+                    _ => json::escaped(buffer, c)?,
+                };
+            }
+        }
+        bytes_written += buffer.write_all(b"\"")?;
+        Ok(bytes_written)
+    }
+}
+
+impl<const PATHS: usize, const LEN: usize> JsonDecoder for FieldMask<PATHS, LEN> {
+    fn twpb_decode_json_value(parser: &mut JsonParser, _recursion_limit: u32) -> Result<Self, DecodeError> {
+        let value = parser.string()?;
+        let mut result = FieldMask::default();
+        let mut path = heapless::String::<LEN>::new();
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != ',' {
+                if c.is_ascii_uppercase() {
+                    path.push('_').map_err(|_| DecodeError::FieldOverflow("paths"))?;
+                }
+                path.push(c.to_ascii_lowercase()).map_err(|_| DecodeError::FieldOverflow("paths"))?;
+            }
+            // an empty string is no paths at all
+            if c == ',' || chars.peek().is_none() {
+                result.paths.push(core::mem::take(&mut path)).map_err(|_| DecodeError::FieldOverflow("paths"))?;
+            }
+        }
+        Ok(result)
+    }
+
+    fn twpb_decode_json_member(&mut self, _key: &JsonStr, _parser: &mut JsonParser, _recursion_limit: u32) -> Result<bool, DecodeError> {
+        Ok(false)
+    }
+}
+
+// The wrapper message around `value`, written as the value of a field: length, tag, value.
+// None is a zero length, which `encode_singular` leaves out like any other empty field.
+fn encode_wrapper<C, T>(buffer: &mut impl Writer, value: &Option<T>) -> Result<usize, WriterError>
+where C: ScalarCodec<T> {
+    match value {
+        Some(value) => {
+            let len = codec::encoded_len_field::<C, T>(1, value);
+            let bytes_written = encoder::leb128_u32(buffer, &(len as u32))?;
+            Ok(bytes_written + codec::encode_field::<C, T>(buffer, 1, value)?)
+        },
+        None => encoder::leb128_u32(buffer, &0),
+    }
+}
+
+// A wrapper message that's present holds a value, its default if the field is left out.
+fn decode_wrapper<C, T, I>(mut bytes: I, field_name: &'static str) -> Result<Option<T>, DecodeError>
+where C: ScalarCodec<T>, T: Default, I: Iterator<Item = u8> {
+    let bufsize = decoder::leb128_u32(&mut bytes)?;
+    let mut iterator = LimitedIterator::new(&mut bytes, bufsize);
+    let mut value = T::default();
+    loop {
+        match decoder::tag(&mut iterator) {
+            Ok((1, wire_type)) if wire_type != C::WIRE_TYPE => return Err(DecodeError::WrongWireType(wire_type, field_name)),
+            Ok((1, _)) => value = C::decode(&mut iterator, field_name)?,
+            Ok((_, wire_type)) => decoder::unknown(&mut iterator, wire_type)?,
+            Err(DecodeError::EmptyBuffer) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Some(value))
+}
+
+macro_rules! wrapper {
+    ($name:ident, $codec:ty) => {
+        pub struct $name;
+
+        impl<T> ScalarCodec<Option<T>> for $name
+        where $codec: ScalarCodec<T>, T: Default {
+            const WIRE_TYPE: u8 = wire_types::LENGTHDELIMITED;
+
+            fn encode(buffer: &mut impl Writer, value: &Option<T>) -> Result<usize, WriterError> {
+                encode_wrapper::<$codec, T>(buffer, value)
+            }
+
+            fn decode<I>(bytes: I, field_name: &'static str) -> Result<Option<T>, DecodeError>
+            where I: Iterator<Item = u8> {
+                decode_wrapper::<$codec, T, I>(bytes, field_name)
+            }
+        }
+
+        // proto3 JSON writes the wrapped value itself, or null
+        impl<T> JsonCodec<Option<T>> for $name
+        where $codec: JsonCodec<T> {
+            fn encode_json(buffer: &mut impl Writer, value: &Option<T>) -> Result<usize, WriterError> {
+                match value {
+                    Some(value) => <$codec as JsonCodec<T>>::encode_json(buffer, value),
+                    None => buffer.write_all(b"null"),
+                }
+            }
+
+            fn decode_json(parser: &mut JsonParser, field_name: &'static str) -> Result<Option<T>, DecodeError> {
+                Ok(Some(<$codec as JsonCodec<T>>::decode_json(parser, field_name)?))
+            }
+
+            fn is_default(value: &Option<T>) -> bool {
+                value.is_none()
+            }
+        }
+    };
+}
+
+wrapper!(DoubleValue, codec::Double);
+wrapper!(FloatValue, codec::Float);
+wrapper!(Int64Value, codec::Int64);
+wrapper!(UInt64Value, codec::UInt64);
+wrapper!(Int32Value, codec::Int32);
+wrapper!(UInt32Value, codec::UInt32);
+wrapper!(BoolValue, codec::Bool);
+wrapper!(StringValue, codec::String);
+wrapper!(BytesValue, codec::Bytes);
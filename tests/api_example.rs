@@ -1,7 +1,10 @@
+// predates running clippy, keeps its original style
+#![allow(clippy::map_clone)]
+
 mod types;
 
 use types::{APIMessage, apimessage, v1};
-use twpb::{MessageDecoder};
+use twpb::{MessageEncoder, MessageDecoder};
 
 #[test] // We can successfully decode a getInfo API request
 fn test_get_info() {
@@ -37,4 +40,22 @@ fn test_get_info_2() {
         },
         _ => panic!("unexpected api message content"),
     }
-}
\ No newline at end of file
+}
+
+#[test] // A getInfo API request survives a round trip, even though all its messages are empty
+fn test_get_info_roundtrip() {
+    let dummydata = include_bytes!("files/bin/python.api.getInfo.bin");
+    let expected = APIMessage {
+        content: Some(apimessage::Content::V1Request(v1::Request{
+            request: Some(v1::request::Request::GetInfo(v1::EmptyRequest{}))
+        }))
+    };
+
+    let mut bytes = [0x0; 100];
+    let len = expected.twpb_encode(&mut bytes.as_mut()).unwrap();
+    assert_eq!(&bytes[0..len], dummydata);
+    assert_eq!(expected.twpb_encoded_len(), len);
+
+    let message = APIMessage::twpb_decode_iter(bytes[0..len].iter().copied()).unwrap();
+    assert_eq!(message, expected);
+}
@@ -0,0 +1,59 @@
+use twpb::{MessageEncoder, MessageDecoder, DecodeError};
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Key {
+    #[twpb(bytes,nr=1)]
+    pub mac: [u8; 6],
+    #[twpb(bytes,nr=2)]
+    pub hash: [u8; 32],
+    #[twpb(sint32,repeated,nr=3)]
+    pub position: [i32; 3],
+}
+
+#[test]
+fn test_arrays() {
+    let key = Key {
+        mac: [0x02, 0x00, 0x5E, 0x10, 0x00, 0x01],
+        hash: [0xAB; 32],
+        position: [1, -1, 0],
+    };
+    let mut buffer = [0x0; 100];
+    let bytes_written = key.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(bytes_written, 8 + 34 + 6);
+    assert_eq!(buffer[0..8], [0x0A, 0x06, 0x02, 0x00, 0x5E, 0x10, 0x00, 0x01]);
+    assert_eq!(buffer[42..48], [0x18, 0x02, 0x18, 0x01, 0x18, 0x00]);
+    let parsed = Key::twpb_decode(&buffer[0..bytes_written]).unwrap();
+    assert_eq!(parsed, key);
+
+    // packed repeated fields fill the array as well
+    let mut packed = [0x0; 100];
+    packed[0..42].copy_from_slice(&buffer[0..42]);
+    packed[42..47].copy_from_slice(&[0x1A, 0x03, 0x02, 0x01, 0x00]);
+    let parsed = Key::twpb_decode(&packed[0..47]).unwrap();
+    assert_eq!(parsed, key);
+}
+
+#[test]
+fn test_arrays_wrong_byte_length() {
+    // a 5 byte mac address
+    let result = Key::twpb_decode(&[0x0A, 0x05, 0x02, 0x00, 0x5E, 0x10, 0x00]).unwrap_err();
+    assert_eq!(result, DecodeError::LengthMismatch("mac"));
+    // a 7 byte mac address
+    let result = Key::twpb_decode(&[0x0A, 0x07, 0x02, 0x00, 0x5E, 0x10, 0x00, 0x01, 0x02]).unwrap_err();
+    assert_eq!(result, DecodeError::LengthMismatch("mac"));
+}
+
+#[test]
+fn test_arrays_wrong_element_count() {
+    let mut buffer = [0x0; 100];
+    let bytes_written = Key::default().twpb_encode(&mut buffer.as_mut()).unwrap();
+
+    // drop the last position element
+    let result = Key::twpb_decode(&buffer[0..bytes_written - 2]).unwrap_err();
+    assert_eq!(result, DecodeError::LengthMismatch("position"));
+
+    // add a fourth position element
+    buffer[bytes_written..bytes_written + 2].copy_from_slice(&[0x18, 0x00]);
+    let result = Key::twpb_decode(&buffer[0..bytes_written + 2]).unwrap_err();
+    assert_eq!(result, DecodeError::LengthMismatch("position"));
+}
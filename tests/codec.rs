@@ -0,0 +1,41 @@
+use twpb::{MessageEncoder, MessageDecoder, ScalarCodec, DecodeError, Writer, WriterError};
+
+// A downstream encoding: temperatures are kept as f32 in Rust,
+// but sent as a sint32 number of centidegrees.
+pub struct CentiDegrees;
+
+impl ScalarCodec<f32> for CentiDegrees {
+    const WIRE_TYPE: u8 = ::twpb::wire_types::VARINT;
+
+    fn encode(buffer: &mut impl Writer, value: &f32) -> Result<usize, WriterError> {
+        ::twpb::encoder::sint32(buffer, &((value * 100.0) as i32))
+    }
+
+    fn decode<I>(bytes: I, field_name: &'static str) -> Result<f32, DecodeError>
+    where I: Iterator<Item = u8> {
+        ::twpb::decoder::sint32(bytes, field_name).map(|v| v as f32 / 100.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Reading {
+    #[twpb(codec="CentiDegrees",nr=1)]
+    pub temperature: f32,
+    #[twpb(codec="CentiDegrees",repeated,nr=2)]
+    pub history: heapless::Vec<f32, 4>,
+}
+
+#[test]
+fn test_custom_codec() {
+    let reading = Reading {
+        temperature: -12.5,
+        history: heapless::Vec::from_slice(&[1.0, 2.5]).unwrap(),
+    };
+    let mut buffer = [0x0; 100];
+    let bytes_written = reading.twpb_encode(&mut buffer.as_mut()).unwrap();
+    // -1250 zigzags to 2499
+    assert_eq!(buffer[0..bytes_written], [0x08, 0xC3, 0x13, 0x10, 0xC8, 0x01, 0x10, 0xF4, 0x03]);
+    assert_eq!(CentiDegrees::encoded_len(&-12.5), 2);
+    let parsed = Reading::twpb_decode(&buffer[0..bytes_written]).unwrap();
+    assert_eq!(parsed, reading);
+}
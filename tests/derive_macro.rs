@@ -1,3 +1,6 @@
+// predates running clippy, keeps its original style
+#![allow(clippy::map_clone)]
+
 mod types;
 
 use types::{Simple, Embedded, embedded};
@@ -60,3 +63,11 @@ fn test_oneof_embedded(){
     assert_eq!(parsed, expected);
     println!("{:?}", parsed);
 }
+
+#[test]
+fn test_oneof_field_numbers(){
+    // field number 2 is not part of the oneof, it's unknown to Embedded
+    assert_eq!(embedded::Content::TWPB_FIELD_NUMBERS, &[1, 3]);
+    let parsed = Embedded::twpb_decode_iter([0x10, 0x01].iter().copied()).unwrap();
+    assert_eq!(parsed, Embedded::default());
+}
@@ -0,0 +1,76 @@
+// twpb-dump is only built with the std feature, `cargo test --features std`.
+#![cfg(feature = "std")]
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn dump(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_twpb-dump"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn test_dump_raw() {
+    let output = dump(&["tests/files/bin/python.oneof.embedded.bin"], "");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "\
+0000  1 {
+0002    1: \"serial\"
+000a    2: \"firmware\"
+0014    3: \"vendor\"
+001c    4: \"product\"
+      }
+0025  5: \"something else\"
+");
+}
+
+#[test]
+fn test_dump_hex_stdin() {
+    let output = dump(&[], "08 96 01\n1d 01 02 03 04");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0000  1: 150\n0003  3: 0x04030201\n");
+}
+
+#[test]
+fn test_dump_schema() {
+    let output = dump(&["-p", "tests/files/proto/api/api.proto", "-I", "tests/files/proto", "-m", "api.Message",
+        "tests/files/bin/python.api.getInfo.bin"], "");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0000  v1_request {\n0002    getInfo {}\n      }\n");
+
+    // packed repeated fields are split into their values
+    let output = dump(&["--proto", "tests/files/proto/types.proto", "--message", "RepeatedTypes",
+        "tests/files/bin/python.types.repeated.bin"], "");
+    assert!(output.status.success());
+    let lines: Vec<_> = stdout(&output).lines().collect();
+    assert_eq!(lines[0], "0000  int32: [4, -300]");
+    assert_eq!(lines[4], "0024  sint32: [-69, 69]");
+    assert_eq!(lines[10], "0068  double: [1, 3.1415926535]");
+    assert_eq!(lines[14], "008e  string: \"अरे\"");
+    assert_eq!(lines[17], "00a5  int32_notpacked: 4");
+}
+
+#[test]
+fn test_dump_malformed() {
+    // the length of field 2 runs past the end, the fields before it are still printed
+    let output = dump(&[], "08 96 01 12 03 0a 01");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "0000  1: 150\n");
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.contains("offset 0x0003"), "{}", stderr);
+
+    let output = dump(&[], "0g");
+    assert_eq!(output.status.code(), Some(2));
+    let output = dump(&["-p", "tests/files/proto/simple.proto", "-m", "Missing"], "");
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,227 @@
+#![cfg(feature = "alloc")]
+// Test values mirror the ones written by the Python generator, keep them verbatim.
+#![allow(clippy::approx_constant)]
+
+use twpb::{DecodeError, MessageEncoder};
+use twpb::dynamic::{DescriptorError, DescriptorPool, DynamicError, DynamicValue};
+use twpb::encoder;
+use twpb::reflect::{Label, ProtoType};
+use twpb::wire_types;
+
+// A FileDescriptorSet as `protoc -o` writes it, built by hand to not depend on protoc.
+
+fn len_field(out: &mut Vec<u8>, number: u32, value: &[u8]) {
+    encoder::tag(out, &number, &wire_types::LENGTHDELIMITED).unwrap();
+    encoder::leb128_u32(out, &(value.len() as u32)).unwrap();
+    out.extend_from_slice(value);
+}
+
+fn varint_field(out: &mut Vec<u8>, number: u32, value: u64) {
+    encoder::tag(out, &number, &wire_types::VARINT).unwrap();
+    encoder::leb128(out, &value).unwrap();
+}
+
+// FieldDescriptorProto, `proto_type` and `label` are the numbers of its enums
+fn field(name: &str, number: u64, label: u64, proto_type: u64, type_name: Option<&str>, oneof: Option<u64>) -> Vec<u8> {
+    let mut out = vec![];
+    len_field(&mut out, 1, name.as_bytes());
+    varint_field(&mut out, 3, number);
+    varint_field(&mut out, 4, label);
+    varint_field(&mut out, 5, proto_type);
+    if let Some(type_name) = type_name {
+        len_field(&mut out, 6, type_name.as_bytes());
+    }
+    if let Some(oneof) = oneof {
+        varint_field(&mut out, 9, oneof);
+    }
+    out
+}
+
+// DescriptorProto
+fn message(name: &str, fields: &[Vec<u8>], nested: &[Vec<u8>], oneofs: &[&str]) -> Vec<u8> {
+    let mut out = vec![];
+    len_field(&mut out, 1, name.as_bytes());
+    for field in fields {
+        len_field(&mut out, 2, field);
+    }
+    for message in nested {
+        len_field(&mut out, 3, message);
+    }
+    for oneof in oneofs {
+        let mut decl = vec![];
+        len_field(&mut decl, 1, oneof.as_bytes());
+        len_field(&mut out, 8, &decl);
+    }
+    out
+}
+
+// FileDescriptorSet with one FileDescriptorProto per entry
+fn descriptor_set(files: &[(&str, &str, Vec<Vec<u8>>)]) -> Vec<u8> {
+    let mut out = vec![];
+    for (name, package, messages) in files {
+        let mut file = vec![];
+        len_field(&mut file, 1, name.as_bytes());
+        if !package.is_empty() {
+            len_field(&mut file, 2, package.as_bytes());
+        }
+        for message in messages {
+            len_field(&mut file, 4, message);
+        }
+        len_field(&mut file, 12, b"proto3");
+        len_field(&mut out, 1, &file);
+    }
+    out
+}
+
+const OPTIONAL: u64 = 1;
+const REPEATED: u64 = 3;
+
+fn pool() -> DescriptorPool {
+    let scalar_types = [("int32", 5), ("int64", 3), ("uint32", 13), ("uint64", 4), ("sint32", 17), ("sint64", 18),
+        ("fixed32", 7), ("fixed64", 6), ("sfixed32", 15), ("sfixed64", 16), ("double", 1), ("float", 2),
+        ("bool", 8), ("string", 9), ("bytes", 12)];
+    let repeated: Vec<_> = scalar_types.iter().enumerate()
+        .map(|(i, (name, proto_type))| field(name, i as u64 + 1, REPEATED, *proto_type, None, None))
+        .chain([field("int32_notpacked", 16, REPEATED, 5, None, None)])
+        .collect();
+
+    let set = descriptor_set(&[
+        ("simple.proto", "", vec![
+            message("SuperSimple", &[
+                field("serial_number", 1, OPTIONAL, 9, None, None),
+                field("firmware_version", 2, OPTIONAL, 9, None, None),
+                field("vendor", 3, OPTIONAL, 9, None, None),
+                field("product", 4, OPTIONAL, 9, None, None),
+            ], &[], &[]),
+            message("Message", &[
+                field("ss", 1, OPTIONAL, 11, Some(".SuperSimple"), Some(0)),
+                field("test", 3, OPTIONAL, 9, None, Some(0)),
+                field("something_else", 5, OPTIONAL, 9, None, None),
+            ], &[], &["content"]),
+        ]),
+        ("types.proto", "", vec![message("RepeatedTypes", &repeated, &[], &[])]),
+        ("gateway.proto", "gw", vec![
+            message("Frame", &[
+                field("device", 1, OPTIONAL, 9, None, None),
+                field("readings", 2, REPEATED, 11, Some(".gw.Frame.Reading"), None),
+                field("origin", 3, OPTIONAL, 11, Some(".SuperSimple"), None),
+            ], &[
+                message("Reading", &[
+                    field("offset", 1, OPTIONAL, 17, None, None),
+                    field("state", 2, OPTIONAL, 14, Some(".gw.State"), None),
+                ], &[], &[]),
+            ], &[]),
+        ]),
+    ]);
+    DescriptorPool::decode(&set).unwrap()
+}
+
+#[test]
+fn test_dynamic_descriptors() {
+    let pool = pool();
+    let names: Vec<_> = pool.messages().map(|m| m.full_name()).collect();
+    assert_eq!(names, ["SuperSimple", "Message", "RepeatedTypes", "gw.Frame", "gw.Frame.Reading"]);
+
+    let message_type = pool.message(".Message").unwrap();
+    let ss = message_type.field_by_name("ss").unwrap();
+    assert_eq!((ss.number, ss.proto_type, ss.label, ss.oneof), (1, ProtoType::Message, Label::Oneof, Some(0)));
+    assert_eq!(message_type.oneofs(), ["content"]);
+    assert_eq!(message_type.message_type(ss), pool.message("SuperSimple"));
+    assert_eq!(pool.message("gw.Frame").unwrap().field(2).unwrap().label, Label::Repeated);
+    assert!(pool.message("Frame").is_none());
+
+    // every referenced message must be in the set
+    let set = descriptor_set(&[("a.proto", "", vec![
+        message("A", &[field("b", 1, OPTIONAL, 11, Some(".B"), None)], &[], &[]),
+    ])]);
+    assert_eq!(DescriptorPool::decode(&set).unwrap_err(), DescriptorError::UnknownType(".B".to_owned()));
+    let set = descriptor_set(&[("a.proto", "", vec![
+        message("A", &[field("g", 1, OPTIONAL, 10, None, None)], &[], &[]),
+    ])]);
+    assert_eq!(DescriptorPool::decode(&set).unwrap_err(), DescriptorError::UnsupportedField("g".to_owned()));
+    assert!(DescriptorPool::decode(&[0x0a, 0x05]).is_err());
+}
+
+#[test]
+fn test_dynamic_decode() {
+    let pool = pool();
+    let message = pool.message("Message").unwrap()
+        .decode(&std::fs::read("tests/files/bin/python.oneof.embedded.bin").unwrap()).unwrap();
+    assert_eq!(message.get("something_else"), Some(&DynamicValue::String("something else".to_owned())));
+    assert_eq!(message.get("test"), None);
+    match message.get("ss") {
+        Some(DynamicValue::Message(ss)) => {
+            assert_eq!(ss.message_type().full_name(), "SuperSimple");
+            assert_eq!(ss.get("vendor"), Some(&DynamicValue::String("vendor".to_owned())));
+        },
+        other => panic!("{:?}", other),
+    }
+
+    // packed and non-packed repeated fields
+    let buffer = std::fs::read("tests/files/bin/python.types.repeated.bin").unwrap();
+    let repeated = pool.message("RepeatedTypes").unwrap().decode(&buffer).unwrap();
+    assert_eq!(repeated.get("int32"), Some(&DynamicValue::List(vec![DynamicValue::Int32(4), DynamicValue::Int32(-300)])));
+    assert_eq!(repeated.get("sint32"), Some(&DynamicValue::List(vec![DynamicValue::Int32(-69), DynamicValue::Int32(69)])));
+    assert_eq!(repeated.get("double"), Some(&DynamicValue::List(vec![DynamicValue::Double(1.0), DynamicValue::Double(3.1415926535)])));
+    assert_eq!(repeated.get("int32_notpacked"), Some(&DynamicValue::List(vec![DynamicValue::Int32(4), DynamicValue::Int32(-300)])));
+    assert_eq!(repeated.fields().count(), 16);
+
+    // the same bytes again, with repeated fields non-packed
+    let mut encoded = vec![];
+    repeated.twpb_encode(&mut encoded).unwrap();
+    assert_eq!(encoded.len(), repeated.twpb_encoded_len());
+    assert_eq!(pool.message("RepeatedTypes").unwrap().decode(&encoded).unwrap(), repeated);
+
+    // string instead of varint
+    let error = pool.message("gw.Frame.Reading").unwrap().decode(&[0x0a, 0x00]).unwrap_err();
+    assert_eq!(error, DecodeError::WrongWireType(wire_types::LENGTHDELIMITED, "DynamicMessage"));
+}
+
+#[test]
+fn test_dynamic_encode() {
+    let pool = pool();
+    let frame_type = pool.message("gw.Frame").unwrap();
+    let reading_type = pool.message("gw.Frame.Reading").unwrap();
+
+    let mut reading = reading_type.new_message();
+    reading.set("offset", DynamicValue::Int32(-2)).unwrap();
+    reading.set("state", DynamicValue::Enum(1)).unwrap();
+    let mut frame = frame_type.new_message();
+    frame.set("device", DynamicValue::String("lamp".to_owned())).unwrap();
+    frame.set("readings", DynamicValue::List(vec![DynamicValue::Message(reading)])).unwrap();
+
+    assert_eq!(frame.set("nope", DynamicValue::Bool(true)), Err(DynamicError::UnknownField("nope".to_owned())));
+    assert_eq!(frame.set("device", DynamicValue::Bytes(vec![])), Err(DynamicError::TypeMismatch("device".to_owned())));
+    // a message of another type, and a single value for a repeated field
+    let other = pool.message("SuperSimple").unwrap().new_message();
+    assert_eq!(frame.set("readings", DynamicValue::List(vec![DynamicValue::Message(other.clone())])),
+        Err(DynamicError::TypeMismatch("readings".to_owned())));
+    assert_eq!(frame.set("readings", DynamicValue::Message(other)), Err(DynamicError::TypeMismatch("readings".to_owned())));
+
+    let mut buffer = [0u8; 32];
+    let len = frame.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(buffer[..len], [0x0a, 0x04, b'l', b'a', b'm', b'p', 0x12, 0x04, 0x08, 0x03, 0x10, 0x01]);
+
+    // unknown fields are kept
+    let mut with_unknown = buffer[..len].to_vec();
+    with_unknown.extend_from_slice(&[0x25, 1, 2, 3, 4]);
+    let decoded = frame_type.decode(&with_unknown).unwrap();
+    assert_eq!(decoded.unknown_fields(), [0x25, 1, 2, 3, 4]);
+    let mut encoded = vec![];
+    decoded.twpb_encode(&mut encoded).unwrap();
+    assert_eq!(encoded, with_unknown);
+
+    // setting a oneof member clears the others
+    let mut message = pool.message("Message").unwrap().new_message();
+    message.set("test", DynamicValue::String("a".to_owned())).unwrap();
+    message.set("ss", DynamicValue::Message(pool.message("SuperSimple").unwrap().new_message())).unwrap();
+    assert_eq!(message.get("test"), None);
+    if let Some(DynamicValue::Message(ss)) = message.get_mut("ss") {
+        ss.set("vendor", DynamicValue::String("v".to_owned())).unwrap();
+    }
+    let mut buffer = vec![];
+    message.twpb_encode(&mut buffer).unwrap();
+    assert_eq!(buffer, [0x0a, 0x03, 0x1a, 0x01, b'v']);
+    assert!(message.clear("ss").is_some());
+    assert_eq!(message.twpb_encoded_len(), 0);
+}
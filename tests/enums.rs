@@ -0,0 +1,20 @@
+use twpb::json::EnumNames;
+
+#[derive(Debug, PartialEq, Clone, Copy, ::twpb_derive::Enum)]
+#[twpb(enum)]
+pub enum PowerState {
+    Off,
+    Standby = 5,
+    On,
+}
+
+#[test]
+fn test_proto_enum() {
+    assert_eq!(i32::from(PowerState::Standby), 5);
+    assert_eq!(i32::from(PowerState::On), 6);
+    assert_eq!(PowerState::try_from(0), Ok(PowerState::Off));
+    assert_eq!(PowerState::try_from(6), Ok(PowerState::On));
+    assert_eq!(PowerState::try_from(1), Err(()));
+    // prefixed with the enum name, in SCREAMING_SNAKE_CASE
+    assert_eq!(PowerState::NAMES, [("POWER_STATE_OFF", 0), ("POWER_STATE_STANDBY", 5), ("POWER_STATE_ON", 6)]);
+}
@@ -0,0 +1,129 @@
+use twpb::{MessageEncoder, MessageDecoder};
+
+// shared by all telemetry messages, always at field numbers 1-3
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Header {
+    #[twpb(uint32, nr=1)]
+    pub seq: u32,
+    #[twpb(fixed64, nr=2)]
+    pub timestamp: u64,
+    #[twpb(string, nr=3)]
+    pub device_id: heapless::String<16>,
+}
+
+#[derive(Debug, PartialEq, ::twpb_derive::Enum)]
+pub enum Level {
+    #[twpb(sint32, nr=5)]
+    Raw(i32),
+    #[twpb(float, nr=6)]
+    Scaled(f32),
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Battery {
+    #[twpb(flatten)]
+    pub header: Header,
+    #[twpb(uint32, nr=4)]
+    pub millivolts: u32,
+    #[twpb(oneof)]
+    pub level: Option<Level>,
+}
+
+// the flattened header's fields are the first fields of an alarm
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Alarm {
+    #[twpb(flatten)]
+    pub header: Header,
+    #[twpb(string, nr=4)]
+    pub text: heapless::String<32>,
+}
+
+// flattened messages can be flattened again
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Forwarded {
+    #[twpb(flatten)]
+    pub alarm: Alarm,
+    #[twpb(uint32, nr=10)]
+    pub hops: u32,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Samples<const N: usize> {
+    #[twpb(flatten)]
+    pub header: Header,
+    #[twpb(sint32, repeated, nr=4)]
+    pub values: heapless::Vec<i32, N>,
+}
+
+fn header() -> Header {
+    Header {
+        seq: 7,
+        timestamp: 1,
+        device_id: heapless::String::from("dev"),
+    }
+}
+
+#[test]
+fn test_flatten() {
+    let message = Battery {
+        header: header(),
+        millivolts: 3300,
+        level: Some(Level::Raw(-1)),
+    };
+    let mut buffer = [0u8; 64];
+    let len = message.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(&buffer[0..len], &[
+        0x08, 0x07,
+        0x11, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x1a, 0x03, b'd', b'e', b'v',
+        0x20, 0xe4, 0x19,
+        0x28, 0x01,
+    ]);
+    assert_eq!(message.twpb_encoded_len(), len);
+    assert_eq!(Battery::twpb_decode_iter(buffer[0..len].iter().copied()), Ok(message));
+
+    // the header fields can come in any order, mixed with the others
+    let parsed = Battery::twpb_decode_iter([0x20, 0x01, 0x08, 0x02].iter().copied()).unwrap();
+    assert_eq!(parsed.header.seq, 2);
+    assert_eq!(parsed.millivolts, 1);
+}
+
+#[test]
+fn test_flatten_header_only() {
+    // any message flattening Header can be read as just a Header
+    let message = Alarm {
+        header: header(),
+        text: heapless::String::from("overheat"),
+    };
+    let mut buffer = [0u8; 64];
+    let len = message.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(Header::twpb_decode_iter(buffer[0..len].iter().copied()), Ok(header()));
+}
+
+#[test]
+fn test_nested_flatten() {
+    let message = Forwarded {
+        alarm: Alarm {
+            header: header(),
+            text: heapless::String::from("overheat"),
+        },
+        hops: 2,
+    };
+    let mut buffer = [0u8; 64];
+    let len = message.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(message.twpb_encoded_len(), len);
+    assert_eq!(Forwarded::twpb_decode_iter(buffer[0..len].iter().copied()), Ok(message));
+    assert!((1..20).filter(|&n| Forwarded::TWPB_FIELD_NUMBERS.contains(n)).eq([1, 2, 3, 4, 10]));
+}
+
+#[test]
+fn test_generic_flatten() {
+    let message = Samples::<4> {
+        header: header(),
+        values: heapless::Vec::from_slice(&[1, -1]).unwrap(),
+    };
+    let mut buffer = [0u8; 64];
+    let len = message.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(message.twpb_encoded_len(), len);
+    assert_eq!(Samples::<4>::twpb_decode_iter(buffer[0..len].iter().copied()), Ok(message));
+}
@@ -1,3 +1,6 @@
+// predates running clippy, keeps its original style
+#![allow(clippy::approx_constant, clippy::char_lit_as_u8, clippy::excessive_precision)]
+
 mod types;
 
 use std::fs;
@@ -0,0 +1,133 @@
+use twpb::{DecodeError, MessageEncoder, MessageDecoder, ScalarCodec, Writer, WriterError};
+
+// sizes picked by the user of the message
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Named<const N: usize> {
+    #[twpb(string, nr=1)]
+    pub name: heapless::String<N>,
+    #[twpb(uint32, repeated, nr=2)]
+    pub values: heapless::Vec<u32, N>,
+}
+
+// a header around any message
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Envelope<T>
+where T: MessageEncoder + MessageDecoder + Default {
+    #[twpb(uint32, nr=1)]
+    pub id: u32,
+    #[twpb(message, nr=2)]
+    pub payload: Option<T>,
+}
+
+#[derive(Debug, PartialEq, ::twpb_derive::Enum)]
+pub enum Value<const N: usize> {
+    #[twpb(string, nr=1)]
+    Text(heapless::String<N>),
+    #[twpb(sint64, nr=2)]
+    Number(i64),
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Entry<const N: usize> {
+    #[twpb(oneof)]
+    pub value: Option<Value<N>>,
+    #[twpb(bool, nr=3)]
+    pub valid: bool,
+}
+
+// Encodes borrowed strings without copying them, decodes into an owned one.
+#[derive(Debug, PartialEq)]
+pub enum Label<'a> {
+    Borrowed(&'a str),
+    Owned(heapless::String<16>),
+}
+
+impl Default for Label<'_> {
+    fn default() -> Self {
+        Label::Owned(heapless::String::new())
+    }
+}
+
+impl Label<'_> {
+    fn as_str(&self) -> &str {
+        match self {
+            Label::Borrowed(s) => s,
+            Label::Owned(s) => s.as_str(),
+        }
+    }
+}
+
+pub struct LabelCodec;
+
+impl<'a> ScalarCodec<Label<'a>> for LabelCodec {
+    const WIRE_TYPE: u8 = twpb::wire_types::LENGTHDELIMITED;
+
+    fn encode(buffer: &mut impl Writer, value: &Label<'a>) -> Result<usize, WriterError> {
+        let mut bytes_written = twpb::encoder::leb128_u32(buffer, &(value.as_str().len() as u32))?;
+        bytes_written += buffer.write_all(value.as_str().as_bytes())?;
+        Ok(bytes_written)
+    }
+
+    fn decode<I>(bytes: I, field_name: &'static str) -> Result<Label<'a>, DecodeError>
+    where I: Iterator<Item = u8> {
+        twpb::decoder::string(bytes, field_name).map(Label::Owned)
+    }
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Labeled<'a> {
+    #[twpb(codec="LabelCodec", nr=1)]
+    pub label: Label<'a>,
+}
+
+#[test]
+fn test_const_generics() {
+    let expected = Named::<4> {
+        name: heapless::String::from("abcd"),
+        values: heapless::Vec::from_slice(&[1, 2, 3]).unwrap(),
+    };
+    let mut buffer = [0u8; 32];
+    let len = expected.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(expected.twpb_encoded_len(), len);
+    assert_eq!(Named::<4>::twpb_decode_iter(buffer[0..len].iter().copied()), Ok(expected));
+
+    // doesn't fit in a smaller instantiation
+    assert_eq!(Named::<2>::twpb_decode_iter(buffer[0..len].iter().copied()), Err(DecodeError::FieldOverflow("name")));
+}
+
+#[test]
+fn test_type_generics() {
+    let expected = Envelope {
+        id: 3,
+        payload: Some(Named::<8>{name: heapless::String::from("x"), values: heapless::Vec::new()}),
+    };
+    let mut buffer = [0u8; 32];
+    let len = expected.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(&buffer[0..len], &[0x08, 0x03, 0x12, 0x03, 0x0a, 0x01, b'x']);
+    assert_eq!(Envelope::twpb_decode_iter(buffer[0..len].iter().copied()), Ok(expected));
+}
+
+#[test]
+fn test_generic_oneof() {
+    let expected = Entry::<8> {
+        value: Some(Value::Text(heapless::String::from("hi"))),
+        valid: true,
+    };
+    let mut buffer = [0u8; 32];
+    let len = expected.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(&buffer[0..len], &[0x0a, 0x02, b'h', b'i', 0x18, 0x01]);
+    assert_eq!(Entry::twpb_decode_iter(buffer[0..len].iter().copied()), Ok(expected));
+    assert_eq!(Value::<8>::TWPB_FIELD_NUMBERS, &[1, 2]);
+}
+
+#[test]
+fn test_lifetimes() {
+    let text = heapless::String::<16>::from("borrowed");
+    let expected = Labeled { label: Label::Borrowed(text.as_str()) };
+    let mut buffer = [0u8; 32];
+    let len = expected.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(expected.twpb_encoded_len(), len);
+
+    let parsed = Labeled::twpb_decode_iter(buffer[0..len].iter().copied()).unwrap();
+    assert_eq!(parsed.label, Label::Owned(text.clone()));
+}
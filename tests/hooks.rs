@@ -0,0 +1,54 @@
+use twpb::{DecodeError, MessageEncoder, MessageDecoder};
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(after_decode = "Measurement::after_decode")]
+pub struct Measurement {
+    #[twpb(sint32, nr=1)]
+    pub centi_degrees: i32,
+    #[twpb(uint32, nr=2)]
+    pub sensor: u32,
+    // runtime bookkeeping, never sent
+    #[twpb(skip)]
+    pub received_at: Option<u64>,
+    // derived from centi_degrees
+    #[twpb(skip)]
+    pub degrees: f32,
+}
+
+impl Measurement {
+    fn after_decode(&mut self) -> Result<(), DecodeError> {
+        if self.sensor == 0 {
+            return Err(DecodeError::Rejected("sensor"));
+        }
+        self.degrees = self.centi_degrees as f32 / 100.0;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_skip() {
+    let message = Measurement {
+        centi_degrees: -150,
+        sensor: 1,
+        received_at: Some(1234),
+        degrees: 99.0,
+    };
+    let mut buffer = [0u8; 32];
+    let len = message.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(&buffer[0..len], &[0x08, 0xab, 0x02, 0x10, 0x01]);
+    assert_eq!(message.twpb_encoded_len(), len);
+
+    let parsed = Measurement::twpb_decode_iter(buffer[0..len].iter().copied()).unwrap();
+    assert_eq!(parsed, Measurement {
+        centi_degrees: -150,
+        sensor: 1,
+        received_at: None,
+        degrees: -1.5,
+    });
+}
+
+#[test]
+fn test_after_decode_rejects() {
+    let parsed = Measurement::twpb_decode_iter([0x08, 0x02].iter().copied());
+    assert_eq!(parsed, Err(DecodeError::Rejected("sensor")));
+}
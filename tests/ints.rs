@@ -1,3 +1,6 @@
+// predates running clippy, keeps its original style
+#![allow(clippy::unnecessary_cast)]
+
 #[test]
 fn test_ints() {
     let mut buffer = [0x0; 100];
@@ -52,4 +55,14 @@ fn test_ints_overflow() {
     assert_eq!(bytes_written, 10);
     let result = ::twpb::decoder::sint32(buffer.into_iter(), "").unwrap_err();
     assert_eq!(result, ::twpb::decoder::DecodeError::TooLargeVarint);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_sint32_min() {
+    let mut buffer = [0x0; 100];
+    let bytes_written = ::twpb::encoder::sint32(&mut buffer.as_mut(), &i32::MIN).unwrap();
+    assert_eq!(bytes_written, 5);
+    assert_eq!(buffer[0..bytes_written], [0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+    let result = ::twpb::decoder::sint32(buffer.into_iter(), "").unwrap();
+    assert_eq!(result, i32::MIN);
+}
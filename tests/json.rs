@@ -0,0 +1,243 @@
+mod types;
+
+use twpb::{DecodeError, JsonEncoder, JsonDecoder};
+use twpb::json::EnumNames;
+use types::Mode;
+
+#[derive(Debug, PartialEq, Default, Clone, Copy, ::twpb_derive::Message)]
+#[twpb(transparent, json)]
+pub struct Serial(#[twpb(fixed64)] pub u64);
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(json)]
+pub struct Header {
+    #[twpb(uint32, nr=1)]
+    pub seq: u32,
+    #[twpb(fixed64, nr=2)]
+    pub timestamp_ms: u64,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(json)]
+pub struct Point {
+    #[twpb(sint32, nr=1)]
+    pub x: i32,
+    #[twpb(sint32, nr=2)]
+    pub y: i32,
+}
+
+#[derive(Debug, PartialEq, ::twpb_derive::Enum)]
+#[twpb(json)]
+pub enum Target {
+    #[twpb(message, nr=10)]
+    Point(Point),
+    #[twpb(string, nr=11)]
+    Name(heapless::String<8>),
+    #[twpb(message, nr=12)]
+    Home,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(json)]
+pub struct Scalars {
+    #[twpb(int32, nr=1)]
+    pub int32: i32,
+    #[twpb(sint64, nr=2)]
+    pub sint64: i64,
+    #[twpb(uint64, nr=3)]
+    pub uint64: u64,
+    #[twpb(float, nr=4)]
+    pub float: f32,
+    #[twpb(double, nr=5)]
+    pub double: f64,
+    #[twpb(bool, nr=6)]
+    pub flag: bool,
+    #[twpb(string, nr=7)]
+    pub text: heapless::String<16>,
+    #[twpb(bytes, nr=8)]
+    pub raw_data: heapless::Vec<u8, 8>,
+    #[twpb(enum, nr=9)]
+    pub mode: Mode,
+    #[twpb(uint32, nr=10)]
+    pub small: u8,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(json)]
+pub struct Command {
+    #[twpb(flatten)]
+    pub header: Header,
+    #[twpb(fixed64, nr=3, json_name = "serialNumber")]
+    pub serial: Serial,
+    #[twpb(sint32, repeated, nr=4)]
+    pub values: heapless::Vec<i32, 4>,
+    #[twpb(fixed32, repeated, nr=5)]
+    pub pair: [u32; 2],
+    #[twpb(message, nr=6)]
+    pub origin: Option<Point>,
+    #[twpb(oneof)]
+    pub target: Option<Target>,
+}
+
+// f32 fractions <-> uint32 percent, in JSON too
+mod percent {
+    use twpb::{DecodeError, Writer, WriterError};
+    use twpb::codec::UInt32;
+    use twpb::json::{JsonCodec, JsonParser};
+
+    pub fn encode(buffer: &mut impl Writer, value: &f32) -> Result<usize, WriterError> {
+        ::twpb::encoder::uint32(buffer, &((value * 100.0) as u32))
+    }
+
+    pub fn decode<I>(bytes: I, field_name: &'static str) -> Result<f32, DecodeError>
+    where I: Iterator<Item = u8> {
+        ::twpb::decoder::uint32(bytes, field_name).map(|value| value as f32 / 100.0)
+    }
+
+    pub fn encoded_len(value: &f32) -> usize {
+        ::twpb::encoder::leb128_len(&((value * 100.0) as u64))
+    }
+
+    pub fn encode_json(buffer: &mut impl Writer, value: &f32) -> Result<usize, WriterError> {
+        <UInt32 as JsonCodec<u32>>::encode_json(buffer, &((value * 100.0) as u32))
+    }
+
+    pub fn decode_json(parser: &mut JsonParser, field_name: &'static str) -> Result<f32, DecodeError> {
+        <UInt32 as JsonCodec<u32>>::decode_json(parser, field_name).map(|value| value as f32 / 100.0)
+    }
+
+    pub fn is_default(value: &f32) -> bool {
+        *value == 0.0
+    }
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(json)]
+pub struct Battery {
+    #[twpb(uint32, with = "percent", nr=1)]
+    pub level: f32,
+}
+
+fn encode(message: &impl JsonEncoder) -> heapless::String<256> {
+    let mut buffer = [0u8; 256];
+    let len = message.twpb_encode_json(&mut buffer.as_mut()).unwrap();
+    heapless::String::from(core::str::from_utf8(&buffer[..len]).unwrap())
+}
+
+#[test]
+fn test_enum_names() {
+    // derived from the Rust names, prefixed with the enum name
+    assert_eq!(<Mode as EnumNames>::NAMES, [("MODE_OFF", 0), ("MODE_ON", 1), ("MODE_AUTO", 2)]);
+    assert_eq!(i32::from(Mode::Auto), 2);
+    assert_eq!(Mode::try_from(1), Ok(Mode::On));
+    assert_eq!(Mode::try_from(3), Err(()));
+}
+
+#[test]
+fn test_scalars() {
+    let message = Scalars {
+        int32: -1,
+        sint64: -2,
+        uint64: u64::MAX,
+        float: 0.5,
+        double: f64::INFINITY,
+        flag: true,
+        text: heapless::String::from("a\"b"),
+        raw_data: heapless::Vec::from_slice(&[0xde, 0xad, 0xbe, 0xef]).unwrap(),
+        mode: Mode::On,
+        small: 7,
+    };
+    let json = encode(&message);
+    assert_eq!(json, concat!(
+        r#"{"int32":-1,"sint64":"-2","uint64":"18446744073709551615","float":0.5,"double":"Infinity","#,
+        r#""flag":true,"text":"a\"b","rawData":"3q2+7w==","mode":"MODE_ON","small":7}"#));
+    assert_eq!(Scalars::twpb_decode_json(json.as_bytes()), Ok(message));
+}
+
+#[test]
+fn test_defaults_left_out() {
+    assert_eq!(encode(&Scalars::default()), "{}");
+    assert_eq!(Scalars::twpb_decode_json(b" { } "), Ok(Scalars::default()));
+}
+
+#[test]
+fn test_with_defaults_left_out() {
+    assert_eq!(encode(&Battery { level: 0.5 }), r#"{"level":50}"#);
+    assert_eq!(encode(&Battery::default()), "{}");
+    assert_eq!(Battery::twpb_decode_json(br#"{"level":50}"#), Ok(Battery { level: 0.5 }));
+}
+
+#[test]
+fn test_lenient_decoding() {
+    // proto field names, numbers as strings and the other way around, enums by number, null
+    let json = br#"{
+        "raw_data": "3q2-7w",
+        "int32": "-1",
+        "sint64": -2,
+        "float": "0.5",
+        "mode": 1,
+        "text": null,
+        "unknown": {"nested": [1, 2, {}]}
+    }"#;
+    let parsed = Scalars::twpb_decode_json(json).unwrap();
+    assert_eq!(parsed, Scalars {
+        int32: -1,
+        sint64: -2,
+        float: 0.5,
+        raw_data: heapless::Vec::from_slice(&[0xde, 0xad, 0xbe, 0xef]).unwrap(),
+        mode: Mode::On,
+        ..Default::default()
+    });
+}
+
+#[test]
+fn test_messages() {
+    let message = Command {
+        header: Header { seq: 1, timestamp_ms: 1000 },
+        serial: Serial(5),
+        values: heapless::Vec::from_slice(&[-1, 2]).unwrap(),
+        pair: [3, 4],
+        origin: Some(Point { x: 0, y: -3 }),
+        target: Some(Target::Name(heapless::String::from("dock"))),
+    };
+    let json = encode(&message);
+    assert_eq!(json, concat!(
+        r#"{"seq":1,"timestampMs":"1000","serialNumber":"5","values":[-1,2],"pair":[3,4],"#,
+        r#""origin":{"y":-3},"name":"dock"}"#));
+    assert_eq!(Command::twpb_decode_json(json.as_bytes()), Ok(message));
+
+    let message = Command {
+        target: Some(Target::Home),
+        ..Default::default()
+    };
+    let json = encode(&message);
+    assert_eq!(json, r#"{"pair":[0,0],"home":{}}"#);
+    assert_eq!(Command::twpb_decode_json(json.as_bytes()), Ok(message));
+
+    let message = Command {
+        target: Some(Target::Point(Point::default())),
+        ..Default::default()
+    };
+    let json = encode(&message);
+    assert_eq!(json, r#"{"pair":[0,0],"point":{}}"#);
+    assert_eq!(Command::twpb_decode_json(json.as_bytes()), Ok(message));
+
+    // null is the default value of repeated fields too
+    let parsed = Command::twpb_decode_json(br#"{"values":null,"pair":[1,2],"pair":null}"#).unwrap();
+    assert_eq!(parsed, Command::default());
+}
+
+#[test]
+fn test_errors() {
+    assert_eq!(Command::twpb_decode_json(br#"{"pair": [1]}"#), Err(DecodeError::LengthMismatch("pair")));
+    assert_eq!(Command::twpb_decode_json(br#"{"values": [1, 2, 3, 4, 5]}"#), Err(DecodeError::FieldOverflow("values")));
+    assert_eq!(Scalars::twpb_decode_json(br#"{"int32": 1.5}"#), Err(DecodeError::InvalidJson(10)));
+    assert_eq!(Scalars::twpb_decode_json(br#"{"small": 256}"#), Err(DecodeError::ValueOutOfRange("small")));
+    assert_eq!(Scalars::twpb_decode_json(br#"{"mode": "MODE_ECO"}"#), Err(DecodeError::InvalidJson(9)));
+    assert_eq!(Scalars::twpb_decode_json(br#"{"flag": true,}"#), Err(DecodeError::InvalidJson(14)));
+    assert_eq!(Scalars::twpb_decode_json(br#"{"flag": true} x"#), Err(DecodeError::InvalidJson(15)));
+
+    // two members of the same oneof
+    assert_eq!(Command::twpb_decode_json(br#"{"name": "a", "home": {}}"#), Err(DecodeError::InvalidJson(21)));
+    assert_eq!(Command::twpb_decode_json(br#"{"name": "a", "name": "b"}"#), Err(DecodeError::InvalidJson(21)));
+}
@@ -0,0 +1,256 @@
+use twpb::{mask, DecodeError, MessageDecoder, MessageEncoder, Reflect, ScalarCodec, Writer, WriterError};
+use twpb::codec::SInt32;
+use twpb::reflect::{ReflectCodec, ReflectError, Value};
+
+#[derive(Debug, PartialEq, Default, Clone, ::twpb_derive::Message)]
+#[twpb(reflect)]
+pub struct Header {
+    #[twpb(uint32, nr=1)]
+    pub seq: u32,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, ::twpb_derive::Message)]
+#[twpb(reflect)]
+pub struct Limits {
+    #[twpb(sint32, nr=1)]
+    pub low: i32,
+    #[twpb(sint32, nr=2)]
+    pub high: i32,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, ::twpb_derive::Message)]
+#[twpb(reflect)]
+pub struct Settings {
+    #[twpb(string, nr=1)]
+    pub vendor: heapless::String<8>,
+    #[twpb(uint32, nr=2)]
+    pub brightness: u32,
+    #[twpb(message, nr=3)]
+    pub limits: Option<Limits>,
+}
+
+#[derive(Debug, PartialEq, Clone, ::twpb_derive::Enum)]
+#[twpb(reflect)]
+pub enum Command {
+    #[twpb(message, nr=10)]
+    Reset,
+    #[twpb(message, nr=11)]
+    Configure(Settings),
+    #[twpb(sint32, nr=12)]
+    Temperature(i32),
+}
+
+#[derive(Debug, PartialEq, Default, Clone, ::twpb_derive::Message)]
+#[twpb(reflect)]
+pub struct Calibration {
+    #[twpb(float, repeated, nr=1)]
+    pub factors: [f32; 2],
+}
+
+#[derive(Debug, PartialEq, Default, Clone, ::twpb_derive::Message)]
+#[twpb(reflect)]
+pub struct Device {
+    #[twpb(flatten)]
+    pub header: Header,
+    #[twpb(string, nr=2)]
+    pub name: heapless::String<8>,
+    #[twpb(message, nr=3)]
+    pub settings: Option<Settings>,
+    #[twpb(fixed32, repeated, nr=4)]
+    pub readings: heapless::Vec<u32, 4>,
+    #[twpb(oneof)]
+    pub command: Option<Command>,
+}
+
+// Degrees as tenths on the wire, a custom codec field that masks can't name.
+pub struct Tenths;
+
+impl ScalarCodec<f32> for Tenths {
+    const WIRE_TYPE: u8 = twpb::wire_types::VARINT;
+
+    fn encode(buffer: &mut impl Writer, value: &f32) -> Result<usize, WriterError> {
+        <SInt32 as ScalarCodec<i32>>::encode(buffer, &((value * 10.0) as i32))
+    }
+
+    fn decode<I>(bytes: I, field_name: &'static str) -> Result<f32, DecodeError>
+    where I: Iterator<Item = u8> {
+        <SInt32 as ScalarCodec<i32>>::decode(bytes, field_name).map(|value| value as f32 / 10.0)
+    }
+}
+
+impl ReflectCodec<f32> for Tenths {
+    fn to_value(value: &f32) -> Value<'_> {
+        Value::Float(*value)
+    }
+
+    fn from_value(value: Value, field_name: &'static str) -> Result<f32, ReflectError> {
+        match value {
+            Value::Float(value) => Ok(value),
+            _ => Err(ReflectError::TypeMismatch(field_name)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Clone, ::twpb_derive::Message)]
+#[twpb(reflect)]
+pub struct Thermostat {
+    #[twpb(uint32, nr=1)]
+    pub seq: u32,
+    #[twpb(codec = "Tenths", nr=2)]
+    pub target: f32,
+}
+
+fn device() -> Device {
+    Device {
+        header: Header { seq: 7 },
+        name: "lamp".into(),
+        settings: Some(Settings { vendor: "acme".into(), brightness: 30, limits: Some(Limits { low: -5, high: 5 }) }),
+        readings: heapless::Vec::from_slice(&[1, 2]).unwrap(),
+        command: Some(Command::Temperature(-3)),
+    }
+}
+
+fn encode(message: &Device, mask: &[&[u32]]) -> Vec<u8> {
+    let mut buffer = [0u8; 128];
+    let len = mask::encode(&mut buffer.as_mut(), message, mask).unwrap();
+    assert_eq!(len, mask::encoded_len(message, mask));
+    buffer[..len].to_vec()
+}
+
+#[test]
+fn test_mask_encode() {
+    let device = device();
+    assert_eq!(encode(&device, &[]), []);
+    assert_eq!(encode(&device, &[&[2]]), [0x12, 0x04, b'l', b'a', b'm', b'p']);
+
+    // flattened fields, nested paths, repeated fields and oneof variants
+    let encoded = encode(&device, &[&[1], &[3, 2], &[4], &[12]]);
+    assert_eq!(encoded, [0x1a, 0x02, 0x10, 0x1e, 0x25, 1, 0, 0, 0, 0x25, 2, 0, 0, 0, 0x60, 0x05, 0x08, 0x07]);
+    let decoded = Device::twpb_decode(&encoded).unwrap();
+    assert_eq!(decoded.header.seq, 7);
+    assert_eq!(decoded.settings, Some(Settings { brightness: 30, ..Default::default() }));
+    assert_eq!(decoded.readings, [1, 2]);
+    assert_eq!(decoded.command, Some(Command::Temperature(-3)));
+    assert_eq!(decoded.name, "");
+
+    // a path ending at a message selects all of it, like the derived encoder would write it
+    let mut device = device;
+    device.settings.as_mut().unwrap().brightness = 0;
+    let encoded = encode(&device, &[&[3]]);
+    assert_eq!(Device::twpb_decode(&encoded).unwrap().settings, device.settings);
+    let mut whole = [0u8; 128];
+    let len = device.settings.as_ref().unwrap().twpb_encode(&mut whole.as_mut()).unwrap();
+    assert_eq!(encoded.len(), 2 + len);
+
+    // unset messages and variants that aren't selected are left out, so are unknown paths
+    let device = Device { command: Some(Command::Reset), ..Default::default() };
+    assert_eq!(encode(&device, &[&[3], &[3, 3, 1], &[11], &[99], &[2, 1]]), []);
+    assert_eq!(encode(&device, &[&[10]]), [0x52, 0x00]);
+}
+
+#[test]
+fn test_mask_merge() {
+    // "update settings": only the brightness and the low limit are applied
+    let mut current = device();
+    let update = Device {
+        name: "other".into(),
+        settings: Some(Settings { vendor: "x".into(), brightness: 80, limits: Some(Limits { low: -9, high: 9 }) }),
+        ..Default::default()
+    };
+    let mask: &[&[u32]] = &[&[3, 2], &[3, 3, 1]];
+    let received = Device::twpb_decode(&encode(&update, mask)).unwrap();
+    mask::merge(&mut current, &received, mask).unwrap();
+
+    let mut expected = device();
+    let settings = expected.settings.as_mut().unwrap();
+    settings.brightness = 80;
+    settings.limits.as_mut().unwrap().low = -9;
+    assert_eq!(current, expected);
+}
+
+#[test]
+fn test_mask_merge_replace_and_clear() {
+    let source = Device {
+        settings: Some(Settings { brightness: 1, ..Default::default() }),
+        readings: heapless::Vec::from_slice(&[9]).unwrap(),
+        command: Some(Command::Reset),
+        ..Default::default()
+    };
+
+    // a path ending at a message replaces it, repeated fields are replaced too
+    let mut target = device();
+    mask::merge(&mut target, &source, &[&[3], &[4], &[10]]).unwrap();
+    assert_eq!(target.settings, source.settings);
+    assert_eq!(target.readings, [9]);
+    assert_eq!(target.command, Some(Command::Reset));
+    assert_eq!(target.name, "lamp");
+
+    // selected fields that are unset in the source are cleared
+    let mut target = device();
+    mask::merge(&mut target, &Device::default(), &[&[1], &[2], &[3, 3], &[4], &[12]]).unwrap();
+    assert_eq!(target.header.seq, 0);
+    assert_eq!(target.name, "");
+    assert_eq!(target.settings, Some(Settings { vendor: "acme".into(), brightness: 30, limits: None }));
+    assert_eq!(target.readings, []);
+    assert_eq!(target.command, None);
+
+    // fixed size arrays always have all their values
+    let mut calibration = Calibration { factors: [0.5, 1.5] };
+    mask::merge(&mut calibration, &Calibration { factors: [2.0, 3.0] }, &[&[1]]).unwrap();
+    assert_eq!(calibration.factors, [2.0, 3.0]);
+    mask::merge(&mut calibration, &Calibration::default(), &[&[1]]).unwrap();
+    assert_eq!(calibration.factors, [0.0, 0.0]);
+
+    // a variant that isn't selected in the target stays unselected, a nested path into an unset message stays unset
+    let mut target = Device { command: Some(Command::Reset), ..Default::default() };
+    mask::merge(&mut target, &Device::default(), &[&[12], &[3, 3, 1]]).unwrap();
+    assert_eq!(target.command, Some(Command::Reset));
+    assert_eq!(target.settings, None);
+
+    // selecting a message variant through a nested path
+    let source = Device { command: Some(Command::Configure(Settings { brightness: 5, ..Default::default() })), ..Default::default() };
+    let mut target = device();
+    mask::merge(&mut target, &source, &[&[11, 2]]).unwrap();
+    assert_eq!(target.command, Some(Command::Configure(Settings { brightness: 5, ..Default::default() })));
+}
+
+#[test]
+fn test_mask_check() {
+    let descriptor = Device::DESCRIPTOR;
+    assert_eq!(mask::check(descriptor, &[&[1], &[3, 3, 2], &[11, 1], &[10]]), Ok(()));
+    assert_eq!(mask::check(descriptor, &[&[99]]), Err(ReflectError::UnknownField(99)));
+    assert_eq!(mask::check(descriptor, &[&[3, 7]]), Err(ReflectError::UnknownField(7)));
+    assert_eq!(mask::check(descriptor, &[&[2, 1]]), Err(ReflectError::TypeMismatch("name")));
+
+    let mut target = device();
+    assert_eq!(mask::merge(&mut target, &Device::default(), &[&[2], &[4, 1]]), Err(ReflectError::TypeMismatch("readings")));
+    assert_eq!(target, device());
+
+    // codec fields can't be written from their value, so masks can't name them
+    assert_eq!(mask::check(Thermostat::DESCRIPTOR, &[&[1], &[2]]), Err(ReflectError::CustomCodec("target")));
+    let mut thermostat = Thermostat { seq: 1, target: 20.5 };
+    let source = Thermostat { seq: 2, target: 18.0 };
+    assert_eq!(mask::merge(&mut thermostat, &source, &[&[2]]), Err(ReflectError::CustomCodec("target")));
+    assert_eq!(thermostat.target, 20.5);
+    let mut buffer = [0u8; 16];
+    let len = mask::encode(&mut buffer.as_mut(), &source, &[&[1], &[2]]).unwrap();
+    assert_eq!(&buffer[..len], &[0x08, 2]);
+}
+
+#[test]
+fn test_reflect_clear() {
+    let mut device = device();
+    device.clear(2).unwrap();
+    device.clear(1).unwrap();
+    device.clear(4).unwrap();
+    device.clear(11).unwrap();
+    assert_eq!((device.name.as_str(), device.header.seq, device.readings.len()), ("", 0, 0));
+    assert_eq!(device.get(12), Some(Value::Int32(-3)));
+    device.clear(12).unwrap();
+    assert_eq!(device.command, None);
+    device.get_mut(3).unwrap().clear(3).unwrap();
+    assert_eq!(device.settings.as_ref().unwrap().limits, None);
+    device.clear(3).unwrap();
+    assert_eq!(device.settings, None);
+    assert_eq!(device.clear(99), Err(ReflectError::UnknownField(99)));
+}
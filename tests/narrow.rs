@@ -0,0 +1,54 @@
+use twpb::{MessageEncoder, MessageDecoder, DecodeError};
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Status {
+    #[twpb(uint32,nr=1)]
+    pub battery_percent: u8,
+    #[twpb(uint32,nr=2)]
+    pub voltage: u16,
+    #[twpb(int32,nr=3)]
+    pub rssi: i8,
+    #[twpb(sint32,nr=4)]
+    pub temperature: i16,
+    #[twpb(uint32,repeated,nr=5)]
+    pub channels: heapless::Vec<u8, 4>,
+}
+
+#[test]
+fn test_narrow_ints() {
+    let status = Status {
+        battery_percent: 100,
+        voltage: 3300,
+        rssi: -70,
+        temperature: -40,
+        channels: heapless::Vec::from_slice(&[1, 6, 11]).unwrap(),
+    };
+    let mut buffer = [0x0; 100];
+    let bytes_written = status.twpb_encode(&mut buffer.as_mut()).unwrap();
+    // narrow fields are encoded exactly like their 32 bit counterparts
+    assert_eq!(buffer[0..bytes_written], [
+        0x08, 0x64,
+        0x10, 0xE4, 0x19,
+        0x18, 0xBA, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01,
+        0x20, 0x4F,
+        0x28, 0x01, 0x28, 0x06, 0x28, 0x0B,
+    ]);
+    let parsed = Status::twpb_decode(&buffer[0..bytes_written]).unwrap();
+    assert_eq!(parsed, status);
+}
+
+#[test]
+fn test_narrow_ints_out_of_range() {
+    // uint32 300 does not fit a u8
+    let result = Status::twpb_decode(&[0x08, 0xAC, 0x02]).unwrap_err();
+    assert_eq!(result, DecodeError::ValueOutOfRange("battery_percent"));
+    // int32 -300 does not fit an i8
+    let result = Status::twpb_decode(&[0x18, 0xD4, 0xFD, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]).unwrap_err();
+    assert_eq!(result, DecodeError::ValueOutOfRange("rssi"));
+    // sint32 70000 does not fit an i16
+    let result = Status::twpb_decode(&[0x20, 0xE0, 0xC5, 0x08]).unwrap_err();
+    assert_eq!(result, DecodeError::ValueOutOfRange("temperature"));
+    // neither do repeated values
+    let result = Status::twpb_decode(&[0x2A, 0x03, 0x01, 0x80, 0x02]).unwrap_err();
+    assert_eq!(result, DecodeError::ValueOutOfRange("channels"));
+}
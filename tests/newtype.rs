@@ -0,0 +1,71 @@
+use twpb::{MessageEncoder, MessageDecoder};
+
+// a single field message
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct DeviceId(#[twpb(fixed64, nr=1)] pub u64);
+
+// a tuple struct message
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Reading(
+    #[twpb(uint32, nr=1)] pub u32,
+    #[twpb(string, nr=2)] pub heapless::String<8>,
+    #[twpb(sint32, repeated, nr=3)] pub heapless::Vec<i32, 4>,
+);
+
+// newtypes that are encoded like their inner scalar
+#[derive(Debug, PartialEq, Default, Clone, Copy, ::twpb_derive::Message)]
+#[twpb(transparent)]
+pub struct Serial(#[twpb(fixed64)] pub u64);
+
+#[derive(Debug, PartialEq, Default, Clone, ::twpb_derive::Message)]
+#[twpb(transparent)]
+pub struct Name {
+    #[twpb(string)]
+    pub value: heapless::String<8>,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Device {
+    #[twpb(fixed64, nr=1)]
+    pub serial: Serial,
+    #[twpb(string, nr=2)]
+    pub name: Name,
+    #[twpb(fixed64, repeated, nr=3)]
+    pub peers: heapless::Vec<Serial, 4>,
+    #[twpb(message, nr=4)]
+    pub id: Option<DeviceId>,
+}
+
+#[test]
+fn test_tuple_structs() {
+    let expected = DeviceId(42);
+    let mut buffer = [0u8; 32];
+    let len = expected.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(&buffer[0..len], &[0x09, 42, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(DeviceId::twpb_decode_iter(buffer[0..len].iter().copied()), Ok(expected));
+
+    let expected = Reading(3, heapless::String::from("temp"), heapless::Vec::from_slice(&[-1, 1]).unwrap());
+    let len = expected.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(expected.twpb_encoded_len(), len);
+    assert_eq!(Reading::twpb_decode_iter(buffer[0..len].iter().copied()), Ok(expected));
+}
+
+#[test]
+fn test_transparent() {
+    let expected = Device {
+        serial: Serial(7),
+        name: Name{value: heapless::String::from("dev")},
+        peers: heapless::Vec::from_slice(&[Serial(1)]).unwrap(),
+        id: Some(DeviceId(2)),
+    };
+    let mut buffer = [0u8; 64];
+    let len = expected.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(&buffer[0..len], &[
+        0x09, 7, 0, 0, 0, 0, 0, 0, 0,
+        0x12, 0x03, b'd', b'e', b'v',
+        0x19, 1, 0, 0, 0, 0, 0, 0, 0,
+        0x22, 0x09, 0x09, 2, 0, 0, 0, 0, 0, 0, 0,
+    ]);
+    assert_eq!(expected.twpb_encoded_len(), len);
+    assert_eq!(Device::twpb_decode_iter(buffer[0..len].iter().copied()), Ok(expected));
+}
@@ -0,0 +1,178 @@
+mod types;
+
+use twpb::{DecodeError, MessageEncoder, MessageDecoder};
+use types::Mode;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MacAddress([u8; 6]);
+
+// MacAddress <-> bytes
+mod mac_address {
+    use super::MacAddress;
+    use twpb::{DecodeError, Writer, WriterError};
+
+    pub fn encode(buffer: &mut impl Writer, value: &MacAddress) -> Result<usize, WriterError> {
+        ::twpb::encoder::bytes_array(buffer, &value.0)
+    }
+
+    pub fn decode<I>(bytes: I, field_name: &'static str) -> Result<MacAddress, DecodeError>
+    where I: Iterator<Item = u8> {
+        ::twpb::decoder::bytes_array(bytes, field_name).map(MacAddress)
+    }
+
+    pub fn encoded_len(_value: &MacAddress) -> usize {
+        7
+    }
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Point {
+    #[twpb(sint32, nr=1)]
+    pub x: i32,
+    #[twpb(sint32, nr=2)]
+    pub y: i32,
+}
+
+#[derive(Debug, PartialEq, ::twpb_derive::Enum)]
+pub enum Setting {
+    #[twpb(uint32, nr=10)]
+    Brightness(u32),
+    #[twpb(enum, nr=11)]
+    Mode(Mode),
+}
+
+// every kind of variant
+#[derive(Debug, PartialEq, ::twpb_derive::Enum)]
+pub enum Command {
+    #[twpb(sint32, nr=1)]
+    Offset(i32),
+    #[twpb(string, nr=2)]
+    Label(heapless::String<16>),
+    #[twpb(message, nr=3)]
+    Target(Point),
+    #[twpb(bytes, with="mac_address", nr=4)]
+    Mac(MacAddress),
+    #[twpb(oneof)]
+    Setting(Setting),
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Request {
+    #[twpb(oneof)]
+    pub command: Option<Command>,
+    #[twpb(uint32, nr=20)]
+    pub id: u32,
+    #[twpb(enum, nr=21)]
+    pub mode: Mode,
+    #[twpb(enum, repeated, nr=22)]
+    pub modes: heapless::Vec<Mode, 4>,
+}
+
+fn roundtrip(command: Command) -> heapless::Vec<u8, 64> {
+    let expected = Request {
+        command: Some(command),
+        id: 7,
+        ..Default::default()
+    };
+    let mut buffer = [0u8; 64];
+    let len = expected.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(expected.twpb_encoded_len(), len);
+    let parsed = Request::twpb_decode_iter(buffer[0..len].iter().copied()).unwrap();
+    assert_eq!(parsed, expected);
+    heapless::Vec::from_slice(&buffer[0..len]).unwrap()
+}
+
+#[test]
+fn test_variants() {
+    assert_eq!(roundtrip(Command::Offset(-2)), [0x08, 0x03, 0xa0, 0x01, 0x07, 0xa8, 0x01, 0x00]);
+    assert_eq!(roundtrip(Command::Label(heapless::String::from("on"))), [0x12, 0x02, b'o', b'n', 0xa0, 0x01, 0x07, 0xa8, 0x01, 0x00]);
+    assert_eq!(roundtrip(Command::Target(Point{x: 1, y: -1})), [0x1a, 0x04, 0x08, 0x02, 0x10, 0x01, 0xa0, 0x01, 0x07, 0xa8, 0x01, 0x00]);
+    assert_eq!(roundtrip(Command::Target(Point::default())), [0x1a, 0x04, 0x08, 0x00, 0x10, 0x00, 0xa0, 0x01, 0x07, 0xa8, 0x01, 0x00]);
+    assert_eq!(roundtrip(Command::Mac(MacAddress([1, 2, 3, 4, 5, 6]))), [0x22, 0x06, 1, 2, 3, 4, 5, 6, 0xa0, 0x01, 0x07, 0xa8, 0x01, 0x00]);
+}
+
+#[test] // the selected variant is always written, even when it holds a default value
+fn test_default_variants() {
+    assert_eq!(roundtrip(Command::Offset(0)), [0x08, 0x00, 0xa0, 0x01, 0x07, 0xa8, 0x01, 0x00]);
+    assert_eq!(roundtrip(Command::Label(heapless::String::new())), [0x12, 0x00, 0xa0, 0x01, 0x07, 0xa8, 0x01, 0x00]);
+    assert_eq!(roundtrip(Command::Setting(Setting::Mode(Mode::Off))), [0x58, 0x00, 0xa0, 0x01, 0x07, 0xa8, 0x01, 0x00]);
+}
+
+#[test]
+fn test_nested_oneof() {
+    assert_eq!(Command::TWPB_FIELD_NUMBERS, &[1, 2, 3, 4, 10, 11]);
+
+    // a nested oneof is indistinguishable from a flat one on the wire
+    assert_eq!(roundtrip(Command::Setting(Setting::Brightness(5))), [0x50, 0x05, 0xa0, 0x01, 0x07, 0xa8, 0x01, 0x00]);
+    assert_eq!(roundtrip(Command::Setting(Setting::Mode(Mode::Auto))), [0x58, 0x02, 0xa0, 0x01, 0x07, 0xa8, 0x01, 0x00]);
+}
+
+#[test]
+fn test_enum_fields() {
+    let expected = Request {
+        command: None,
+        id: 0,
+        mode: Mode::On,
+        modes: heapless::Vec::from_slice(&[Mode::Auto, Mode::Off]).unwrap(),
+    };
+    let mut buffer = [0u8; 64];
+    let len = expected.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(&buffer[0..len], &[0xa0, 0x01, 0x00, 0xa8, 0x01, 0x01, 0xb0, 0x01, 0x02, 0xb0, 0x01, 0x00]);
+    let parsed = Request::twpb_decode_iter(buffer[0..len].iter().copied()).unwrap();
+    assert_eq!(parsed, expected);
+
+    // values unknown to the Rust enum
+    let parsed = Request::twpb_decode_iter([0xa8, 0x01, 0x03].iter().copied());
+    assert_eq!(parsed, Err(DecodeError::ValueOutOfRange("mode")));
+    let parsed = Request::twpb_decode_iter([0x58, 0x03].iter().copied());
+    assert_eq!(parsed, Err(DecodeError::ValueOutOfRange("Setting::Mode")));
+}
+
+// The API example from tests/types, without a struct for every empty request
+#[derive(Debug, PartialEq, ::twpb_derive::Enum)]
+pub enum V1Request {
+    #[twpb(message, nr=1)]
+    GetInfo,
+    #[twpb(message, nr=2)]
+    GetOtherThing,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct V1RequestMessage {
+    #[twpb(oneof)]
+    pub request: Option<V1Request>,
+}
+
+#[derive(Debug, PartialEq, ::twpb_derive::Enum)]
+pub enum ApiContent {
+    #[twpb(message, nr=1)]
+    V1Request(V1RequestMessage),
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct ApiMessage {
+    #[twpb(oneof)]
+    pub content: Option<ApiContent>,
+}
+
+#[test]
+fn test_unit_variants() {
+    let dummydata = include_bytes!("files/bin/python.api.getInfo.bin");
+    let expected = ApiMessage {
+        content: Some(ApiContent::V1Request(V1RequestMessage{request: Some(V1Request::GetInfo)})),
+    };
+    let parsed = ApiMessage::twpb_decode_iter(dummydata.iter().copied()).unwrap();
+    assert_eq!(parsed, expected);
+
+    let mut buffer = [0u8; 16];
+    let len = expected.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(&buffer[0..len], dummydata);
+    assert_eq!(expected.twpb_encoded_len(), len);
+
+    // whatever the embedded message holds, it's ignored
+    let parsed = V1RequestMessage::twpb_decode_iter([0x12, 0x02, 0x08, 0x01].iter().copied()).unwrap();
+    assert_eq!(parsed.request, Some(V1Request::GetOtherThing));
+
+    let parsed = V1RequestMessage::twpb_decode_iter([0x10, 0x01].iter().copied());
+    assert_eq!(parsed, Err(DecodeError::WrongWireType(0, "V1Request::GetOtherThing")));
+}
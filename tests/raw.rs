@@ -0,0 +1,84 @@
+use twpb::MessageEncoder;
+use twpb::decoder::{DecodeError, RawFieldIter, RawValue};
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Settings {
+    #[twpb(string, nr=1)]
+    pub vendor: heapless::String<8>,
+    #[twpb(sint32, nr=2)]
+    pub offset: i32,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Frame {
+    #[twpb(uint64, nr=1)]
+    pub id: u64,
+    #[twpb(fixed32, nr=2)]
+    pub crc: u32,
+    #[twpb(double, nr=3)]
+    pub gain: f64,
+    #[twpb(message, nr=4)]
+    pub settings: Option<Settings>,
+    #[twpb(bytes, nr=5)]
+    pub raw_data: heapless::Vec<u8, 8>,
+}
+
+#[test]
+fn test_raw_fields() {
+    let frame = Frame {
+        id: 300,
+        crc: 0xdeadbeef,
+        gain: 1.5,
+        settings: Some(Settings { vendor: heapless::String::from("x"), offset: -1 }),
+        raw_data: heapless::Vec::from_slice(&[0xff]).unwrap(),
+    };
+    let mut buffer = [0u8; 64];
+    let len = frame.twpb_encode(&mut buffer.as_mut()).unwrap();
+
+    let mut fields = RawFieldIter::new(&buffer[..len]);
+    assert_eq!(fields.next(), Some(Ok((1, RawValue::Varint(300)))));
+    assert_eq!(fields.position(), 3);
+    assert_eq!(fields.next(), Some(Ok((2, RawValue::Fixed32(0xdeadbeef)))));
+    assert_eq!(fields.next(), Some(Ok((3, RawValue::Fixed64(1.5f64.to_bits())))));
+
+    // the embedded message is recognized as one
+    let (number, settings) = fields.next().unwrap().unwrap();
+    assert_eq!(number, 4);
+    assert_eq!(settings, RawValue::LengthDelimited(&[0x0a, 0x01, b'x', 0x10, 0x01]));
+    let nested: Result<heapless::Vec<_, 4>, _> = settings.as_message().unwrap().collect();
+    assert_eq!(nested.unwrap(), [(1, RawValue::LengthDelimited(b"x")), (2, RawValue::Varint(1))]);
+
+    // a single 0xff can't be a message, nor can an empty value or a scalar
+    let (_, raw_data) = fields.next().unwrap().unwrap();
+    assert_eq!(raw_data, RawValue::LengthDelimited(&[0xff]));
+    assert!(raw_data.as_message().is_none());
+    assert!(RawValue::LengthDelimited(&[]).as_message().is_none());
+    assert!(RawValue::Varint(8).as_message().is_none());
+
+    assert_eq!(fields.next(), None);
+    assert_eq!(fields.position(), len);
+}
+
+#[test]
+fn test_raw_errors() {
+    // cut off in the middle of a value
+    let mut fields = RawFieldIter::new(&[0x08, 0x01, 0x15, 0x01, 0x02]);
+    assert_eq!(fields.next(), Some(Ok((1, RawValue::Varint(1)))));
+    assert_eq!(fields.next(), Some(Err(DecodeError::UnexpectedEndOfBuffer)));
+    assert_eq!(fields.position(), 2);
+    assert_eq!(fields.next(), None);
+
+    // length past the end of the buffer
+    let mut fields = RawFieldIter::new(&[0x0a, 0x05, b'a']);
+    assert_eq!(fields.next(), Some(Err(DecodeError::UnexpectedEndOfBuffer)));
+
+    // tag without value
+    let mut fields = RawFieldIter::new(&[0x08]);
+    assert_eq!(fields.next(), Some(Err(DecodeError::UnexpectedEndOfBuffer)));
+
+    // groups and field number 0
+    let mut fields = RawFieldIter::new(&[0x0b, 0x0c]);
+    assert_eq!(fields.next(), Some(Err(DecodeError::UnsupportedWireType(3))));
+    let mut fields = RawFieldIter::new(&[0x00, 0x00]);
+    assert_eq!(fields.next(), Some(Err(DecodeError::UnknownFieldNumber(0))));
+}
@@ -0,0 +1,117 @@
+#![cfg(feature = "pool")]
+
+use heapless::pool::{Box, Pool};
+use twpb::{MessageEncoder, MessageDecoder, DecodeError, BoxPool};
+
+// A menu where every entry can have a submenu
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Menu {
+    #[twpb(string,nr=1)]
+    pub title: heapless::String<10>,
+    #[twpb(message,nr=2)]
+    pub submenu: Option<Box<Menu>>,
+    #[twpb(message,nr=3)]
+    pub footer: Option<Footer>,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Footer {
+    #[twpb(string,nr=1)]
+    pub text: heapless::String<10>,
+}
+
+static MENUS: Pool<Menu> = Pool::new();
+
+impl BoxPool for Menu {
+    fn pool() -> &'static Pool<Menu> {
+        &MENUS
+    }
+}
+
+fn grow_pool() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        MENUS.grow(std::boxed::Box::leak(vec![0u8; 64 * 1024].into_boxed_slice()));
+    });
+}
+
+fn menu(depth: usize) -> Menu {
+    let mut m = Menu{title: heapless::String::from("level"), ..Default::default()};
+    if depth > 0 {
+        m.submenu = Some(MENUS.alloc().unwrap().init(menu(depth - 1)));
+    } else {
+        m.footer = Some(Footer{text: heapless::String::from("end")});
+    }
+    m
+}
+
+#[test]
+fn test_recursive() {
+    grow_pool();
+    let source = menu(2);
+    let mut buffer = [0x0; 100];
+    let bytes_written = source.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(bytes_written, source.twpb_encoded_len());
+    assert_eq!(buffer[0..bytes_written], [
+        0x0A, 0x05, b'l', b'e', b'v', b'e', b'l',
+        0x12, 0x17,
+            0x0A, 0x05, b'l', b'e', b'v', b'e', b'l',
+            0x12, 0x0E,
+                0x0A, 0x05, b'l', b'e', b'v', b'e', b'l',
+                0x1A, 0x05,
+                    0x0A, 0x03, b'e', b'n', b'd',
+    ]);
+    let parsed = Menu::twpb_decode(&buffer[0..bytes_written]).unwrap();
+    assert_eq!(parsed, source);
+}
+
+#[test]
+fn test_recursion_limit() {
+    grow_pool();
+    let source = menu(5);
+    let mut buffer = [0x0; 200];
+    let bytes_written = source.twpb_encode(&mut buffer.as_mut()).unwrap();
+
+    // 5 submenus + 1 footer
+    assert!(Menu::twpb_decode_with_limit(&buffer[0..bytes_written], 6).is_ok());
+    let result = Menu::twpb_decode_with_limit(&buffer[0..bytes_written], 5).unwrap_err();
+    assert_eq!(result, DecodeError::RecursionLimit);
+
+    // the default limit protects against maliciously deep nesting
+    let mut deep = [0x0; 1000];
+    let mut len = 0;
+    for _ in 0..200 {
+        // an empty submenu, with the previous one inside of it
+        let mut next = [0x0; 1000];
+        next[0] = 0x12;
+        let l = ::twpb::encoder::leb128_u32(&mut next[1..].as_mut(), &(len as u32)).unwrap();
+        next[1 + l..1 + l + len].copy_from_slice(&deep[0..len]);
+        len += 1 + l;
+        deep = next;
+    }
+    let result = Menu::twpb_decode(&deep[0..len]).unwrap_err();
+    assert_eq!(result, DecodeError::RecursionLimit);
+}
+
+#[cfg(feature = "alloc")]
+mod alloc_box {
+    use twpb::{MessageEncoder, MessageDecoder};
+
+    #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+    pub struct Node {
+        #[twpb(uint32,nr=1)]
+        pub value: u32,
+        #[twpb(message,nr=2)]
+        pub next: Option<Box<Node>>,
+    }
+
+    #[test]
+    fn test_recursive_alloc() {
+        let source = Node{value: 1, next: Some(Box::new(Node{value: 2, next: None}))};
+        let mut buffer = [0x0; 100];
+        let bytes_written = source.twpb_encode(&mut buffer.as_mut()).unwrap();
+        assert_eq!(buffer[0..bytes_written], [0x08, 0x01, 0x12, 0x02, 0x08, 0x02]);
+        let parsed = Node::twpb_decode(&buffer[0..bytes_written]).unwrap();
+        assert_eq!(parsed, source);
+    }
+}
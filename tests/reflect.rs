@@ -0,0 +1,173 @@
+mod types;
+
+use twpb::Reflect;
+use twpb::reflect::{Label, ProtoType, ReflectError, Value};
+use types::{Mode, SimpleTypes};
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(reflect)]
+pub struct Header {
+    #[twpb(uint32, nr=1)]
+    pub seq: u32,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(reflect)]
+pub struct Settings {
+    #[twpb(string, nr=1)]
+    pub vendor: heapless::String<8>,
+    #[twpb(enum, nr=2)]
+    pub mode: Mode,
+}
+
+#[derive(Debug, PartialEq, ::twpb_derive::Enum)]
+#[twpb(reflect)]
+pub enum Command {
+    #[twpb(message, nr=10)]
+    Reset,
+    #[twpb(message, nr=11)]
+    Configure(Settings),
+    #[twpb(oneof)]
+    Set(Setpoint),
+}
+
+#[derive(Debug, PartialEq, ::twpb_derive::Enum)]
+#[twpb(reflect)]
+pub enum Setpoint {
+    #[twpb(sint32, nr=12)]
+    Temperature(i32),
+    #[twpb(uint32, nr=13)]
+    Brightness(u8),
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(reflect)]
+pub struct Device {
+    #[twpb(flatten)]
+    pub header: Header,
+    #[twpb(string, nr=2)]
+    pub name: heapless::String<8>,
+    #[twpb(message, nr=3)]
+    pub settings: Option<Settings>,
+    #[twpb(fixed32, repeated, nr=4)]
+    pub readings: heapless::Vec<u32, 2>,
+    #[twpb(bytes, nr=5)]
+    pub key: [u8; 2],
+    #[twpb(float, repeated, nr=6)]
+    pub calibration: [f32; 2],
+    #[twpb(oneof)]
+    pub command: Option<Command>,
+}
+
+#[test]
+fn test_descriptor() {
+    let descriptor = SimpleTypes::DESCRIPTOR;
+    assert_eq!(descriptor.name, "SimpleTypes");
+    assert_eq!(descriptor.fields.len(), 15);
+    let field = descriptor.field(3).unwrap();
+    assert_eq!(field.name, "uint32");
+    assert_eq!(field.proto_type, ProtoType::UInt32);
+    assert_eq!(field.label, Label::Singular);
+    // the proto name, not that of the Rust field
+    assert_eq!(descriptor.field_by_name("bool").unwrap().number, 13);
+    assert!(descriptor.field(16).is_none());
+
+    let descriptor = Device::DESCRIPTOR;
+    let settings = descriptor.field(3).unwrap();
+    assert_eq!(settings.proto_type, ProtoType::Message);
+    let mode = settings.message_descriptor().unwrap().field(2).unwrap();
+    assert_eq!(mode.proto_type, ProtoType::Enum);
+    assert_eq!(mode.enum_descriptor().unwrap().values[1], ("MODE_ON", 1));
+    assert_eq!(descriptor.field(4).unwrap().label, Label::Repeated);
+
+    // oneof variants, also those of nested oneofs, and flattened fields are fields of the message
+    let brightness = descriptor.field(13).unwrap();
+    assert_eq!((brightness.name, brightness.label), ("brightness", Label::Oneof));
+    assert_eq!(descriptor.field_by_name("seq").unwrap().number, 1);
+    assert_eq!(descriptor.field(10).unwrap().message_descriptor().unwrap().fields.len(), 0);
+    assert_eq!(descriptor.oneofs[0].0, "command");
+    assert_eq!(descriptor.oneofs[0].1.nested[0].name, "Setpoint");
+
+    let mut numbers = vec![];
+    descriptor.for_each_field(|field| numbers.push(field.number));
+    assert_eq!(numbers, [2, 3, 4, 5, 6, 10, 11, 12, 13, 1]);
+}
+
+#[test]
+fn test_reflect_get_set() {
+    let mut device = Device::default();
+    assert_eq!(device.descriptor().name, "Device");
+
+    device.set(2, Value::String("lamp")).unwrap();
+    device.set(1, Value::UInt32(7)).unwrap();
+    assert_eq!(device.name, "lamp");
+    assert_eq!(device.header.seq, 7);
+    assert_eq!(device.get(2), Some(Value::String("lamp")));
+    assert_eq!(device.get(1), Some(Value::UInt32(7)));
+
+    assert_eq!(device.set(2, Value::UInt32(1)), Err(ReflectError::TypeMismatch("name")));
+    assert_eq!(device.set(2, Value::String("too long for it")), Err(ReflectError::FieldOverflow("name")));
+    assert_eq!(device.set(5, Value::Bytes(&[1])), Err(ReflectError::ValueOutOfRange("key")));
+    assert_eq!(device.set(99, Value::Bool(true)), Err(ReflectError::UnknownField(99)));
+    device.set(5, Value::Bytes(&[1, 2])).unwrap();
+    assert_eq!(device.key, [1, 2]);
+
+    // unset messages are None until changed through get_mut
+    assert_eq!(device.get(3), None);
+    let settings = device.get_mut(3).unwrap();
+    settings.set(2, Value::Enum(1)).unwrap();
+    assert_eq!(settings.set(2, Value::Enum(5)), Err(ReflectError::ValueOutOfRange("mode")));
+    assert_eq!(device.settings.as_ref().unwrap().mode, Mode::On);
+    match device.get(3) {
+        Some(Value::Message(settings)) => assert_eq!(settings.get(2), Some(Value::Enum(1))),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn test_reflect_repeated() {
+    let mut device = Device::default();
+    device.set_index(4, 0, Value::UInt32(1)).unwrap();
+    device.set_index(4, 1, Value::UInt32(2)).unwrap();
+    device.set_index(4, 0, Value::UInt32(3)).unwrap();
+    assert_eq!(device.set_index(4, 2, Value::UInt32(4)), Err(ReflectError::FieldOverflow("readings")));
+    assert_eq!(device.set_index(4, 5, Value::UInt32(4)), Err(ReflectError::IndexOutOfRange("readings")));
+    assert_eq!(device.repeated_len(4), 2);
+    assert_eq!(device.get_index(4, 0), Some(Value::UInt32(3)));
+    assert_eq!(device.get(4), None);
+    device.truncate(4, 1).unwrap();
+    assert_eq!(device.readings, [3]);
+
+    // fixed size arrays always have all their values
+    assert_eq!(device.repeated_len(6), 2);
+    device.set_index(6, 1, Value::Float(0.5)).unwrap();
+    assert_eq!(device.calibration, [0.0, 0.5]);
+    assert_eq!(device.set_index(6, 2, Value::Float(0.5)), Err(ReflectError::IndexOutOfRange("calibration")));
+    assert_eq!(device.truncate(6, 1), Err(ReflectError::FixedSize("calibration")));
+}
+
+#[test]
+fn test_reflect_oneof() {
+    let mut device = Device::default();
+    assert_eq!(device.get(12), None);
+
+    device.set(12, Value::Int32(-5)).unwrap();
+    assert_eq!(device.command, Some(Command::Set(Setpoint::Temperature(-5))));
+    assert_eq!(device.get(12), Some(Value::Int32(-5)));
+    assert_eq!(device.get(13), None);
+
+    // a failed set keeps the selected variant
+    assert_eq!(device.set(13, Value::UInt32(300)), Err(ReflectError::ValueOutOfRange("brightness")));
+    assert_eq!(device.command, Some(Command::Set(Setpoint::Temperature(-5))));
+    device.set(13, Value::UInt32(30)).unwrap();
+    assert_eq!(device.get(13), Some(Value::UInt32(30)));
+
+    // message variants are selected by get_mut, unit variants by set
+    device.get_mut(11).unwrap().set(1, Value::String("acme")).unwrap();
+    assert!(matches!(&device.command, Some(Command::Configure(s)) if s.vendor == "acme"));
+    assert!(device.get_mut(10).is_none());
+    device.set(10, Value::Message(&())).unwrap();
+    assert_eq!(device.command, Some(Command::Reset));
+    assert!(matches!(device.get(10), Some(Value::Message(_))));
+    assert_eq!(device.set(11, Value::Bool(true)), Err(ReflectError::TypeMismatch("configure")));
+}
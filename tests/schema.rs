@@ -0,0 +1,97 @@
+mod types;
+
+use twpb::WriterError;
+use twpb::reflect::MessageDescriptor;
+use types::gateway::Device;
+
+fn schema(imports: &[&str], roots: &[&'static MessageDescriptor]) -> String {
+    let mut buffer = [0u8; 2048];
+    let len = twpb::schema::write(&mut buffer.as_mut(), imports, roots).unwrap();
+    String::from_utf8(buffer[..len].to_vec()).unwrap()
+}
+
+// The tokens of a proto file, so indentation and line breaks don't matter.
+// The [packed=..] options are left out, twpb decodes both encodings.
+fn tokens(proto: &str) -> Vec<String> {
+    proto.replace(" [packed=true]", "").replace(" [packed=false]", "")
+        .replace('{', " { ").replace('}', " } ").replace(';', " ; ")
+        .split_whitespace().map(str::to_owned).collect()
+}
+
+fn assert_matches_file(generated: String, path: &str) {
+    let file = std::fs::read_to_string(format!("tests/files/proto/{}", path)).unwrap();
+    assert_eq!(tokens(&generated), tokens(&file), "{}", generated);
+}
+
+#[test]
+fn test_schema_matches_proto_files() {
+    assert_matches_file(schema(&["api/api.proto"], &[types::Simple::DESCRIPTOR, types::Embedded::DESCRIPTOR]),
+        "simple.proto");
+    assert_matches_file(schema(&[], &[types::SimpleTypes::DESCRIPTOR, types::RepeatedTypes::DESCRIPTOR]),
+        "types.proto");
+    // messages of api.v1 are referred to, not written
+    assert_matches_file(schema(&["api/v1/v1.proto"], &[types::APIMessage::DESCRIPTOR]),
+        "api/api.proto");
+    // EmptyRequest is nested in Request
+    assert_matches_file(schema(&[], &[types::v1::Request::DESCRIPTOR, types::v1::Response::DESCRIPTOR,
+        types::v1::SysInfo::DESCRIPTOR, types::v1::OtherThing::DESCRIPTOR]), "api/v1/v1.proto");
+}
+
+#[test]
+fn test_schema_message_tree() {
+    // flattened fields are inlined, nested oneofs merged, unit variants are Empty and enums are found in the fields
+    assert_eq!(schema(&[], &[Device::DESCRIPTOR]), "\
+syntax = \"proto3\";
+
+import \"google/protobuf/empty.proto\";
+
+message Device {
+  uint32 seq = 1;
+  string name = 2;
+  Settings settings = 3;
+  repeated fixed32 readings = 4;
+  bytes key = 5;
+  oneof command {
+    google.protobuf.Empty reset = 10;
+    Settings configure = 11;
+    sint32 temperature = 12;
+    uint32 brightness = 13;
+  }
+}
+
+message Settings {
+  string vendor = 1;
+  Mode mode = 2;
+  repeated Mode schedule = 3;
+}
+
+enum Mode {
+  MODE_OFF = 0;
+  MODE_ON = 1;
+  MODE_AUTO = 2;
+}
+");
+
+    // messages follow their first use and are written once, also when used by several fields
+    let generated = schema(&[], &[types::v1::Request::DESCRIPTOR, types::v1::Response::DESCRIPTOR]);
+    let messages: Vec<_> = generated.lines().filter(|line| line.trim_start().starts_with("message ")).collect();
+    assert_eq!(messages, ["message Request {", "  message EmptyRequest {}", "message Response {",
+        "message SysInfo {", "message OtherThing {"]);
+    assert!(generated.contains("\npackage api.v1;\n"));
+    assert!(!generated.contains("import"));
+
+    // without its parent, a nested message is still written inside it
+    assert_eq!(schema(&[], &[types::v1::EmptyRequest::DESCRIPTOR]), "\
+syntax = \"proto3\";
+
+package api.v1;
+
+message Request {
+  message EmptyRequest {}
+}
+");
+
+    let mut buffer = [0u8; 64];
+    let result = twpb::schema::write(&mut buffer.as_mut(), &[], &[types::APIMessage::DESCRIPTOR]);
+    assert_eq!(result, Err(WriterError::BufferOverflow));
+}
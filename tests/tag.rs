@@ -1,3 +1,6 @@
+// predates running clippy, keeps its original style
+#![allow(clippy::unnecessary_cast)]
+
 #[test]
 fn test_tags() {
     let mut buffer = [0x0; 100];
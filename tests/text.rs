@@ -0,0 +1,186 @@
+mod types;
+
+use twpb::{DecodeError, TextEncoder, TextDecoder};
+use types::Mode;
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(text)]
+pub struct SensorSettings {
+    #[twpb(string, nr=1)]
+    pub vendor: heapless::String<8>,
+    #[twpb(enum, nr=2)]
+    pub mode: Mode,
+}
+
+#[derive(Debug, PartialEq, ::twpb_derive::Enum)]
+#[twpb(text)]
+pub enum Content {
+    #[twpb(message, nr=2)]
+    Ss(SensorSettings),
+    #[twpb(uint32, nr=3)]
+    Reset(u32),
+    #[twpb(message, nr=4)]
+    Ping,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Copy, ::twpb_derive::Message)]
+#[twpb(transparent, text)]
+pub struct Checksum(#[twpb(fixed32)] pub u32);
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(text)]
+pub struct Request {
+    #[twpb(string, nr=1)]
+    pub serial: heapless::String<8>,
+    #[twpb(oneof)]
+    pub content: Option<Content>,
+    #[twpb(sint32, repeated, nr=5)]
+    pub values: heapless::Vec<i32, 4>,
+    #[twpb(fixed32, repeated, nr=6)]
+    pub pair: [u32; 2],
+    #[twpb(bytes, nr=7)]
+    pub raw_data: heapless::Vec<u8, 8>,
+    #[twpb(fixed32, nr=8)]
+    pub checksum: Checksum,
+    #[twpb(double, nr=9)]
+    pub gain: f64,
+}
+
+// f32 fractions <-> uint32 percent, in the text format too
+mod percent {
+    use twpb::{DecodeError, Writer, WriterError};
+    use twpb::codec::UInt32;
+    use twpb::text::{TextCodec, TextParser};
+
+    pub fn encode(buffer: &mut impl Writer, value: &f32) -> Result<usize, WriterError> {
+        ::twpb::encoder::uint32(buffer, &((value * 100.0) as u32))
+    }
+
+    pub fn decode<I>(bytes: I, field_name: &'static str) -> Result<f32, DecodeError>
+    where I: Iterator<Item = u8> {
+        ::twpb::decoder::uint32(bytes, field_name).map(|value| value as f32 / 100.0)
+    }
+
+    pub fn encoded_len(value: &f32) -> usize {
+        ::twpb::encoder::leb128_len(&((value * 100.0) as u64))
+    }
+
+    pub fn encode_text(buffer: &mut impl Writer, value: &f32) -> Result<usize, WriterError> {
+        <UInt32 as TextCodec<u32>>::encode_text(buffer, &((value * 100.0) as u32))
+    }
+
+    pub fn decode_text(parser: &mut TextParser, field_name: &'static str) -> Result<f32, DecodeError> {
+        <UInt32 as TextCodec<u32>>::decode_text(parser, field_name).map(|value| value as f32 / 100.0)
+    }
+
+    pub fn is_default(value: &f32) -> bool {
+        *value == 0.0
+    }
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(text)]
+pub struct Battery {
+    #[twpb(string, nr=1)]
+    pub name: heapless::String<8>,
+    #[twpb(uint32, with = "percent", nr=2)]
+    pub level: f32,
+}
+
+fn encode(message: &impl TextEncoder) -> heapless::String<256> {
+    let mut buffer = [0u8; 256];
+    let len = message.twpb_encode_text(&mut buffer.as_mut()).unwrap();
+    heapless::String::from(core::str::from_utf8(&buffer[..len]).unwrap())
+}
+
+#[test]
+fn test_text() {
+    let message = Request {
+        serial: heapless::String::from("abc"),
+        content: Some(Content::Ss(SensorSettings { vendor: heapless::String::from("x"), mode: Mode::Off })),
+        ..Default::default()
+    };
+    // fixed size arrays are always written
+    let text = encode(&message);
+    assert_eq!(text, "serial: \"abc\" ss { vendor: \"x\" } pair: [0, 0]");
+    assert_eq!(Request::twpb_decode_text(text.as_bytes()), Ok(message));
+}
+
+#[test]
+fn test_text_all_fields() {
+    let message = Request {
+        serial: heapless::String::from("a\"\n"),
+        content: Some(Content::Reset(7)),
+        values: heapless::Vec::from_slice(&[-1, 2]).unwrap(),
+        pair: [3, 4],
+        raw_data: heapless::Vec::from_slice(&[0, 0xff, b'a']).unwrap(),
+        checksum: Checksum(9),
+        gain: -0.5,
+    };
+    let text = encode(&message);
+    assert_eq!(text, "serial: \"a\\\"\\n\" reset: 7 values: [-1, 2] pair: [3, 4] raw_data: \"\\000\\377a\" checksum: 9 gain: -0.5");
+    assert_eq!(Request::twpb_decode_text(text.as_bytes()), Ok(message));
+
+    let ping = Request { content: Some(Content::Ping), ..Default::default() };
+    let text = encode(&ping);
+    assert_eq!(text, "ping {} pair: [0, 0]");
+    assert_eq!(Request::twpb_decode_text(text.as_bytes()), Ok(ping));
+}
+
+#[test]
+fn test_text_with() {
+    let battery = Battery { name: heapless::String::from("main"), level: 0.5 };
+    let text = encode(&battery);
+    assert_eq!(text, "name: \"main\" level: 50");
+    assert_eq!(Battery::twpb_decode_text(text.as_bytes()), Ok(battery));
+
+    // like the scalars, a value the module calls default isn't written
+    let empty = Battery { name: heapless::String::from("main"), level: 0.0 };
+    assert_eq!(encode(&empty), "name: \"main\"");
+}
+
+#[test]
+fn test_text_parser() {
+    // the way it may be typed on a console
+    let text = "
+        # a comment
+        serial: 'abc'
+        ss: < vendor: \"\\x78\" mode: MODE_ON >;
+        values: 1, values: [-0x2, 010]
+        pair: [1, 2]
+        gain: 1e3
+    ";
+    let expected = Request {
+        serial: heapless::String::from("abc"),
+        content: Some(Content::Ss(SensorSettings { vendor: heapless::String::from("x"), mode: Mode::On })),
+        values: heapless::Vec::from_slice(&[1, -2, 8]).unwrap(),
+        pair: [1, 2],
+        gain: 1000.0,
+        ..Default::default()
+    };
+    assert_eq!(Request::twpb_decode_text(text.as_bytes()), Ok(expected));
+
+    // enums may be given by number
+    let settings = SensorSettings::twpb_decode_text(b"mode: 1").unwrap();
+    assert_eq!(settings.mode, Mode::On);
+}
+
+#[test]
+fn test_text_errors() {
+    assert_eq!(Request::twpb_decode_text(b"serial: \"abc\" unknown: 1"), Err(DecodeError::InvalidText(14)));
+    assert_eq!(Request::twpb_decode_text(b"serial \"abc\""), Err(DecodeError::InvalidText(7)));
+    assert_eq!(Request::twpb_decode_text(b"ss { vendor: \"x\""), Err(DecodeError::InvalidText(16)));
+    assert_eq!(Request::twpb_decode_text(b"ss { vendor: \"x\" }}"), Err(DecodeError::InvalidText(18)));
+    assert_eq!(Request::twpb_decode_text(b"pair: [1, 2, 3]"), Err(DecodeError::LengthMismatch("pair")));
+    assert_eq!(Request::twpb_decode_text(b"pair: 1"), Err(DecodeError::LengthMismatch("pair")));
+    assert_eq!(Request::twpb_decode_text(b"values: [1, 2, 3, 4, 5]"), Err(DecodeError::FieldOverflow("values")));
+    assert_eq!(Request::twpb_decode_text(b"checksum: -1"), Err(DecodeError::ValueOutOfRange("checksum")));
+    assert_eq!(Request::twpb_decode_text(b"ping { x: 1 }"), Err(DecodeError::InvalidText(7)));
+    assert_eq!(SensorSettings::twpb_decode_text(b"mode: MODE_OF"), Err(DecodeError::InvalidText(6)));
+    // escapes that don't fit a byte or don't name a unicode scalar value
+    assert_eq!(Request::twpb_decode_text(b"serial: \"a\\400\""), Err(DecodeError::InvalidText(10)));
+    assert_eq!(Request::twpb_decode_text(b"serial: \"\\ud800\""), Err(DecodeError::InvalidText(9)));
+    assert_eq!(Request::twpb_decode_text(b"serial: \"\\U00110000\""), Err(DecodeError::InvalidText(9)));
+    let valid = Request::twpb_decode_text(b"serial: \"\\u00e9\\U0001f600\" raw_data: \"\\377\"").unwrap();
+    assert_eq!((valid.serial.as_str(), valid.raw_data.as_slice()), ("\u{e9}\u{1f600}", &[0xff][..]));
+}
@@ -3,6 +3,15 @@
 // Hence, lots of dead code warnings, disable at crate level.
 #![allow(dead_code)]
 
+#[derive(Debug, PartialEq, Default, Clone, Copy, ::twpb_derive::Enum)]
+#[twpb(enum)]
+pub enum Mode {
+    #[default]
+    Off = 0,
+    On = 1,
+    Auto = 2,
+}
+
 #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
 pub struct SimpleTypes {
     #[twpb(int32,nr=1)]
@@ -29,7 +38,7 @@ pub struct SimpleTypes {
     pub double: f64,
     #[twpb(float,nr=12)]
     pub float: f32,
-    #[twpb(bool,nr=13)]
+    #[twpb(bool,nr=13,name="bool")]
     pub boolean: bool,
     #[twpb(string,nr=14)]
     pub string: heapless::String<10>,
@@ -63,7 +72,7 @@ pub struct RepeatedTypes {
     pub double: heapless::Vec<f64, 10>,
     #[twpb(float,repeated,nr=12)]
     pub float: heapless::Vec<f32, 10>,
-    #[twpb(bool,repeated,nr=13)]
+    #[twpb(bool,repeated,nr=13,name="bool")]
     pub boolean: heapless::Vec<bool, 10>,
     #[twpb(string,repeated,nr=14)]
     pub string: heapless::Vec<heapless::String<10>, 10>,
@@ -74,8 +83,9 @@ pub struct RepeatedTypes {
 }
 
 #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(name="SuperSimple")]
 pub struct Simple{
-    #[twpb(string,nr=1)]
+    #[twpb(string,nr=1,name="serial_number")]
     pub serial: heapless::String<10>,
     #[twpb(string,nr=2)]
     pub firmware_version: heapless::String<10>,
@@ -86,8 +96,9 @@ pub struct Simple{
 }
 
 #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(name="Message")]
 pub struct Embedded {
-    #[twpb(oneof,nr="1-3")]
+    #[twpb(oneof)]
     pub content: ::core::option::Option<embedded::Content>,
     // To test that our oneof (which contains a Message object)
     // consumes only the bytes its supposed to, we have an extra
@@ -97,8 +108,6 @@ pub struct Embedded {
 }
 
 pub mod embedded {
-    use ::twpb::{MessageDecoder};
-
     #[derive(PartialEq, Debug, ::twpb_derive::Enum)]
     pub enum Content {
         #[twpb(message,nr=1)]
@@ -109,14 +118,13 @@ pub mod embedded {
 }
 
 #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(name="Message", package="api")]
 pub struct APIMessage {
     #[twpb(oneof,nr="1-2")]
     pub content: ::core::option::Option<apimessage::Content>,
 }
 
 pub mod apimessage {
-    use ::twpb::{MessageDecoder};
-
     #[derive(PartialEq, Debug, ::twpb_derive::Enum)]
     pub enum Content {
         #[twpb(message,nr=1)]
@@ -128,23 +136,27 @@ pub mod apimessage {
 
 pub mod v1 {
     #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+    #[twpb(name="Request.EmptyRequest", package="api.v1")]
     pub struct EmptyRequest {}
 
     #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+    #[twpb(package="api.v1")]
     pub struct Request {
-        #[twpb(oneof,nr="1-2")]
+        #[twpb(oneof)]
         pub request: ::core::option::Option<request::Request>,
     }
 
     #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+    #[twpb(package="api.v1")]
     pub struct Response {
-        #[twpb(oneof,nr="1-2")]
+        #[twpb(oneof)]
         pub response: ::core::option::Option<response::Response>,
     }
 
     #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+    #[twpb(package="api.v1")]
     pub struct SysInfo{
-        #[twpb(string,nr=1)]
+        #[twpb(string,nr=1,name="serial_number")]
         pub serial: heapless::String<10>,
         #[twpb(string,nr=2)]
         pub firmware_version: heapless::String<10>,
@@ -155,26 +167,23 @@ pub mod v1 {
     }
 
     #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+    #[twpb(package="api.v1")]
     pub struct OtherThing{
         #[twpb(string,nr=1)]
         pub other: heapless::String<10>,
     }
 
     pub mod request {
-        use ::twpb::{MessageDecoder};
-
         #[derive(PartialEq, Debug, ::twpb_derive::Enum)]
         pub enum Request {
-            #[twpb(message,nr=1)]
+            #[twpb(message,nr=1,name="getInfo")]
             GetInfo(super::EmptyRequest),
-            #[twpb(message,nr=2)]
+            #[twpb(message,nr=2,name="getOtherThing")]
             GetOtherThing(super::EmptyRequest),
         }
     }
 
     pub mod response {
-        use ::twpb::{MessageDecoder};
-
         #[derive(PartialEq, Debug, ::twpb_derive::Enum)]
         pub enum Response {
             #[twpb(message,nr=1)]
@@ -183,4 +192,61 @@ pub mod v1 {
             OtherThing(super::OtherThing),
         }
     }
-}
\ No newline at end of file
+}
+
+// Not from a proto file, the schema of these is checked in tests/schema.rs.
+pub mod gateway {
+    use super::Mode;
+
+    #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+    pub struct Header {
+        #[twpb(uint32,nr=1)]
+        pub seq: u32,
+    }
+
+    #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+    pub struct Settings {
+        #[twpb(string,nr=1)]
+        pub vendor: heapless::String<10>,
+        #[twpb(enum,nr=2)]
+        pub mode: Mode,
+        #[twpb(enum,repeated,nr=3)]
+        pub schedule: heapless::Vec<Mode, 4>,
+    }
+
+    #[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+    pub struct Device {
+        #[twpb(flatten)]
+        pub header: Header,
+        #[twpb(string,nr=2)]
+        pub name: heapless::String<10>,
+        #[twpb(message,nr=3)]
+        pub settings: Option<Settings>,
+        #[twpb(fixed32,repeated,nr=4)]
+        pub readings: heapless::Vec<u32, 4>,
+        #[twpb(oneof)]
+        pub command: Option<device::Command>,
+        #[twpb(bytes,nr=5)]
+        pub key: heapless::Vec<u8, 16>,
+    }
+
+    pub mod device {
+        #[derive(PartialEq, Debug, ::twpb_derive::Enum)]
+        pub enum Command {
+            #[twpb(message,nr=10)]
+            Reset,
+            #[twpb(message,nr=11)]
+            Configure(super::Settings),
+            #[twpb(oneof)]
+            Set(Setpoint),
+        }
+
+        #[derive(PartialEq, Debug, ::twpb_derive::Enum)]
+        pub enum Setpoint {
+            #[twpb(sint32,nr=12)]
+            Temperature(i32),
+            #[twpb(uint32,nr=13)]
+            Brightness(u32),
+        }
+    }
+}
@@ -0,0 +1,6 @@
+// The derive macros must reject invalid input with an error pointing at the offending tokens.
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,10 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+#[twpb(after_decode = 5)]
+struct Msg {
+    #[twpb(uint32, nr=1)]
+    a: u32,
+}
+
+fn main() {}
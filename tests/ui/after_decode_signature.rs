@@ -0,0 +1,14 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+#[twpb(after_decode = "check")]
+struct Msg {
+    #[twpb(uint32, nr=1)]
+    a: u32,
+}
+
+fn check(_message: &Msg) -> bool {
+    true
+}
+
+fn main() {}
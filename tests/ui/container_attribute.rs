@@ -0,0 +1,7 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+#[twpb(opaque)]
+struct Serial(#[twpb(fixed64, nr=1)] u64);
+
+fn main() {}
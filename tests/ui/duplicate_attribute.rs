@@ -0,0 +1,10 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(uint32, nr=1)]
+    #[twpb(uint32, nr=2)]
+    a: u32,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+#[twpb(enum)]
+struct Mode {
+    #[twpb(uint32, nr=1)]
+    level: u32,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use twpb_derive::Enum;
+
+#[derive(Enum)]
+struct Choice {
+    a: u32,
+}
+
+fn main() {}
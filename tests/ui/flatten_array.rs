@@ -0,0 +1,15 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+struct Header {
+    #[twpb(uint32, repeated, nr=1)]
+    ids: [u32; 2],
+}
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(flatten)]
+    header: Header,
+}
+
+fn main() {}
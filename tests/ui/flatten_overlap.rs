@@ -0,0 +1,19 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+struct Header {
+    #[twpb(uint32, nr=1)]
+    seq: u32,
+    #[twpb(fixed64, nr=2)]
+    timestamp: u64,
+}
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(flatten)]
+    header: Header,
+    #[twpb(string, nr=2)]
+    name: heapless::String<8>,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(uint32, nr=1, json_name = 5)]
+    a: u32,
+}
+
+fn main() {}
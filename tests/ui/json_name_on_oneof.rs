@@ -0,0 +1,16 @@
+use twpb_derive::{Enum, Message};
+
+#[derive(Enum)]
+enum Choice {
+    #[twpb(uint32, nr=1)]
+    A(u32),
+}
+
+#[derive(Message, Default)]
+#[twpb(json)]
+struct Msg {
+    #[twpb(oneof, json_name = "choice")]
+    choice: Option<Choice>,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use twpb_derive::Message;
+
+#[derive(Message)]
+enum Msg {
+    A,
+}
+
+fn main() {}
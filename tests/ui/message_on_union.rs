@@ -0,0 +1,8 @@
+use twpb_derive::Message;
+
+#[derive(Message)]
+union Msg {
+    a: u32,
+}
+
+fn main() {}
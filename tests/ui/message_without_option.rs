@@ -0,0 +1,12 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+struct Inner {}
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(message, nr=1)]
+    a: Inner,
+}
+
+fn main() {}
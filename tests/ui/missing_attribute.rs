@@ -0,0 +1,8 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+struct Msg {
+    a: u32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+struct Info {
+    #[twpb(string, nr=1, name = "serial.number")]
+    serial: heapless::String<8>,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+#[twpb(name = "Request EmptyRequest")]
+struct EmptyRequest {}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+struct Header {
+    #[twpb(uint32, nr=1)]
+    seq: u32,
+}
+
+#[derive(Message, Default)]
+struct Request {
+    #[twpb(flatten, name = "header")]
+    header: Header,
+}
+
+fn main() {}
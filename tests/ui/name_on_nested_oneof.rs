@@ -0,0 +1,15 @@
+use twpb_derive::Enum;
+
+#[derive(Enum)]
+enum Inner {
+    #[twpb(uint32, nr=1)]
+    A(u32),
+}
+
+#[derive(Enum)]
+enum Outer {
+    #[twpb(oneof, name = "inner")]
+    Inner(Inner),
+}
+
+fn main() {}
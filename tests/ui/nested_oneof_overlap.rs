@@ -0,0 +1,17 @@
+use twpb_derive::Enum;
+
+#[derive(Enum)]
+enum Inner {
+    #[twpb(uint32, nr=1)]
+    A(u32),
+}
+
+#[derive(Enum)]
+enum Choice {
+    #[twpb(uint32, nr=1)]
+    A(u32),
+    #[twpb(oneof)]
+    B(Inner),
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(uint32, nr="1;two")]
+    a: u32,
+}
+
+fn main() {}
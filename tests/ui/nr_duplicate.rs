@@ -0,0 +1,11 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(uint32, nr=1)]
+    a: u32,
+    #[twpb(string, nr=1)]
+    b: heapless::String<8>,
+}
+
+fn main() {}
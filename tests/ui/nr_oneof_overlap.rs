@@ -0,0 +1,19 @@
+use twpb_derive::{Enum, Message};
+
+#[derive(Enum)]
+enum Choice {
+    #[twpb(uint32, nr=2)]
+    A(u32),
+    #[twpb(uint32, nr=3)]
+    B(u32),
+}
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(uint32, nr=3)]
+    a: u32,
+    #[twpb(oneof, nr="2-3")]
+    choice: Option<Choice>,
+}
+
+fn main() {}
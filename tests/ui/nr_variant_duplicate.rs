@@ -0,0 +1,11 @@
+use twpb_derive::Enum;
+
+#[derive(Enum)]
+enum Choice {
+    #[twpb(uint32, nr=1)]
+    A(u32),
+    #[twpb(sint32, nr=1)]
+    B(i32),
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use twpb_derive::Enum;
+
+#[derive(Enum)]
+#[twpb(after_decode = "check")]
+enum Choice {
+    #[twpb(uint32, nr=1)]
+    A(u32),
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use twpb_derive::{Enum, Message};
+
+#[derive(Enum)]
+enum Choice {
+    #[twpb(uint32, nr=1)]
+    A(u32),
+    #[twpb(uint32, nr=2)]
+    B(u32),
+}
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(oneof)]
+    choice: Option<Choice>,
+    #[twpb(string, nr=2)]
+    name: heapless::String<8>,
+}
+
+fn main() {}
@@ -0,0 +1,23 @@
+use twpb_derive::{Enum, Message};
+
+#[derive(Enum)]
+enum First {
+    #[twpb(uint32, nr=1)]
+    A(u32),
+}
+
+#[derive(Enum)]
+enum Second {
+    #[twpb(uint32, nr=1)]
+    B(u32),
+}
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(oneof)]
+    first: Option<First>,
+    #[twpb(oneof)]
+    second: Option<Second>,
+}
+
+fn main() {}
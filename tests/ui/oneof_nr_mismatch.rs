@@ -0,0 +1,17 @@
+use twpb_derive::{Enum, Message};
+
+#[derive(Enum)]
+enum Choice {
+    #[twpb(uint32, nr=1)]
+    A(u32),
+    #[twpb(uint32, nr=3)]
+    B(u32),
+}
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(oneof, nr="1-3")]
+    choice: Option<Choice>,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use twpb_derive::{Enum, Message};
+
+#[derive(Enum)]
+enum Choice {
+    #[twpb(uint32, nr=1)]
+    A(u32),
+}
+
+#[derive(Message)]
+struct Msg {
+    #[twpb(oneof, nr=1)]
+    a: Choice,
+}
+
+fn main() {}
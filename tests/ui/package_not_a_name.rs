@@ -0,0 +1,10 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+#[twpb(package = "api..v1")]
+struct Request {
+    #[twpb(uint32, nr=1)]
+    id: u32,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use twpb_derive::Enum;
+
+#[derive(Enum)]
+#[twpb(enum, json)]
+enum Mode {
+    Off,
+    On,
+}
+
+fn main() {}
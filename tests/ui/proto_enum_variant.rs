@@ -0,0 +1,10 @@
+use twpb_derive::Enum;
+
+#[derive(Enum)]
+#[twpb(enum)]
+enum Mode {
+    Off,
+    Level(u8),
+}
+
+fn main() {}
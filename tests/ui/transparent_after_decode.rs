@@ -0,0 +1,7 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+#[twpb(transparent, after_decode = "check")]
+struct Serial(#[twpb(fixed64)] u64);
+
+fn main() {}
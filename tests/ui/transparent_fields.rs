@@ -0,0 +1,7 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+#[twpb(transparent)]
+struct Pair(#[twpb(uint32)] u32, #[twpb(uint32)] u32);
+
+fn main() {}
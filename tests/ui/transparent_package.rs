@@ -0,0 +1,7 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+#[twpb(transparent, package = "api.v1")]
+struct Serial(#[twpb(fixed64)] u64);
+
+fn main() {}
@@ -0,0 +1,7 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+#[twpb(transparent)]
+struct Serials(#[twpb(fixed64, repeated)] heapless::Vec<u64, 4>);
+
+fn main() {}
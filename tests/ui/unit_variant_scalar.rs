@@ -0,0 +1,9 @@
+use twpb_derive::Enum;
+
+#[derive(Enum)]
+enum Choice {
+    #[twpb(uint32, nr=1)]
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use twpb_derive::Enum;
+
+#[derive(Enum)]
+enum Choice {
+    #[twpb(flatten)]
+    A(u32),
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use twpb_derive::Message;
+
+#[derive(Message, Default)]
+struct Msg {
+    #[twpb(bytes, with=5, nr=1)]
+    a: u32,
+}
+
+fn main() {}
@@ -0,0 +1,152 @@
+use core::net::Ipv4Addr;
+use core::time::Duration;
+use twpb::{MessageEncoder, MessageDecoder};
+
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+pub struct MacAddress([u8; 6]);
+
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct Millivolts(u32);
+
+// MacAddress <-> bytes
+mod mac_address {
+    use super::MacAddress;
+    use twpb::{DecodeError, Writer, WriterError};
+
+    pub fn encode(buffer: &mut impl Writer, value: &MacAddress) -> Result<usize, WriterError> {
+        let mut bytes_written = ::twpb::encoder::leb128_u32(buffer, &6)?;
+        bytes_written += buffer.write_all(&value.0)?;
+        Ok(bytes_written)
+    }
+
+    pub fn decode<I>(bytes: I, field_name: &'static str) -> Result<MacAddress, DecodeError>
+    where I: Iterator<Item = u8> {
+        let buf: heapless::Vec<u8, 6> = ::twpb::decoder::bytes(bytes, field_name)?;
+        let octets = buf.as_slice().try_into().map_err(|_| DecodeError::FieldOverflow(field_name))?;
+        Ok(MacAddress(octets))
+    }
+
+    pub fn encoded_len(_value: &MacAddress) -> usize {
+        7
+    }
+}
+
+// Ipv4Addr <-> fixed32
+mod ipv4 {
+    use core::net::Ipv4Addr;
+    use twpb::{DecodeError, Writer, WriterError};
+
+    pub fn encode(buffer: &mut impl Writer, value: &Ipv4Addr) -> Result<usize, WriterError> {
+        ::twpb::encoder::fixed32(buffer, &u32::from(*value))
+    }
+
+    pub fn decode<I>(bytes: I, field_name: &'static str) -> Result<Ipv4Addr, DecodeError>
+    where I: Iterator<Item = u8> {
+        ::twpb::decoder::fixed32(bytes, field_name).map(Ipv4Addr::from)
+    }
+
+    pub fn encoded_len(_value: &Ipv4Addr) -> usize {
+        4
+    }
+}
+
+// Duration <-> uint64 milliseconds
+mod duration_ms {
+    use core::time::Duration;
+    use twpb::{DecodeError, Writer, WriterError};
+
+    pub fn encode(buffer: &mut impl Writer, value: &Duration) -> Result<usize, WriterError> {
+        ::twpb::encoder::uint64(buffer, &(value.as_millis() as u64))
+    }
+
+    pub fn decode<I>(bytes: I, field_name: &'static str) -> Result<Duration, DecodeError>
+    where I: Iterator<Item = u8> {
+        ::twpb::decoder::uint64(bytes, field_name).map(Duration::from_millis)
+    }
+
+    pub fn encoded_len(value: &Duration) -> usize {
+        ::twpb::encoder::leb128_len(&(value.as_millis() as u64))
+    }
+}
+
+// Millivolts <-> uint32
+mod millivolts {
+    use super::Millivolts;
+    use twpb::{DecodeError, Writer, WriterError};
+
+    pub fn encode(buffer: &mut impl Writer, value: &Millivolts) -> Result<usize, WriterError> {
+        ::twpb::encoder::uint32(buffer, &value.0)
+    }
+
+    pub fn decode<I>(bytes: I, field_name: &'static str) -> Result<Millivolts, DecodeError>
+    where I: Iterator<Item = u8> {
+        ::twpb::decoder::uint32(bytes, field_name).map(Millivolts)
+    }
+
+    pub fn encoded_len(value: &Millivolts) -> usize {
+        ::twpb::encoder::leb128_len(&(value.0 as u64))
+    }
+}
+
+#[derive(Debug, PartialEq, ::twpb_derive::Message)]
+pub struct Device {
+    #[twpb(bytes,with="mac_address",nr=1)]
+    pub mac: MacAddress,
+    #[twpb(fixed32,with="ipv4",nr=2)]
+    pub address: Ipv4Addr,
+    #[twpb(uint64,with="duration_ms",nr=3)]
+    pub uptime: Duration,
+    #[twpb(uint32,with="millivolts",nr=4)]
+    pub supply: Millivolts,
+    #[twpb(fixed32,with="ipv4",repeated,nr=5)]
+    pub dns: heapless::Vec<Ipv4Addr, 4>,
+    #[twpb(uint32,with="millivolts",repeated,nr=6)]
+    pub samples: heapless::Vec<Millivolts, 4>,
+}
+
+// Ipv4Addr has no Default
+impl Default for Device {
+    fn default() -> Self {
+        Device {
+            mac: MacAddress::default(),
+            address: Ipv4Addr::UNSPECIFIED,
+            uptime: Duration::default(),
+            supply: Millivolts::default(),
+            dns: heapless::Vec::new(),
+            samples: heapless::Vec::new(),
+        }
+    }
+}
+
+#[test]
+fn test_with_adapters() {
+    let device = Device {
+        mac: MacAddress([0x02, 0x00, 0x5E, 0x10, 0x00, 0x01]),
+        address: Ipv4Addr::new(192, 168, 1, 2),
+        uptime: Duration::from_millis(1500),
+        supply: Millivolts(3300),
+        dns: heapless::Vec::from_slice(&[Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(8, 8, 8, 8)]).unwrap(),
+        samples: heapless::Vec::from_slice(&[Millivolts(1), Millivolts(2)]).unwrap(),
+    };
+    let mut buffer = [0x0; 100];
+    let bytes_written = device.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(buffer[0..bytes_written], [
+        0x0A, 0x06, 0x02, 0x00, 0x5E, 0x10, 0x00, 0x01,
+        0x15, 0x02, 0x01, 0xA8, 0xC0,
+        0x18, 0xDC, 0x0B,
+        0x20, 0xE4, 0x19,
+        0x2D, 0x01, 0x01, 0x01, 0x01,
+        0x2D, 0x08, 0x08, 0x08, 0x08,
+        0x30, 0x01,
+        0x30, 0x02,
+    ]);
+    let parsed = Device::twpb_decode(&buffer[0..bytes_written]).unwrap();
+    assert_eq!(parsed, device);
+}
+
+#[test]
+fn test_with_adapters_packed() {
+    // field 6 as a packed repeated uint32 [1, 2, 300]
+    let parsed = Device::twpb_decode(&[0x32, 0x04, 0x01, 0x02, 0xAC, 0x02]).unwrap();
+    assert_eq!(parsed.samples, [Millivolts(1), Millivolts(2), Millivolts(300)]);
+}
@@ -0,0 +1,248 @@
+use twpb::{MessageDecoder, MessageEncoder, JsonEncoder, JsonDecoder, TypeName, WriterError};
+use twpb::decoder::DecodeError;
+use twpb::wkt::{Any, Duration, Empty, FieldMask, OutOfRange, Timestamp};
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(json)]
+pub struct Event {
+    #[twpb(codec = "twpb::wkt::Int32Value", nr=2)]
+    pub limit: Option<i32>,
+    #[twpb(codec = "twpb::wkt::StringValue", nr=3)]
+    pub label: Option<heapless::String<8>>,
+    #[twpb(codec = "twpb::wkt::BoolValue", nr=4)]
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(json)]
+pub struct Schedule {
+    #[twpb(message, nr=1)]
+    pub start: Option<Timestamp>,
+    #[twpb(message, nr=2)]
+    pub interval: Option<Duration>,
+    #[twpb(message, nr=3)]
+    pub update_mask: Option<FieldMask<4, 16>>,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Request {
+    #[twpb(oneof)]
+    pub request: Option<request::Request>,
+    #[twpb(message, nr=3)]
+    pub at: Option<Timestamp>,
+}
+
+pub mod request {
+    #[derive(Debug, PartialEq, ::twpb_derive::Enum)]
+    pub enum Request {
+        #[twpb(message, nr=1)]
+        GetInfo(twpb::wkt::Empty),
+        #[twpb(message, nr=2)]
+        Sleep(twpb::wkt::Duration),
+    }
+}
+
+fn encode(message: &impl MessageEncoder) -> Vec<u8> {
+    let mut buffer = [0u8; 64];
+    let len = message.twpb_encode(&mut buffer.as_mut()).unwrap();
+    assert_eq!(len, message.twpb_encoded_len());
+    buffer[..len].to_vec()
+}
+
+#[test]
+fn test_wkt_timestamp() {
+    let timestamp = Timestamp { seconds: 1_600_000_000, nanos: 500 };
+    assert_eq!(encode(&timestamp), [0x08, 0x80, 0xa0, 0xf8, 0xfa, 0x05, 0x10, 0xf4, 0x03]);
+    assert_eq!(Timestamp::twpb_decode(&encode(&timestamp)).unwrap(), timestamp);
+
+    let since = core::time::Duration::new(1_600_000_000, 500);
+    assert_eq!(Timestamp::from_unix_epoch(since), Ok(timestamp));
+    assert_eq!(timestamp.since_unix_epoch(), Ok(since));
+    assert_eq!(Timestamp { seconds: -1, nanos: 0 }.since_unix_epoch(), Err(OutOfRange));
+    assert_eq!(Timestamp { seconds: 0, nanos: 1_000_000_000 }.since_unix_epoch(), Err(OutOfRange));
+    assert_eq!(Timestamp::from_unix_epoch(core::time::Duration::MAX), Err(OutOfRange));
+}
+
+#[test]
+fn test_wkt_duration() {
+    let duration = Duration::try_from(core::time::Duration::from_millis(1500)).unwrap();
+    assert_eq!(duration, Duration { seconds: 1, nanos: 500_000_000 });
+    assert_eq!(core::time::Duration::try_from(duration), Ok(core::time::Duration::from_millis(1500)));
+
+    // negative and mixed sign durations
+    assert_eq!(core::time::Duration::try_from(Duration { seconds: -1, nanos: -5 }), Err(OutOfRange));
+    assert_eq!(core::time::Duration::try_from(Duration { seconds: 1, nanos: -5 }), Err(OutOfRange));
+    assert_eq!(core::time::Duration::try_from(Duration { seconds: 0, nanos: 1_000_000_000 }), Err(OutOfRange));
+    assert_eq!(Duration::try_from(core::time::Duration::MAX), Err(OutOfRange));
+}
+
+#[test]
+fn test_wkt_empty() {
+    // an Empty variant is a zero length message
+    let request = Request { request: Some(request::Request::GetInfo(Empty {})), at: None };
+    assert_eq!(encode(&request), [0x0a, 0x00]);
+    assert_eq!(Request::twpb_decode(&[0x0a, 0x00]).unwrap(), request);
+
+    let at = Some(Timestamp { seconds: 1, nanos: 0 });
+    let request = Request { request: Some(request::Request::Sleep(Duration { seconds: 2, nanos: 0 })), at };
+    assert_eq!(encode(&request), [0x12, 0x04, 0x08, 0x02, 0x10, 0x00, 0x1a, 0x04, 0x08, 0x01, 0x10, 0x00]);
+}
+
+#[test]
+fn test_wkt_field_mask() {
+    let mut mask = FieldMask::<4, 16>::default();
+    mask.paths.push("settings.mode".into()).unwrap();
+    let encoded = encode(&mask);
+    assert_eq!(encoded[..2], [0x0a, 13]);
+    assert_eq!(FieldMask::<4, 16>::twpb_decode(&encoded).unwrap(), mask);
+}
+
+#[test]
+fn test_wkt_wrappers() {
+    // unset wrappers are left out
+    assert_eq!(encode(&Event::default()), []);
+
+    // set wrappers are written, also at their default value
+    let event = Event { limit: Some(0), label: Some("".into()), enabled: Some(true) };
+    let encoded = encode(&event);
+    assert_eq!(encoded, [0x12, 0x02, 0x08, 0x00, 0x1a, 0x02, 0x0a, 0x00, 0x22, 0x02, 0x08, 0x01]);
+    assert_eq!(Event::twpb_decode(&encoded).unwrap(), event);
+
+    // an empty wrapper message holds the default value, unknown fields in it are skipped
+    let event = Event::twpb_decode(&[0x12, 0x00, 0x1a, 0x02, 0x10, 0x01, 0x22, 0x00]).unwrap();
+    assert_eq!(event.limit, Some(0));
+    assert_eq!(event.label, Some("".into()));
+    assert_eq!(event.enabled, Some(false));
+    assert_eq!(Event::twpb_decode(&[0x1a, 0x04, 0x0a, 0x02, b'o', b'k']).unwrap().label, Some("ok".into()));
+
+    // the wrapped value must have the wire type of its codec
+    assert_eq!(Event::twpb_decode(&[0x12, 0x05, 0x0d, 0x01, 0x00, 0x00, 0x00]), Err(DecodeError::WrongWireType(5, "limit")));
+    assert_eq!(Event::twpb_decode(&[0x1a, 0x02, 0x08, 0x01]), Err(DecodeError::WrongWireType(0, "label")));
+}
+
+#[test]
+fn test_wkt_wrappers_json() {
+    let event = Event { limit: Some(-3), label: Some("a".into()), ..Default::default() };
+    let mut buffer = [0u8; 64];
+    let len = event.twpb_encode_json(&mut buffer.as_mut()).unwrap();
+    assert_eq!(core::str::from_utf8(&buffer[..len]).unwrap(), r#"{"limit":-3,"label":"a"}"#);
+
+    let decoded = Event::twpb_decode_json(br#"{"limit":-3,"label":null,"enabled":false}"#).unwrap();
+    assert_eq!(decoded, Event { limit: Some(-3), enabled: Some(false), ..Default::default() });
+}
+
+fn json(message: &impl JsonEncoder) -> String {
+    let mut buffer = [0u8; 128];
+    let len = message.twpb_encode_json(&mut buffer.as_mut()).unwrap();
+    String::from_utf8(buffer[..len].to_vec()).unwrap()
+}
+
+#[test]
+fn test_wkt_json() {
+    let mut update_mask = FieldMask::default();
+    update_mask.paths.push("limits.low_power".into()).unwrap();
+    update_mask.paths.push("name".into()).unwrap();
+    let schedule = Schedule {
+        start: Some(Timestamp { seconds: 63_072_000 + 36_020, nanos: 21_000_000 }),
+        interval: Some(Duration { seconds: -1, nanos: -500_000_000 }),
+        update_mask: Some(update_mask),
+    };
+    let encoded = json(&schedule);
+    assert_eq!(encoded, r#"{"start":"1972-01-01T10:00:20.021Z","interval":"-1.500s","updateMask":"limits.lowPower,name"}"#);
+    assert_eq!(Schedule::twpb_decode_json(encoded.as_bytes()).unwrap(), schedule);
+
+    // 0, 3, 6 or 9 fractional digits, times before the epoch count forward from a negative second
+    assert_eq!(json(&Timestamp::default()), r#""1970-01-01T00:00:00Z""#);
+    assert_eq!(json(&Timestamp { seconds: -1, nanos: 1_000 }), r#""1969-12-31T23:59:59.000001Z""#);
+    assert_eq!(json(&Timestamp { seconds: 951_782_400, nanos: 7 }), r#""2000-02-29T00:00:00.000000007Z""#);
+    assert_eq!(json(&Duration { seconds: 0, nanos: -1 }), r#""-0.000000001s""#);
+    assert_eq!(json(&Duration::default()), r#""0s""#);
+    assert_eq!(json(&FieldMask::<4, 16>::default()), r#""""#);
+
+    // offsets are converted to UTC
+    let start = Timestamp::twpb_decode_json(br#""1972-01-01T12:30:20.5+02:30""#).unwrap();
+    assert_eq!(start, Timestamp { seconds: 63_072_000 + 36_020, nanos: 500_000_000 });
+    assert_eq!(Timestamp::twpb_decode_json(br#""0001-01-01T00:00:00Z""#).unwrap().seconds, -62_135_596_800);
+    assert_eq!(Duration::twpb_decode_json(br#""-0.5s""#).unwrap(), Duration { seconds: 0, nanos: -500_000_000 });
+    assert_eq!(Duration::twpb_decode_json(br#""1.5s""#).unwrap(), Duration { seconds: 1, nanos: 500_000_000 });
+    assert_eq!(FieldMask::<4, 16>::twpb_decode_json(br#""""#).unwrap(), FieldMask::default());
+
+    assert_eq!(Timestamp::twpb_decode_json(br#""1972-02-30T00:00:00Z""#), Err(DecodeError::InvalidJson(0)));
+    assert_eq!(Timestamp::twpb_decode_json(br#""1972-01-01T00:00:00""#), Err(DecodeError::InvalidJson(0)));
+    assert_eq!(Timestamp::twpb_decode_json(br#""1972-01-01T00:00:00.Z""#), Err(DecodeError::InvalidJson(0)));
+    assert_eq!(Timestamp::twpb_decode_json(br#" {"seconds": 1}"#), Err(DecodeError::InvalidJson(1)));
+    assert_eq!(Duration::twpb_decode_json(br#""1.5""#), Err(DecodeError::InvalidJson(0)));
+    assert_eq!(Duration::twpb_decode_json(br#""315576000001s""#), Err(DecodeError::InvalidJson(0)));
+    assert_eq!(FieldMask::<1, 16>::twpb_decode_json(br#""a,b""#), Err(DecodeError::FieldOverflow("paths")));
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn test_wkt_time_crate() {
+    let time = time::OffsetDateTime::from_unix_timestamp_nanos(-1_500_000_000).unwrap();
+    let timestamp = Timestamp::from(time);
+    assert_eq!(timestamp, Timestamp { seconds: -2, nanos: 500_000_000 });
+    assert_eq!(time::OffsetDateTime::try_from(timestamp), Ok(time));
+    assert_eq!(time::OffsetDateTime::try_from(Timestamp { seconds: i64::MAX, nanos: 0 }), Err(OutOfRange));
+
+    let duration = Duration::from(time::Duration::milliseconds(-1500));
+    assert_eq!(duration, Duration { seconds: -1, nanos: -500_000_000 });
+    assert_eq!(time::Duration::try_from(duration), Ok(time::Duration::milliseconds(-1500)));
+    assert_eq!(time::Duration::try_from(Duration { seconds: -1, nanos: 5 }), Err(OutOfRange));
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(package = "gateway.v1")]
+pub struct Reading {
+    #[twpb(sint32, nr=1)]
+    pub temperature: i32,
+}
+
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+pub struct Envelope {
+    #[twpb(uint32, nr=1)]
+    pub seq: u32,
+    #[twpb(message, nr=2)]
+    pub payload: Option<Any<64, 32>>,
+}
+
+// the type URL has the proto name
+#[derive(Debug, PartialEq, Default, ::twpb_derive::Message)]
+#[twpb(package = "gateway.v1", name = "Reading.Calibration")]
+pub struct Calibration {
+    #[twpb(float, nr=1)]
+    pub offset: f32,
+}
+
+#[test]
+fn test_wkt_type_url() {
+    assert_eq!(Reading::TYPE_URL, "type.googleapis.com/gateway.v1.Reading");
+    assert_eq!(Calibration::TYPE_URL, "type.googleapis.com/gateway.v1.Reading.Calibration");
+    assert_eq!(Envelope::TYPE_URL, "type.googleapis.com/Envelope");
+    assert_eq!(Timestamp::TYPE_URL, "type.googleapis.com/google.protobuf.Timestamp");
+    assert_eq!(<Any<1, 1>>::TYPE_URL, "type.googleapis.com/google.protobuf.Any");
+}
+
+#[test]
+fn test_wkt_any() {
+    let payload = Any::pack(&Reading { temperature: -2 }).unwrap();
+    assert_eq!(payload.type_url, Reading::TYPE_URL);
+    assert_eq!(payload.value, [0x08, 0x03]);
+    let envelope = Envelope { seq: 1, payload: Some(payload) };
+    let envelope = Envelope::twpb_decode(&encode(&envelope)).unwrap();
+
+    // dispatch on the type
+    let payload = envelope.payload.unwrap();
+    assert!(payload.is::<Reading>());
+    assert!(!payload.is::<Timestamp>());
+    assert_eq!(payload.unpack::<Timestamp>(), Ok(None));
+    assert_eq!(payload.unpack::<Reading>(), Ok(Some(Reading { temperature: -2 })));
+
+    // only the name after the last slash counts
+    let payload = Any::<64, 32> { type_url: "example.com/x/gateway.v1.Reading".into(), value: heapless::Vec::new() };
+    assert_eq!(payload.unpack::<Reading>(), Ok(Some(Reading::default())));
+
+    // too small for the type URL or the message
+    assert_eq!(Any::<16, 32>::pack(&Reading::default()), Err(WriterError::BufferOverflow));
+    assert_eq!(Any::<64, 8>::pack(&Timestamp { seconds: -1, nanos: 0 }), Err(WriterError::BufferOverflow));
+}
@@ -0,0 +1,49 @@
+use syn::{self, parse_quote};
+
+// Maps the proto scalar type names accepted in #[twpb(..)] to their
+// `::twpb::codec::ScalarCodec` marker type.
+pub fn for_proto_type(proto_type: &str) -> Option<syn::Path> {
+    let path = match proto_type {
+        "int32" => parse_quote!(::twpb::codec::Int32),
+        "int64" => parse_quote!(::twpb::codec::Int64),
+        "uint32" => parse_quote!(::twpb::codec::UInt32),
+        "uint64" => parse_quote!(::twpb::codec::UInt64),
+        "sint32" => parse_quote!(::twpb::codec::SInt32),
+        "sint64" => parse_quote!(::twpb::codec::SInt64),
+        "fixed32" => parse_quote!(::twpb::codec::Fixed32),
+        "fixed64" => parse_quote!(::twpb::codec::Fixed64),
+        "sfixed32" => parse_quote!(::twpb::codec::SFixed32),
+        "sfixed64" => parse_quote!(::twpb::codec::SFixed64),
+        "double" => parse_quote!(::twpb::codec::Double),
+        "float" => parse_quote!(::twpb::codec::Float),
+        "bool" => parse_quote!(::twpb::codec::Bool),
+        "string" => parse_quote!(::twpb::codec::String),
+        "bytes" => parse_quote!(::twpb::codec::Bytes),
+        "enum" => parse_quote!(::twpb::codec::Enum),
+        _ => return None,
+    };
+    Some(path)
+}
+
+// The wire type of a proto scalar type, for fields that don't go through a ScalarCodec.
+pub fn wire_type_for(proto_type: &str) -> Option<syn::Path> {
+    let path = match proto_type {
+        "int32" | "int64" |
+        "uint32" | "uint64" |
+        "sint32" | "sint64" |
+        "bool" | "enum"
+        => parse_quote!(::twpb::wire_types::VARINT),
+
+        "fixed64" | "sfixed64" | "double"
+        => parse_quote!(::twpb::wire_types::B64),
+
+        "string" | "bytes" | "message"
+        => parse_quote!(::twpb::wire_types::LENGTHDELIMITED),
+
+        "fixed32" | "sfixed32" | "float"
+        => parse_quote!(::twpb::wire_types::B32),
+
+        _ => return None,
+    };
+    Some(path)
+}
@@ -0,0 +1,396 @@
+// Code generation for the proto3 JSON mapping of #[twpb(json)] messages and oneofs,
+// see `twpb::json` for the runtime side.
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::types::*;
+
+// `key == "jsonName" || key == "proto_name"`, parsers accept both.
+fn key_matches(json_name: &str, proto_name: &str) -> TokenStream {
+    if json_name == proto_name {
+        quote!(*key == #json_name)
+    } else {
+        quote!(*key == #json_name || *key == #proto_name)
+    }
+}
+
+// Write a repeated field as JSON array, `encode` writes the value `val`.
+fn encode_array(field_name: &syn::Member, json_name: &str, encode: TokenStream) -> TokenStream {
+    quote!{
+        if !self.#field_name.is_empty() {
+            bytes_written += ::twpb::json::member(buffer, first, #json_name)?;
+            bytes_written += ::twpb::Writer::write_all(buffer, b"[")?;
+            for (i, val) in self.#field_name.iter().enumerate() {
+                if i > 0 {
+                    bytes_written += ::twpb::Writer::write_all(buffer, b",")?;
+                }
+                bytes_written += #encode;
+            }
+            bytes_written += ::twpb::Writer::write_all(buffer, b"]")?;
+        }
+    }
+}
+
+// Read a JSON array into a repeated field, `decode` reads one value from `parser`.
+fn decode_array(field: &ParsedField, decode: TokenStream) -> TokenStream {
+    let field_name = &field.field_name;
+    let name = &field.proto_name;
+    if let syn::Type::Array(_) = field.field_type {
+        // fixed size arrays must receive exactly as many values as they can hold, null resets them
+        quote!{
+            if parser.null() {
+                for slot in self.#field_name.iter_mut() {
+                    *slot = ::core::default::Default::default();
+                }
+            } else {
+                let mut len = 0;
+                parser.array(|parser| {
+                    let slot = self.#field_name.get_mut(len)
+                        .ok_or(::twpb::decoder::DecodeError::LengthMismatch(#name))?;
+                    *slot = #decode;
+                    len += 1;
+                    Ok(())
+                })?;
+                if len != self.#field_name.len() {
+                    return Err(::twpb::decoder::DecodeError::LengthMismatch(#name));
+                }
+            }
+        }
+    } else {
+        quote!{
+            self.#field_name.clear();
+            if !parser.null() {
+                parser.array(|parser| {
+                    let value = #decode;
+                    self.#field_name.push(value).map_err(|_| ::twpb::decoder::DecodeError::FieldOverflow(#name))
+                })?;
+            }
+        }
+    }
+}
+
+// JsonEncoder and JsonDecoder for a message. `after_decode` is the hook call of the binary decoder.
+pub fn derive_message(struct_name: &syn::Ident, generics: &syn::Generics, fields: &[ParsedField],
+    after_decode: &Option<TokenStream>) -> syn::parse::Result<TokenStream> {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut encodecode = quote!();
+    let mut decodecode = quote!();
+    for field in fields {
+        let field_name = &field.field_name;
+        let field_type = &field.field_type;
+        let name = &field.proto_name;
+        let json_name = &field.json_name;
+        let matches = key_matches(json_name, name);
+
+        if field.proto_type == "flatten" {
+            encodecode.extend(quote!{
+                bytes_written += ::twpb::JsonEncoder::twpb_encode_json_members(&self.#field_name, buffer, first)?;
+            });
+            decodecode.extend(quote!{
+                if ::twpb::JsonDecoder::twpb_decode_json_member(&mut self.#field_name, key, parser, recursion_limit)? {
+                    return Ok(true);
+                }
+            });
+
+        } else if field.proto_type == "oneof" {
+            let optionarg = option_inner(field_type)
+                .ok_or_else(|| syn::Error::new_spanned(field_type, "oneof fields must be wrapped in an Option"))?;
+            encodecode.extend(quote!{
+                if let Some(value) = self.#field_name.as_ref() {
+                    bytes_written += <#optionarg>::twpb_encode_json_members(value, buffer, first)?;
+                }
+            });
+            decodecode.extend(quote!{
+                if <#optionarg>::twpb_decode_json_member(key, parser, recursion_limit, &mut self.#field_name)? {
+                    return Ok(true);
+                }
+            });
+
+        } else if field.proto_type == "message" {
+            let optionarg = option_inner(field_type)
+                .ok_or_else(|| syn::Error::new_spanned(field_type, "message fields must be wrapped in an Option"))?;
+            let (decode_value, as_message) = if is_box(optionarg) {
+                (quote!{{
+                    let value = <<#optionarg as ::core::ops::Deref>::Target as ::twpb::JsonDecoder>::twpb_decode_json_value(parser, recursion_limit - 1)?;
+                    <#optionarg as ::twpb::MessageBox>::new_box(value)
+                        .ok_or(::twpb::decoder::DecodeError::AllocationFailed(#name))?
+                }}, quote!(::core::ops::Deref::deref(value)))
+            } else {
+                (quote!{
+                    <#optionarg as ::twpb::JsonDecoder>::twpb_decode_json_value(parser, recursion_limit - 1)?
+                }, quote!(value))
+            };
+            encodecode.extend(quote!{
+                if let Some(value) = self.#field_name.as_ref() {
+                    bytes_written += ::twpb::json::member(buffer, first, #json_name)?;
+                    bytes_written += ::twpb::JsonEncoder::twpb_encode_json(#as_message, buffer)?;
+                }
+            });
+            decodecode.extend(quote!{
+                if #matches {
+                    if parser.null() {
+                        self.#field_name = None;
+                    } else {
+                        if recursion_limit == 0 {
+                            return Err(::twpb::decoder::DecodeError::RecursionLimit);
+                        }
+                        self.#field_name = Some(#decode_value);
+                    }
+                    return Ok(true);
+                }
+            });
+
+        } else if let Some(with) = &field.with {
+            // the module must also provide encode_json, decode_json and is_default
+            if field.repeated {
+                encodecode.extend(encode_array(field_name, json_name, quote!(#with::encode_json(buffer, val)?)));
+                let decode = decode_array(field, quote!(#with::decode_json(parser, #name)?));
+                decodecode.extend(quote!{
+                    if #matches {
+                        #decode
+                        return Ok(true);
+                    }
+                });
+            } else {
+                encodecode.extend(quote!{
+                    if !#with::is_default(&self.#field_name) {
+                        bytes_written += ::twpb::json::member(buffer, first, #json_name)?;
+                        bytes_written += #with::encode_json(buffer, &self.#field_name)?;
+                    }
+                });
+                decodecode.extend(quote!{
+                    if #matches {
+                        if parser.null() {
+                            self.#field_name = ::core::default::Default::default();
+                        } else {
+                            self.#field_name = #with::decode_json(parser, #name)?;
+                        }
+                        return Ok(true);
+                    }
+                });
+            }
+
+        } else {
+            let codec = field.codec.as_ref()
+                .ok_or_else(|| syn::Error::new(field.attr_span, format!("no codec for proto type '{}'", field.proto_type)))?;
+            if field.repeated {
+                encodecode.extend(encode_array(field_name, json_name,
+                    quote!(<#codec as ::twpb::json::JsonCodec<_>>::encode_json(buffer, val)?)));
+                let decode = decode_array(field, quote!(<#codec as ::twpb::json::JsonCodec<_>>::decode_json(parser, #name)?));
+                decodecode.extend(quote!{
+                    if #matches {
+                        #decode
+                        return Ok(true);
+                    }
+                });
+            } else {
+                encodecode.extend(quote!{
+                    if !<#codec as ::twpb::json::JsonCodec<#field_type>>::is_default(&self.#field_name) {
+                        bytes_written += ::twpb::json::member(buffer, first, #json_name)?;
+                        bytes_written += <#codec as ::twpb::json::JsonCodec<#field_type>>::encode_json(buffer, &self.#field_name)?;
+                    }
+                });
+                decodecode.extend(quote!{
+                    if #matches {
+                        if parser.null() {
+                            self.#field_name = ::core::default::Default::default();
+                        } else {
+                            self.#field_name = <#codec as ::twpb::json::JsonCodec<#field_type>>::decode_json(parser, #name)?;
+                        }
+                        return Ok(true);
+                    }
+                });
+            }
+        }
+    }
+
+    Ok(quote!{
+        impl #impl_generics ::twpb::JsonEncoder for #struct_name #ty_generics #where_clause {
+            fn twpb_encode_json_members(&self, buffer: &mut impl ::twpb::traits::Writer, first: &mut bool) -> Result<usize, ::twpb::traits::WriterError> {
+                let mut bytes_written = 0;
+                #encodecode
+                Ok(bytes_written)
+            }
+        }
+        impl #impl_generics ::twpb::JsonDecoder for #struct_name #ty_generics #where_clause {
+            fn twpb_decode_json_value(parser: &mut ::twpb::json::JsonParser, recursion_limit: u32) -> Result<Self, ::twpb::decoder::DecodeError> {
+                let mut result = <Self as ::core::default::Default>::default();
+                parser.object(|parser, key| {
+                    // members that are not a field are ignored, like unknown fields
+                    if !::twpb::JsonDecoder::twpb_decode_json_member(&mut result, &key, parser, recursion_limit)? {
+                        parser.skip(recursion_limit)?;
+                    }
+                    Ok(())
+                })?;
+
+                #after_decode
+
+                Ok(result)
+            }
+
+            fn twpb_decode_json_member(&mut self, key: &::twpb::json::JsonStr, parser: &mut ::twpb::json::JsonParser, recursion_limit: u32) -> Result<bool, ::twpb::decoder::DecodeError> {
+                #decodecode
+                Ok(false)
+            }
+        }
+    })
+}
+
+// JSON for a oneof enum. Its variants are members of the object of the message containing it,
+// so instead of JsonEncoder and JsonDecoder it gets inherent functions working on members.
+pub fn derive_enum(struct_name: &syn::Ident, generics: &syn::Generics, variants: &[ParsedVariant]) -> syn::parse::Result<TokenStream> {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut encodecode = quote!();
+    let mut decodecode = quote!();
+    // only one member of a oneof may be set, like proto3 JSON parsers require
+    let check_unset = quote!{
+        if value.is_some() {
+            return Err(::twpb::decoder::DecodeError::InvalidJson(position));
+        }
+    };
+    for variant in variants {
+        let field_name = &variant.field_name;
+        let field_type = &variant.field_type;
+        let json_name = &variant.json_name;
+        let matches = key_matches(json_name, &variant.proto_name);
+        let error_name = quote!(concat!(stringify!(#struct_name), "::", stringify!(#field_name)));
+
+        // the selected variant is always written, even at its default value
+        if variant.proto_type == "oneof" {
+            encodecode.extend(quote!{
+                #struct_name::#field_name(c) => {
+                    bytes_written += <#field_type>::twpb_encode_json_members(c, buffer, first)?;
+                },
+            });
+            decodecode.extend(quote!{
+                let mut nested = None;
+                if <#field_type>::twpb_decode_json_member(key, parser, recursion_limit, &mut nested)? {
+                    #check_unset
+                    *value = nested.map(#struct_name::#field_name);
+                    return Ok(true);
+                }
+            });
+        } else if variant.proto_type == "message" && variant.unit {
+            // an empty message without a Rust type, whatever it holds is ignored
+            encodecode.extend(quote!{
+                #struct_name::#field_name => {
+                    bytes_written += ::twpb::json::member(buffer, first, #json_name)?;
+                    bytes_written += ::twpb::Writer::write_all(buffer, b"{}")?;
+                },
+            });
+            decodecode.extend(quote!{
+                if #matches {
+                    #check_unset
+                    if parser.null() {
+                        *value = None;
+                    } else {
+                        if recursion_limit == 0 {
+                            return Err(::twpb::decoder::DecodeError::RecursionLimit);
+                        }
+                        parser.object(|parser, _| parser.skip(recursion_limit - 1))?;
+                        *value = Some(#struct_name::#field_name);
+                    }
+                    return Ok(true);
+                }
+            });
+        } else {
+            let (encode, decode) = if variant.proto_type == "message" {
+                (quote!(::twpb::JsonEncoder::twpb_encode_json(c, buffer)?), quote!{{
+                    if recursion_limit == 0 {
+                        return Err(::twpb::decoder::DecodeError::RecursionLimit);
+                    }
+                    <#field_type as ::twpb::JsonDecoder>::twpb_decode_json_value(parser, recursion_limit - 1)?
+                }})
+            } else if let Some(with) = &variant.with {
+                (quote!(#with::encode_json(buffer, c)?), quote!(#with::decode_json(parser, #error_name)?))
+            } else {
+                let codec = variant.codec.as_ref()
+                    .ok_or_else(|| syn::Error::new(variant.attr_span, format!("no codec for proto type '{}'", variant.proto_type)))?;
+                (quote!(<#codec as ::twpb::json::JsonCodec<#field_type>>::encode_json(buffer, c)?),
+                    quote!(<#codec as ::twpb::json::JsonCodec<#field_type>>::decode_json(parser, #error_name)?))
+            };
+            encodecode.extend(quote!{
+                #struct_name::#field_name(c) => {
+                    bytes_written += ::twpb::json::member(buffer, first, #json_name)?;
+                    bytes_written += #encode;
+                },
+            });
+            decodecode.extend(quote!{
+                if #matches {
+                    #check_unset
+                    if parser.null() {
+                        *value = None;
+                    } else {
+                        *value = Some(#struct_name::#field_name(#decode));
+                    }
+                    return Ok(true);
+                }
+            });
+        }
+    }
+
+    Ok(quote!{
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            // Write the selected variant as member of a JSON object, see `JsonEncoder::twpb_encode_json_members`.
+            pub fn twpb_encode_json_members(&self, buffer: &mut impl ::twpb::traits::Writer, first: &mut bool) -> Result<usize, ::twpb::traits::WriterError> {
+                let mut bytes_written = 0;
+                match &self {
+                    #encodecode
+                };
+                Ok(bytes_written)
+            }
+
+            // Read member `key` into `value` if it's one of the variants, null unsets it.
+            // A variant when `value` is already set is an error, the object has two members of the oneof.
+            pub fn twpb_decode_json_member(key: &::twpb::json::JsonStr, parser: &mut ::twpb::json::JsonParser, recursion_limit: u32, value: &mut Option<Self>) -> Result<bool, ::twpb::decoder::DecodeError> {
+                let position = parser.position();
+                #decodecode
+                Ok(false)
+            }
+        }
+    })
+}
+
+// JsonCodec for a #[twpb(transparent, json)] newtype, in the JSON of its inner scalar.
+pub fn derive_transparent(struct_name: &syn::Ident, generics: &syn::Generics, field: &ParsedField) -> TokenStream {
+    let member = &field.field_name;
+    let inner_type = &field.field_type;
+
+    let mut impl_generics_source = generics.clone();
+    let (codec_path, encode, decode, is_default) = if let Some(with) = &field.with {
+        // parsing the field checked there is a marker for the proto type
+        let codec = crate::codecs::for_proto_type(&field.proto_type).unwrap();
+        (codec,
+            quote!(#with::encode_json(buffer, &value.#member)),
+            quote!(#with::decode_json(parser, field_name)?),
+            quote!(#with::is_default(&value.#member)))
+    } else {
+        let codec = field.codec.clone().unwrap();
+        impl_generics_source.make_where_clause().predicates
+            .push(syn::parse_quote!(#codec: ::twpb::json::JsonCodec<#inner_type>));
+        (codec.clone(),
+            quote!(<#codec as ::twpb::json::JsonCodec<#inner_type>>::encode_json(buffer, &value.#member)),
+            quote!(<#codec as ::twpb::json::JsonCodec<#inner_type>>::decode_json(parser, field_name)?),
+            quote!(<#codec as ::twpb::json::JsonCodec<#inner_type>>::is_default(&value.#member)))
+    };
+
+    let (impl_generics, _, where_clause) = impl_generics_source.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    quote!{
+        impl #impl_generics ::twpb::json::JsonCodec<#struct_name #ty_generics> for #codec_path #where_clause {
+            fn encode_json(buffer: &mut impl ::twpb::traits::Writer, value: &#struct_name #ty_generics) -> Result<usize, ::twpb::traits::WriterError> {
+                #encode
+            }
+
+            fn decode_json(parser: &mut ::twpb::json::JsonParser, field_name: &'static str) -> Result<#struct_name #ty_generics, ::twpb::decoder::DecodeError> {
+                Ok(#struct_name { #member: #decode })
+            }
+
+            fn is_default(value: &#struct_name #ty_generics) -> bool {
+                #is_default
+            }
+        }
+    }
+}
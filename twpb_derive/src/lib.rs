@@ -1,20 +1,21 @@
+mod codecs;
+mod json;
+mod reflect;
+mod text;
 mod types;
-mod wiretypes;
 
 use types::*;
-use wiretypes::wire_types;
 
 extern crate proc_macro;
 extern crate proc_macro2;
 use proc_macro::{TokenStream};
-use proc_macro2::{Span};
-use quote::quote;
-use syn::{self, Data, DataStruct, DataEnum, DeriveInput, Fields, Ident};
+use quote::{quote, quote_spanned, format_ident};
+use syn::{self, spanned::Spanned, Data, DataStruct, DataEnum, DeriveInput, Fields};
 
 
 #[proc_macro_derive(Enum, attributes(twpb))]
 pub fn derive_enum(tokens: TokenStream) -> TokenStream {
-    try_derive_enum(tokens).unwrap()
+    try_derive_enum(tokens).unwrap_or_else(|e| e.to_compile_error().into())
 }
 
 
@@ -22,97 +23,203 @@ fn try_derive_enum(tokens: TokenStream) -> Result<TokenStream, syn::Error> {
     let input: DeriveInput = syn::parse(tokens)?;
 
     let struct_name = input.ident;
-    // println!("derive enum {}", struct_name);
+    let generics = input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let container = ParsedContainer::parse(&input.attrs)?;
 
     let variants = match input.data {
         Data::Enum(DataEnum{variants, ..}) => variants,
-        _ => panic!("Derive enum called on non-enum type"),
+        _ => return Err(syn::Error::new_spanned(&struct_name, "Enum can only be derived for an enum")),
     };
+    // #[twpb(enum)]: the variants are the values of a proto enum, not the fields of a oneof
+    if let Some(span) = container.enumeration {
+        if container.transparent.is_some() || container.after_decode.is_some() || container.json || container.text || container.reflect {
+            return Err(syn::Error::new(span, "a proto enum can only have the name and package options"));
+        }
+        if let Some(variant) = variants.iter().find(|v| !matches!(v.fields, Fields::Unit) || v.attrs.iter().any(|a| a.path.is_ident("twpb"))) {
+            return Err(syn::Error::new_spanned(variant,
+                "the variants of a proto enum are its values, they can't hold a value or have a #[twpb(..)] attribute"));
+        }
+        return Ok(derive_proto_enum(&struct_name, &container, &variants).into());
+    }
+    if container.transparent.is_some() || container.after_decode.is_some() || container.package.is_some() || container.name.is_some() {
+        return Err(syn::Error::new_spanned(&struct_name, "a oneof enum can only have the json, text and reflect options"));
+    }
+
+    let fields: Result<Vec<_>, _> = variants.into_iter()
+        .map(ParsedVariant::parse)
+        .collect();
+    let fields = fields?;
+    check_field_numbers(fields.iter().map(|f| (f.field_numbers.as_slice(), f.nr_span)))?;
+    let jsoncode = if container.json {
+        json::derive_enum(&struct_name, &generics, &fields)?
+    } else {
+        quote!()
+    };
+    let textcode = if container.text {
+        text::derive_enum(&struct_name, &generics, &fields)?
+    } else {
+        quote!()
+    };
+    let mut reflectcode = reflect::descriptor_enum(&struct_name, &generics, &fields);
+    if container.reflect {
+        reflectcode.extend(reflect::derive_enum(&struct_name, &generics, &fields)?);
+    }
+    // nested oneofs without explicit nr contribute their field numbers in a const context
+    let explicit_numbers: Vec<u32> = fields.iter()
+        .flat_map(|f| f.field_numbers.iter().copied())
+        .collect();
+    let mut number_checks = NumberChecks { owner: &struct_name, explicit_numbers: &explicit_numbers, inferred_oneofs: vec![] };
 
-    let mut debugmsg = quote!();
+    let mut numbercheckcode = quote!();
     let mut decodecode = quote!();
     let mut encodecode = quote!();
-    for variant in variants {
-        // println!("variant {}", variant.ident);
-
-        let field = ParsedVariant::parse(variant)?;
+    let mut lencode = quote!();
+    for field in fields {
         let field_name = field.field_name;
         let proto_type = field.proto_type;
         let field_type = field.field_type;
         let field_numbers = field.field_numbers.iter().map(|n| quote!(#n)).reduce(|acc, new| quote! {#acc , #new});
-        let first_field_number = field.field_numbers[0];
-        // println!("'{}' of type {:?} has field numbers {:?}", 
-        //     field_name, proto_type, field.field_numbers);
-        debugmsg.extend(quote!{
-            // println!("Dealing with variant '{}::{}' ({}) at field numbers [{}]",
-            //     stringify!(#struct_name), stringify!(#field_name), stringify!(#proto_type), stringify!(#field_numbers));
-        });
+        // a nested oneof without nr has no field numbers of its own, it doesn't need this
+        let first_field_number = field.field_numbers.first().copied().unwrap_or_default();
 
         if proto_type == "oneof" {
-            panic!("nested oneof unimplemented")
+            // a nested oneof is flattened into this one on the wire
+            numbercheckcode.extend(number_checks.oneof("oneof", &field_name, &field_type,
+                &field.field_numbers, field.nr_span));
+
+            decodecode.extend(quote!{
+                if <#field_type>::TWPB_FIELD_NUMBERS.contains(field_number) {
+                    let value = #struct_name::#field_name(<#field_type>::twpb_decode(field_number, wire_type, &mut *bytes, field_name, recursion_limit)?);
+                    return Ok(value);
+                }
+            });
+            encodecode.extend(quote!{
+                #struct_name::#field_name(c) => {
+                    bytes_written += ::twpb::MessageEncoder::twpb_encode(c, buffer)?;
+                },
+            });
+            lencode.extend(quote!{
+                #struct_name::#field_name(c) => ::twpb::MessageEncoder::twpb_encoded_len(c),
+            });
+        } else if proto_type == "message" && field.unit {
+            // an empty message without a Rust type, whatever it holds is ignored
+            decodecode.extend(quote!{
+                if [#field_numbers].iter().any(|&i| i == field_number) {
+                    if wire_type != ::twpb::wire_types::LENGTHDELIMITED {
+                        return Err(::twpb::decoder::DecodeError::WrongWireType(wire_type, concat!(stringify!(#struct_name), "::", stringify!(#field_name))));
+                    }
+                    ::twpb::decoder::unknown(&mut bytes, wire_type)?;
+                    return Ok(#struct_name::#field_name);
+                }
+            });
+            encodecode.extend(quote!{
+                #struct_name::#field_name => {
+                    bytes_written += ::twpb::encoder::tag(buffer, &#first_field_number, &::twpb::wire_types::LENGTHDELIMITED)?;
+                    bytes_written += ::twpb::encoder::leb128_u32(buffer, &0)?;
+                },
+            });
+            lencode.extend(quote!{
+                #struct_name::#field_name => ::twpb::encoder::tag_len(&#first_field_number) + 1,
+            });
         } else if proto_type == "message" {
             decodecode.extend(quote!{
-                // println!("testing for embedded message match '{}::{}' [{}] = '{}'", stringify!(#struct_name), stringify!(#field_name), stringify!(#field_numbers), stringify!(#field_type));
                 if [#field_numbers].iter().any(|&i| i == field_number) {
+                    if recursion_limit == 0 {
+                        return Err(::twpb::decoder::DecodeError::RecursionLimit);
+                    }
                     let bufsize = ::twpb::decoder::leb128_u32(&mut bytes)?;
-                    // println!("embedded message match with size {}", bufsize);
                     let mut iterator = ::twpb::LimitedIterator::new(&mut bytes, bufsize);
-                    let value = #struct_name::#field_name(#field_type::twpb_decode_iter(&mut iterator)?);
+                    let value = #struct_name::#field_name(<#field_type as ::twpb::MessageDecoder>::twpb_decode_iter_with_limit(&mut iterator, recursion_limit - 1)?);
                     return Ok(value);
                 }
             });
             encodecode.extend(quote!{
                 #struct_name::#field_name(c) => {
+                    // The selected variant is always written, even an empty message,
+                    // or it would decode as no variant being set at all.
                     // We need to send the payload size first.
-                    // So serialize twice, once to a nil buffer just to count bytes
-                    let mut nullbuffer = ::twpb::iterators::NullCounterBuffer::new();
-                    let len = c.twpb_encode(&mut nullbuffer)?;
-                    if len != 0 {
-                        bytes_written += ::twpb::encoder::tag(buffer, &#first_field_number, &::twpb::wire_types::LENGTHDELIMITED)?;
-                        bytes_written += ::twpb::encoder::leb128_u32(buffer, &(len as u32))?;
-                        // second time to actually send data
-                        bytes_written += c.twpb_encode(buffer)?;
-                    }
+                    let len = ::twpb::MessageEncoder::twpb_encoded_len(c);
+                    bytes_written += ::twpb::encoder::tag(buffer, &#first_field_number, &::twpb::wire_types::LENGTHDELIMITED)?;
+                    bytes_written += ::twpb::encoder::leb128_u32(buffer, &(len as u32))?;
+                    bytes_written += ::twpb::MessageEncoder::twpb_encode(c, buffer)?;
                 },
             });
-        } else {
-            let parse_fn = Ident::new(&format!("{}", &proto_type), Span::call_site());
+            lencode.extend(quote!{
+                #struct_name::#field_name(c) => {
+                    let len = ::twpb::MessageEncoder::twpb_encoded_len(c);
+                    ::twpb::encoder::tag_len(&#first_field_number) + ::twpb::encoder::leb128_len(&(len as u64)) + len
+                },
+            });
+        } else if let Some(with) = field.with {
+            // a user provided module converts between the Rust type and the wire format
+            let wire_type = codecs::wire_type_for(&proto_type)
+                .ok_or_else(|| syn::Error::new(field.attr_span, format!("with can not be used on proto type '{}'", proto_type)))?;
             decodecode.extend(quote!{
-                // println!("testing for match '{}::{}' [{}]", stringify!(#struct_name), stringify!(#field_name), stringify!(#field_numbers));
                 if [#field_numbers].iter().any(|&i| i == field_number) {
-                    // println!("enum variant match for '{}::{}' ({})", stringify!(#struct_name), stringify!(#field_name), stringify!(#proto_type));
-                    let value = #struct_name::#field_name(::twpb::decoder::#parse_fn(&mut bytes, stringify!(#struct_name::#field_name))?);
+                    let value = #struct_name::#field_name(#with::decode(&mut bytes, concat!(stringify!(#struct_name), "::", stringify!(#field_name)))?);
                     return Ok(value);
                 }
             });
-            let wire_type = wire_types::for_proto_type(proto_type.as_ref())
-                .unwrap_or_else(|| panic!("unknown wire type for proto type '{}'", proto_type));
             encodecode.extend(quote!{
                 #struct_name::#field_name(c) => {
                     bytes_written += ::twpb::encoder::tag(buffer, &#first_field_number, &#wire_type)?;
-                    bytes_written += ::twpb::encoder::#parse_fn(buffer, c)?;
+                    bytes_written += #with::encode(buffer, c)?;
+                },
+            });
+            lencode.extend(quote!{
+                #struct_name::#field_name(c) => ::twpb::encoder::tag_len(&#first_field_number) + #with::encoded_len(c),
+            });
+        } else {
+            let codec = field.codec
+                .ok_or_else(|| syn::Error::new(field.attr_span, format!("no codec for proto type '{}'", proto_type)))?;
+            decodecode.extend(quote!{
+                if [#field_numbers].iter().any(|&i| i == field_number) {
+                    let value = #struct_name::#field_name(<#codec as ::twpb::ScalarCodec<#field_type>>::decode(&mut bytes, concat!(stringify!(#struct_name), "::", stringify!(#field_name)))?);
+                    return Ok(value);
+                }
+            });
+            encodecode.extend(quote!{
+                #struct_name::#field_name(c) => {
+                    bytes_written += ::twpb::codec::encode_field::<#codec, #field_type>(buffer, #first_field_number, c)?;
                 },
             });
+            lencode.extend(quote!{
+                #struct_name::#field_name(c) => ::twpb::codec::encoded_len_field::<#codec, #field_type>(#first_field_number, c),
+            });
         }
     }
 
-    Ok(TokenStream::from(quote!{
-        impl #struct_name {
-            pub fn twpb_decode<I>(field_number: u32, wire_type: u8, mut bytes: &mut I, field_name: &str) -> Result<#struct_name, ::twpb::decoder::DecodeError>
-            where I: Iterator<Item = u8> {
-                // println!("decoding proto {}", stringify!(#struct_name));
+    // The field numbers of nested oneofs are only known to their enums, refer to them.
+    let inferred_oneofs = &number_checks.inferred_oneofs;
+    let all_field_numbers = quote!{
+        ::twpb::fieldnumbers::FieldNumbers {
+            numbers: &[#(#explicit_numbers),*],
+            nested: &[#(&<#inferred_oneofs>::TWPB_FIELD_NUMBERS),*],
+        }
+    };
 
-                #debugmsg
+    let (numbercheckitems, numbercheckuse) = number_check_code(&struct_name, &generics, numbercheckcode);
 
-                // println!("got field nr {}", field_number);
-                // println!("got wire type {}", wire_type);
+    Ok(TokenStream::from(quote!{
+        #numbercheckitems
+        #jsoncode
+        #textcode
+        #reflectcode
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            // All field numbers of this oneof, messages containing it dispatch on these.
+            pub const TWPB_FIELD_NUMBERS: ::twpb::fieldnumbers::FieldNumbers = #all_field_numbers;
+
+            pub fn twpb_decode<I>(field_number: u32, wire_type: u8, mut bytes: &mut I, field_name: &str, recursion_limit: u32) -> Result<Self, ::twpb::decoder::DecodeError>
+            where I: Iterator<Item = u8> {
+                #numbercheckuse
 
                 #decodecode
 
                 return Err(::twpb::decoder::DecodeError::UnexpectedEndOfBuffer);
             }
         }
-        impl ::twpb::MessageEncoder for #struct_name {
+        impl #impl_generics ::twpb::MessageEncoder for #struct_name #ty_generics #where_clause {
             fn twpb_encode(&self, buffer: &mut impl ::twpb::traits::Writer) -> Result<usize, ::twpb::traits::WriterError> {
                 let mut bytes_written = 0;
                 match &self {
@@ -120,103 +227,479 @@ fn try_derive_enum(tokens: TokenStream) -> Result<TokenStream, syn::Error> {
                 };
                 Ok(bytes_written)
             }
+
+            fn twpb_encoded_len(&self) -> usize {
+                match &self {
+                    #lencode
+                }
+            }
         }
     }))
 }
 
+// The field numbers of a message or enum (`owner`), to check those of its oneofs and flattened messages against.
+// The numbers of a oneof without nr are only known to its own type, they're collected in `inferred_oneofs`.
+struct NumberChecks<'a> {
+    owner: &'a syn::Ident,
+    explicit_numbers: &'a [u32],
+    inferred_oneofs: Vec<syn::Type>,
+}
+
+impl NumberChecks<'_> {
+    // Const assertions on the field numbers of a oneof or flattened message (`kind`) inside `owner`.
+    // Its numbers are only known to its own type, so these can't be checked by the macro.
+    // An explicit nr must match the type exactly, otherwise its numbers must not be used by any other field.
+    fn oneof(&mut self, kind: &str, field_name: &dyn quote::ToTokens, oneof: &syn::Type,
+        field_numbers: &[u32], nr_span: proc_macro2::Span) -> proc_macro2::TokenStream {
+        let owner = self.owner;
+        let explicit_numbers = self.explicit_numbers;
+        let mut checks = quote!();
+        if field_numbers.is_empty() {
+            checks.extend(quote_spanned!{nr_span=>
+                assert!(::twpb::fieldnumbers::disjoint(&::twpb::fieldnumbers::FieldNumbers { numbers: &[#(#explicit_numbers),*], nested: &[] }, &<#oneof>::TWPB_FIELD_NUMBERS),
+                    concat!(#kind, " `", stringify!(#field_name), "` uses field numbers of other fields of `", stringify!(#owner), "`"));
+            });
+            for other in self.inferred_oneofs.iter() {
+                checks.extend(quote_spanned!{nr_span=>
+                    assert!(::twpb::fieldnumbers::disjoint(&<#other>::TWPB_FIELD_NUMBERS, &<#oneof>::TWPB_FIELD_NUMBERS),
+                        concat!(#kind, " `", stringify!(#field_name), "` uses field numbers of other fields of `", stringify!(#owner), "`"));
+                });
+            }
+            self.inferred_oneofs.push(oneof.clone());
+        } else {
+            checks.extend(quote_spanned!{nr_span=>
+                assert!(::twpb::fieldnumbers::same(&[#(#field_numbers),*], &<#oneof>::TWPB_FIELD_NUMBERS),
+                    concat!("nr of oneof `", stringify!(#field_name), "` does not match the field numbers of `", stringify!(#oneof), "`"));
+            });
+        }
+        checks
+    }
+}
+
+// Wraps the const assertions of `NumberChecks::oneof` into an associated const of `name`.
+// Returns the items to emit, and a statement forcing their evaluation from a function of `name`.
+// Without generics they are checked right away, otherwise once `name` gets instantiated.
+fn number_check_code(name: &syn::Ident, generics: &syn::Generics, checks: proc_macro2::TokenStream)
+    -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    if checks.is_empty() {
+        return (quote!(), quote!());
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let mut items = quote!{
+        impl #impl_generics #name #ty_generics #where_clause {
+            const TWPB_NUMBER_CHECK: () = {
+                #checks
+            };
+        }
+    };
+    if generics.params.is_empty() {
+        items.extend(quote!{
+            const _: () = #name::TWPB_NUMBER_CHECK;
+        });
+    }
+    (items, quote!{
+        let () = Self::TWPB_NUMBER_CHECK;
+    })
+}
+
+// A #[twpb(transparent)] newtype is not a message, it is encoded like its inner scalar.
+// It can then be used as a field of that scalar type, e.g. `#[twpb(fixed64, nr=1)] id: DeviceId`.
+fn derive_transparent(struct_name: &syn::Ident, generics: &syn::Generics, field: ParsedField) -> proc_macro2::TokenStream {
+    let member = field.field_name;
+    let inner_type = field.field_type;
+
+    let mut impl_generics_source = generics.clone();
+    let (codec_path, wire_type, encode, decode, encoded_len) = if let Some(with) = field.with {
+        // wire_type_for was checked when parsing the field
+        let wire_type = codecs::wire_type_for(&field.proto_type).unwrap();
+        // `with` modules don't name a type, use the proto type's marker
+        let codec = codecs::for_proto_type(&field.proto_type).unwrap();
+        (codec, quote!(#wire_type),
+            quote!(#with::encode(buffer, &value.#member)),
+            quote!(#with::decode(bytes, field_name)?),
+            quote!(#with::encoded_len(&value.#member)))
+    } else {
+        let codec = field.codec.unwrap();
+        impl_generics_source.make_where_clause().predicates
+            .push(syn::parse_quote!(#codec: ::twpb::ScalarCodec<#inner_type>));
+        (codec.clone(), quote!(<#codec as ::twpb::ScalarCodec<#inner_type>>::WIRE_TYPE),
+            quote!(<#codec as ::twpb::ScalarCodec<#inner_type>>::encode(buffer, &value.#member)),
+            quote!(<#codec as ::twpb::ScalarCodec<#inner_type>>::decode(bytes, field_name)?),
+            quote!(<#codec as ::twpb::ScalarCodec<#inner_type>>::encoded_len(&value.#member)))
+    };
+
+    let (impl_generics, _, where_clause) = impl_generics_source.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    quote!{
+        impl #impl_generics ::twpb::ScalarCodec<#struct_name #ty_generics> for #codec_path #where_clause {
+            const WIRE_TYPE: u8 = #wire_type;
+
+            fn encode(buffer: &mut impl ::twpb::traits::Writer, value: &#struct_name #ty_generics) -> Result<usize, ::twpb::traits::WriterError> {
+                #encode
+            }
+
+            fn decode<I>(bytes: I, field_name: &'static str) -> Result<#struct_name #ty_generics, ::twpb::decoder::DecodeError>
+            where I: Iterator<Item = u8> {
+                Ok(#struct_name { #member: #decode })
+            }
+
+            fn encoded_len(value: &#struct_name #ty_generics) -> usize {
+                #encoded_len
+            }
+        }
+    }
+}
+
+// A proto enum converts to and from its i32 value, and names its values for JSON and text,
+// e.g. `Mode::On` is `MODE_ON`. Values unknown to the Rust enum don't convert.
+fn derive_proto_enum(enum_name: &syn::Ident, container: &ParsedContainer, variants: &syn::punctuated::Punctuated<syn::Variant, syn::Token![,]>) -> proc_macro2::TokenStream {
+    let descriptor = reflect::descriptor_proto_enum(enum_name, &container.proto_name(enum_name), &container.package());
+    let prefix = snake_case(&enum_name.to_string()).to_uppercase();
+    let variant_names: Vec<_> = variants.iter().map(|v| &v.ident).collect();
+    let value_names = variant_names.iter()
+        .map(|v| format!("{}_{}", prefix, snake_case(&v.to_string()).to_uppercase()));
+    quote!{
+        impl ::core::convert::From<#enum_name> for i32 {
+            fn from(value: #enum_name) -> i32 {
+                value as i32
+            }
+        }
+
+        impl ::core::convert::TryFrom<i32> for #enum_name {
+            type Error = ();
+
+            fn try_from(value: i32) -> Result<#enum_name, ()> {
+                #(
+                    if value == #enum_name::#variant_names as i32 {
+                        return Ok(#enum_name::#variant_names);
+                    }
+                )*
+                Err(())
+            }
+        }
+
+        impl ::twpb::json::EnumNames for #enum_name {
+            const NAMES: &'static [(&'static str, i32)] = &[#((#value_names, #enum_name::#variant_names as i32)),*];
+        }
+
+        #descriptor
+    }
+}
+
 #[proc_macro_derive(Message, attributes(twpb))]
 pub fn derive_message(tokens: TokenStream) -> TokenStream {
-    try_derive_message(tokens).unwrap()
+    try_derive_message(tokens).unwrap_or_else(|e| e.to_compile_error().into())
 }
 
 fn try_derive_message(tokens: TokenStream) -> Result<TokenStream, syn::Error> {
     let input: DeriveInput = syn::parse(tokens)?;
 
     let struct_name = input.ident;
+    let generics = input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let container = ParsedContainer::parse(&input.attrs)?;
 
     // Get all struct fields
-    let fields = match input.data {
+    let fields: Vec<syn::Field> = match input.data {
         Data::Struct(DataStruct{fields: Fields::Named(syn::FieldsNamed{named: fields, ..}), ..})
             => fields.into_iter().collect(),
+        // tuple structs, fields are accessed by index
+        Data::Struct(DataStruct{fields: Fields::Unnamed(syn::FieldsUnnamed{unnamed: fields, ..}), ..})
+            => fields.into_iter().collect(),
         // There can also be no fields at all
         Data::Struct(DataStruct{fields: Fields::Unit, ..}) => vec![],
 
-        Data::Struct(DataStruct{fields: Fields::Unnamed(..), ..})
-            => panic!("Unnamed fields are not supported"),
-        Data::Enum(..) => panic!("Message can not be derived for an enum"),
-        Data::Union(..) => panic!("Message can not be derived for a union"),
+        Data::Enum(..) => return Err(syn::Error::new_spanned(&struct_name, "Message can not be derived for an enum")),
+        Data::Union(..) => return Err(syn::Error::new_spanned(&struct_name, "Message can not be derived for a union")),
     };
+    if let Some(span) = container.enumeration {
+        return Err(syn::Error::new(span, "a message is not a proto enum, derive Enum for an enum instead"));
+    }
+
+    if let Some(span) = container.transparent {
+        if fields.len() != 1 {
+            return Err(syn::Error::new(span, "a transparent newtype must have exactly one field"));
+        }
+        if let Some(after_decode) = container.after_decode {
+            return Err(syn::Error::new_spanned(after_decode, "a transparent newtype can not have an after_decode hook"));
+        }
+        if let Some(package) = container.package {
+            return Err(syn::Error::new_spanned(package, "a transparent newtype is not a message, it has no package"));
+        }
+        if let Some(name) = container.name {
+            return Err(syn::Error::new_spanned(name, "a transparent newtype is not a message, it has no proto name"));
+        }
+        let field = ParsedField::parse_transparent(fields.into_iter().next().unwrap(), 0)?;
+        let mut tokens = if container.json {
+            json::derive_transparent(&struct_name, &generics, &field)
+        } else {
+            quote!()
+        };
+        if container.text {
+            tokens.extend(text::derive_transparent(&struct_name, &generics, &field));
+        }
+        if container.reflect {
+            tokens.extend(reflect::derive_transparent(&struct_name, &generics, &field));
+        }
+        tokens.extend(derive_transparent(&struct_name, &generics, field));
+        return Ok(tokens.into());
+    }
 
     // Parse each field and extract protobuf info
     let fields: Result<Vec<_>, _> = fields.into_iter()
-        .map(|field| ParsedField::parse(field))
+        .enumerate()
+        .map(|(index, field)| ParsedField::parse(field, index))
         .collect();
-    let fields = fields?;
+    // skipped fields keep their Default value, there is nothing to generate for them
+    let fields: Vec<_> = fields?.into_iter()
+        .filter(|field| !field.skip)
+        .collect();
+    check_field_numbers(fields.iter().map(|f| (f.field_numbers.as_slice(), f.nr_span)))?;
+    let proto_name = container.proto_name(&struct_name);
+    let package = container.package();
+
+    // the hook gets coerced first, so a wrong signature is reported at the attribute
+    let after_decode = container.after_decode.map(|hook| quote_spanned!{hook.span()=>
+        let hook: fn(&mut Self) -> Result<(), ::twpb::decoder::DecodeError> = #hook;
+        hook(&mut result)?;
+    });
+    let jsoncode = if container.json {
+        json::derive_message(&struct_name, &generics, &fields, &after_decode)?
+    } else {
+        quote!()
+    };
+    let textcode = if container.text {
+        text::derive_message(&struct_name, &generics, &fields, &after_decode)?
+    } else {
+        quote!()
+    };
+    let mut reflectcode = reflect::descriptor_message(&struct_name, &proto_name, &package, &generics, &fields)?;
+    if container.reflect {
+        reflectcode.extend(reflect::derive_message(&struct_name, &generics, &fields)?);
+    }
 
+    // The field numbers of a oneof without explicit nr are only known to its enum,
+    // so the overlap checks for those happen in a const context, see `numbercheckcode`.
+    let explicit_numbers: Vec<u32> = fields.iter()
+        .flat_map(|f| f.field_numbers.iter().copied())
+        .collect();
+    let mut number_checks = NumberChecks { owner: &struct_name, explicit_numbers: &explicit_numbers, inferred_oneofs: vec![] };
+
+    let mut numbercheckcode = quote!();
     let mut allocatecode = quote!();
     let mut decodecode = quote!();
+    // fixed size arrays count their values while decoding, they are decoded in the decode loop
+    let mut arraydecodecode = quote!();
+    let mut has_arrays = false;
+    let mut flattened: Vec<syn::Type> = vec![];
+    let mut checkcode = quote!();
     let mut encodecode = quote!();
+    let mut lencode = quote!();
     for field in fields {
-        // println!("'{}::{:?}' of type {:?} has field numbers {:?}",
-        //     struct_name, field.field_name, field.proto_type, field.field_numbers);
-
         let field_name = field.field_name;
         let proto_type = field.proto_type;
+        let mut fielddecode = quote!();
+        let is_array = field.repeated && matches!(field.field_type, syn::Type::Array(_));
         let field_numbers = field.field_numbers.iter().map(|n| quote!(#n)).reduce(|acc, new| quote! {#acc , #new});
-        let first_field_number = field.field_numbers[0];
-        // allocatecode.extend(quote!{
-        //     // println!("Dealing with '{}::{}' ({}) at field numbers [{}]",
-        //     //     stringify!(#struct_name), stringify!(#field_name), stringify!(#proto_type), stringify!(#field_numbers));
-        // });
+        // a oneof without nr has no field numbers of its own, it doesn't need this
+        let first_field_number = field.field_numbers.first().copied().unwrap_or_default();
+        // How a decoded value of a repeated field gets stored.
+        // Fixed size arrays must receive exactly as many values as they can hold,
+        // everything else is a heapless::Vec that gets pushed to.
+        let mut push = quote!{
+            result.#field_name.push(value).map_err(|_| ::twpb::decoder::DecodeError::UnexpectedEndOfBuffer)
+        };
         if field.repeated {
-            allocatecode.extend(quote!{
-                result.#field_name = ::heapless::Vec::new();
-            });
+            if is_array {
+                has_arrays = true;
+                let counter = format_ident!("__twpb_{}_len", field_name);
+                allocatecode.extend(quote!{
+                    let mut #counter: usize = 0;
+                });
+                push = quote!{{
+                    let slot = result.#field_name.get_mut(#counter)
+                        .ok_or(::twpb::decoder::DecodeError::LengthMismatch(stringify!(#field_name)))?;
+                    *slot = value;
+                    #counter += 1;
+                    Ok(())
+                }};
+                checkcode.extend(quote!{
+                    if #counter != result.#field_name.len() {
+                        return Err(::twpb::decoder::DecodeError::LengthMismatch(stringify!(#field_name)));
+                    }
+                });
+            } else {
+                allocatecode.extend(quote!{
+                    result.#field_name = ::heapless::Vec::new();
+                });
+            }
         }
 
-        if proto_type == "oneof" {
+        if proto_type == "flatten" {
+            // the fields of a flattened message are part of this one,
+            // it's encoded without tag or length and decoded one field at a time
+            let field_type = &field.field_type;
+            numbercheckcode.extend(number_checks.oneof("flattened message", &field_name, field_type,
+                &field.field_numbers, field.nr_span));
+            numbercheckcode.extend(quote_spanned!{field.attr_span=>
+                assert!(<#field_type>::TWPB_FLATTENABLE,
+                    concat!("`", stringify!(#field_type), "` can not be flattened, it has fixed size arrays or an after_decode hook"));
+            });
+            flattened.push(field_type.clone());
+
+            fielddecode.extend(quote!{
+                if !fieldMatch {
+                    fieldMatch = <#field_type>::twpb_decode_field(&mut result.#field_name, field_number, wire_type, &mut bytes, recursion_limit)?;
+                }
+            });
+            encodecode.extend(quote!{
+                bytes_written += ::twpb::MessageEncoder::twpb_encode(&self.#field_name, buffer)?;
+            });
+            lencode.extend(quote!{
+                len += ::twpb::MessageEncoder::twpb_encoded_len(&self.#field_name);
+            });
+
+        } else if proto_type == "oneof" {
             // oneofs are always wrapped into a rust Option object, so we need what's _in_ the Option
+            let optionarg = option_inner(&field.field_type)
+                .ok_or_else(|| syn::Error::new_spanned(&field.field_type, "oneof fields must be wrapped in an Option"))?;
 
-            // Get the Option object
-            let optionarg = match field.field_type {
-                syn::Type::Path(syn::TypePath{path: syn::Path{segments: b, ..}, ..}) => 
-                b.into_iter().find(|b| b.ident == "Option" && ! b.arguments.is_empty()).unwrap().arguments,
-                _ => panic!("oneof field '{}' not wrapped in Option object", field_name),
-            };
+            numbercheckcode.extend(number_checks.oneof("oneof", &field_name, optionarg,
+                &field.field_numbers, field.nr_span));
 
-            // Get the arguments inside it
-            let optionarg = match optionarg {
-                syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments{args: b, ..}) => b,
-                _ => panic!("Invalid Option object for oneof field '{}'", field_name),
-            };
+            fielddecode.extend(quote!{
+                if <#optionarg>::TWPB_FIELD_NUMBERS.contains(field_number) {
+                    fieldMatch = true;
+                    result.#field_name = Some(<#optionarg>::twpb_decode(field_number, wire_type, &mut bytes, stringify!(#field_name), recursion_limit)?);
+                }
+            });
 
-            // There can be only one
-            let optionarg = &optionarg[0];
+            encodecode.extend(quote!{
+                if let Some(value) = self.#field_name.as_ref() {
+                    bytes_written += ::twpb::MessageEncoder::twpb_encode(value, buffer)?;
+                }
+            });
+            lencode.extend(quote!{
+                if let Some(value) = self.#field_name.as_ref() {
+                    len += ::twpb::MessageEncoder::twpb_encoded_len(value);
+                }
+            });
 
-            // println!("message encountered enum for {:?}", optionarg);
-            decodecode.extend(quote!{
+        } else if proto_type == "message" {
+            // embedded messages have explicit presence, so they are wrapped into a rust Option object
+            let optionarg = option_inner(&field.field_type)
+                .ok_or_else(|| syn::Error::new_spanned(&field.field_type, "message fields must be wrapped in an Option"))?;
+
+            // Recursive messages need a pointer type implementing ::twpb::MessageBox
+            let (decode_value, as_message) = if is_box(optionarg) {
+                (quote!{{
+                    // Erase the iterator type, or every level of nesting would instantiate
+                    // the decoder with an ever longer iterator type.
+                    let iterator: &mut dyn Iterator<Item = u8> = &mut iterator;
+                    let value = <<#optionarg as ::core::ops::Deref>::Target as ::twpb::MessageDecoder>::twpb_decode_iter_with_limit(iterator, recursion_limit - 1)?;
+                    <#optionarg as ::twpb::MessageBox>::new_box(value)
+                        .ok_or(::twpb::decoder::DecodeError::AllocationFailed(stringify!(#field_name)))?
+                }}, quote!(::core::ops::Deref::deref(value)))
+            } else {
+                (quote!{
+                    <#optionarg as ::twpb::MessageDecoder>::twpb_decode_iter_with_limit(&mut iterator, recursion_limit - 1)?
+                }, quote!(value))
+            };
+
+            fielddecode.extend(quote!{
                 if [#field_numbers].iter().any(|&i| i == field_number) {
                     fieldMatch = true;
-                    // println!("parsing enum field of type '{}'", stringify!(#optionarg));
-                    // println!("match for '{}::{}' ({})", stringify!(#struct_name), stringify!(#field_name), stringify!(#proto_type));
-                    result.#field_name = #optionarg::twpb_decode(field_number, wire_type, &mut bytes, stringify!(#field_name)).ok();
+                    if recursion_limit == 0 {
+                        return Err(::twpb::decoder::DecodeError::RecursionLimit);
+                    }
+                    let bufsize = ::twpb::decoder::leb128_u32(&mut bytes)?;
+                    let mut iterator = ::twpb::LimitedIterator::new(&mut bytes, bufsize);
+                    result.#field_name = Some(#decode_value);
                 }
             });
 
             encodecode.extend(quote!{
                 if let Some(value) = self.#field_name.as_ref() {
-                    bytes_written += value.twpb_encode(buffer)?;
+                    let value = #as_message;
+                    // We need to send the payload size first.
+                    let len = ::twpb::MessageEncoder::twpb_encoded_len(value);
+                    bytes_written += ::twpb::encoder::tag(buffer, &#first_field_number, &::twpb::wire_types::LENGTHDELIMITED)?;
+                    bytes_written += ::twpb::encoder::leb128_u32(buffer, &(len as u32))?;
+                    bytes_written += ::twpb::MessageEncoder::twpb_encode(value, buffer)?;
+                }
+            });
+            lencode.extend(quote!{
+                if let Some(value) = self.#field_name.as_ref() {
+                    let l = ::twpb::MessageEncoder::twpb_encoded_len(#as_message);
+                    len += ::twpb::encoder::tag_len(&#first_field_number) + ::twpb::encoder::leb128_len(&(l as u64)) + l;
                 }
-            })
+            });
 
-        } else if proto_type == "message" {
-            panic!("encountered embedded message for {}, {}", field_name, proto_type);
+        } else if let Some(with) = field.with {
+            // a user provided module converts between the Rust type and the wire format
+            let wire_type = codecs::wire_type_for(&proto_type)
+                .ok_or_else(|| syn::Error::new(field.attr_span, format!("with can not be used on proto type '{}'", proto_type)))?;
 
-        } else {
-            let parse_fn = Ident::new(&format!("{}", &proto_type), Span::call_site());
+            if field.repeated {
+                // always non-packed, see below
+                encodecode.extend(quote!{
+                    for val in self.#field_name.iter() {
+                        bytes_written += ::twpb::encoder::tag(buffer, &#first_field_number, &#wire_type)?;
+                        bytes_written += #with::encode(buffer, val)?;
+                    }
+                });
+                lencode.extend(quote!{
+                    for val in self.#field_name.iter() {
+                        len += ::twpb::encoder::tag_len(&#first_field_number) + #with::encoded_len(val);
+                    }
+                });
+                fielddecode.extend(quote!{
+                    if [#field_numbers].iter().any(|&i| i == field_number) {
+                        fieldMatch = true;
+                        let mut push = |value| -> Result<(), ::twpb::decoder::DecodeError> { #push };
+                        // packed repeated field
+                        if wire_type == ::twpb::wire_types::LENGTHDELIMITED && #wire_type != ::twpb::wire_types::LENGTHDELIMITED {
+                            let bufsize = ::twpb::decoder::leb128_u32(&mut bytes)?;
+                            let mut iterator = ::twpb::LimitedIterator::new(&mut bytes, bufsize);
+                            loop {
+                                match #with::decode(&mut iterator, stringify!(#field_name)) {
+                                    Ok(value) => push(value)?,
+                                    Err(::twpb::decoder::DecodeError::EmptyBuffer) => break,
+                                    Err(e) => return Err(e),
+                                };
+                            }
+                        // non-packed repeated field
+                        } else {
+                            push(#with::decode(&mut bytes, stringify!(#field_name))?)?;
+                        }
+                    }
+                });
+            } else {
+                encodecode.extend(quote!{
+                    // only a length byte would be written, content is empty
+                    if #wire_type != ::twpb::wire_types::LENGTHDELIMITED || #with::encoded_len(&self.#field_name) != 1 {
+                        bytes_written += ::twpb::encoder::tag(buffer, &#first_field_number, &#wire_type)?;
+                        bytes_written += #with::encode(buffer, &self.#field_name)?;
+                    }
+                });
+                lencode.extend(quote!{
+                    let l = #with::encoded_len(&self.#field_name);
+                    if #wire_type != ::twpb::wire_types::LENGTHDELIMITED || l != 1 {
+                        len += ::twpb::encoder::tag_len(&#first_field_number) + l;
+                    }
+                });
+                fielddecode.extend(quote!{
+                    if [#field_numbers].iter().any(|&i| i == field_number) {
+                        fieldMatch = true;
+                        result.#field_name = #with::decode(&mut bytes, stringify!(#field_name))?;
+                    }
+                });
+            }
 
-            let wire_type = wire_types::for_proto_type(proto_type.as_ref())
-                .unwrap_or_else(|| panic!("unknown wire type for proto type '{}'", proto_type));
+        } else {
+            let codec = field.codec
+                .ok_or_else(|| syn::Error::new(field.attr_span, format!("no codec for proto type '{}'", proto_type)))?;
+            let field_type = field.field_type;
 
             // if the value is a repeated field, we need to iterate over the values
             if field.repeated {
@@ -238,68 +721,98 @@ fn try_derive_message(tokens: TokenStream) -> Result<TokenStream, syn::Error> {
                 // forward- and backward-compatible way.
                 encodecode.extend(quote!{
                     for val in self.#field_name.iter() {
-                        bytes_written += ::twpb::encoder::tag(buffer, &#first_field_number, &#wire_type)?;
-                        bytes_written += ::twpb::encoder::#parse_fn(buffer, val)?;
+                        bytes_written += ::twpb::codec::encode_field::<#codec, _>(buffer, #first_field_number, val)?;
+                    }
+                });
+                lencode.extend(quote!{
+                    for val in self.#field_name.iter() {
+                        len += ::twpb::codec::encoded_len_field::<#codec, _>(#first_field_number, val);
                     }
                 });
             // non-repeated field -> just write the value
             } else {
                 encodecode.extend(quote!{
-                    let mut skip = false;
-                    if #wire_type == ::twpb::wire_types::LENGTHDELIMITED {
-                        let mut nullbuffer = ::twpb::iterators::NullCounterBuffer::new();
-                        let l = ::twpb::encoder::#parse_fn(&mut nullbuffer, &self.#field_name)?;
-                        // only a length byte was written, content is empty
-                        skip = l == 1;
-                    }
-                    if !skip {
-                        bytes_written += ::twpb::encoder::tag(buffer, &#first_field_number, &#wire_type)?;
-                        bytes_written += ::twpb::encoder::#parse_fn(buffer, &self.#field_name)?;
-                    }
+                    bytes_written += ::twpb::codec::encode_singular::<#codec, #field_type>(buffer, #first_field_number, &self.#field_name)?;
+                });
+                lencode.extend(quote!{
+                    len += ::twpb::codec::encoded_len_singular::<#codec, #field_type>(#first_field_number, &self.#field_name);
                 });
             }
 
             if field.repeated {
-                decodecode.extend(quote!{
+                fielddecode.extend(quote!{
                     if [#field_numbers].iter().any(|&i| i == field_number) {
                         fieldMatch = true;
-                        // packed repeated field
-                        // 'string' and 'bytes' are never packed, because their non-repeated encoding is already the same as packed repeated encoding
-                        if wire_type == ::twpb::wire_types::LENGTHDELIMITED && #proto_type != "string" && #proto_type != "bytes" {
-                            let bufsize = ::twpb::decoder::leb128_u32(&mut bytes)?;
-                            let mut iterator = ::twpb::LimitedIterator::new(&mut bytes, bufsize);
-                            loop {
-                                match ::twpb::decoder::#parse_fn(&mut iterator, stringify!(#field_name)) {
-                                    Ok(value) => result.#field_name.push(value).map_err(|_| ::twpb::decoder::DecodeError::UnexpectedEndOfBuffer)?,
-                                    Err(::twpb::decoder::DecodeError::EmptyBuffer) => break,
-                                    Err(e) => return Err(e),
-                                };
-                            }
-                        // non-packed repeated field
-                        } else {
-                            let value = ::twpb::decoder::#parse_fn(&mut bytes, stringify!(#field_name))?;
-                            result.#field_name.push(value).map_err(|_| ::twpb::decoder::DecodeError::UnexpectedEndOfBuffer)?;
-                        }
+                        ::twpb::codec::decode_repeated::<#codec, _, _, _>(wire_type, &mut bytes, stringify!(#field_name), |value| {
+                            #push
+                        })?;
                     }
                 });
             } else {
-                decodecode.extend(quote!{
+                fielddecode.extend(quote!{
                     if [#field_numbers].iter().any(|&i| i == field_number) {
                         fieldMatch = true;
-                        // println!("match for '{}::{}' ({})", stringify!(#struct_name), stringify!(#field_name), stringify!(#proto_type));
-                        result.#field_name = ::twpb::decoder::#parse_fn(&mut bytes, stringify!(#field_name))?;
+                        result.#field_name = <#codec as ::twpb::ScalarCodec<#field_type>>::decode(&mut bytes, stringify!(#field_name))?;
                     }
                 });
             }
         }
+
+        if is_array {
+            arraydecodecode.extend(fielddecode);
+        } else {
+            decodecode.extend(fielddecode);
+        }
     }
 
+    let (numbercheckitems, numbercheckuse) = number_check_code(&struct_name, &generics, numbercheckcode);
+    let inferred_oneofs = &number_checks.inferred_oneofs;
+    let type_url = if package.is_empty() {
+        format!("type.googleapis.com/{}", proto_name)
+    } else {
+        format!("type.googleapis.com/{}.{}", package, proto_name)
+    };
+    // a flattened message is decoded one field at a time, arrays must get all their values
+    // and the after_decode hook must see the whole message
+    let has_hook = after_decode.is_some();
+    let flattenable = quote!(!#has_arrays && !#has_hook #(&& <#flattened>::TWPB_FLATTENABLE)*);
+
     Ok(TokenStream::from(quote!{
-        impl ::twpb::MessageDecoder for #struct_name {
-            fn twpb_decode_iter<I>(mut bytes: I) -> Result<#struct_name, ::twpb::decoder::DecodeError>
+        #numbercheckitems
+        #jsoncode
+        #textcode
+        #reflectcode
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            // All field numbers of this message, including those of its oneofs and flattened messages.
+            pub const TWPB_FIELD_NUMBERS: ::twpb::fieldnumbers::FieldNumbers = ::twpb::fieldnumbers::FieldNumbers {
+                numbers: &[#(#explicit_numbers),*],
+                nested: &[#(&<#inferred_oneofs>::TWPB_FIELD_NUMBERS),*],
+            };
+            // Whether this message can be #[twpb(flatten)] into another one.
+            pub const TWPB_FLATTENABLE: bool = #flattenable;
+
+            // Decodes a single field into self, returns false if the field number isn't part of this message.
+            pub fn twpb_decode_field<I>(&mut self, field_number: u32, wire_type: u8, mut bytes: &mut I, recursion_limit: u32) -> Result<bool, ::twpb::decoder::DecodeError>
+            where I: Iterator<Item = u8> {
+                let result = self;
+                let mut fieldMatch = false;
+                #decodecode
+                Ok(fieldMatch)
+            }
+        }
+        impl #impl_generics ::twpb::TypeName for #struct_name #ty_generics #where_clause {
+            const TYPE_URL: &'static str = #type_url;
+        }
+        impl #impl_generics ::twpb::MessageDecoder for #struct_name #ty_generics #where_clause {
+            fn twpb_decode_iter<I>(bytes: I) -> Result<Self, ::twpb::decoder::DecodeError>
             where I: Iterator<Item = u8> {
-                // println!("decoding proto {}", stringify!(#struct_name));
-                let mut result = #struct_name::default();
+                Self::twpb_decode_iter_with_limit(bytes, ::twpb::decoder::RECURSION_LIMIT)
+            }
+
+            fn twpb_decode_iter_with_limit<I>(mut bytes: I, recursion_limit: u32) -> Result<Self, ::twpb::decoder::DecodeError>
+            where I: Iterator<Item = u8> {
+                #numbercheckuse
+                let mut result = <Self as ::core::default::Default>::default();
 
                 #allocatecode
 
@@ -309,10 +822,8 @@ fn try_derive_message(tokens: TokenStream) -> Result<TokenStream, syn::Error> {
                 loop {
                     match ::twpb::decoder::tag(&mut bytes) {
                         Ok((field_number, wire_type)) => {
-                            // println!("got field nr {}", field_number);
-                            // println!("got wire type {}", wire_type);
-                            let mut fieldMatch = false;
-                            #decodecode
+                            let mut fieldMatch = Self::twpb_decode_field(&mut result, field_number, wire_type, &mut bytes, recursion_limit)?;
+                            #arraydecodecode
                             if !fieldMatch {
                                 ::twpb::decoder::unknown(&mut bytes, wire_type)?;
                             }
@@ -321,15 +832,26 @@ fn try_derive_message(tokens: TokenStream) -> Result<TokenStream, syn::Error> {
                         Err(e) => return Err(e),
                     }
                 }
+
+                #checkcode
+
+                #after_decode
+
                 Ok(result)
             }
         }
-        impl ::twpb::MessageEncoder for #struct_name {
+        impl #impl_generics ::twpb::MessageEncoder for #struct_name #ty_generics #where_clause {
             fn twpb_encode(&self, buffer: &mut impl ::twpb::traits::Writer) -> Result<usize, ::twpb::traits::WriterError> {
                 let mut bytes_written = 0;
                 #encodecode
                 Ok(bytes_written)
             }
+
+            fn twpb_encoded_len(&self) -> usize {
+                let mut len = 0;
+                #lencode
+                len
+            }
         }
     }))
 }
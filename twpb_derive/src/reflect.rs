@@ -0,0 +1,673 @@
+// Code generation for the descriptors of every message, oneof and proto enum,
+// and for the Reflect trait of #[twpb(reflect)] messages, see `twpb::reflect` for the runtime side.
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::types::*;
+
+fn proto_type_variant(proto_type: &str) -> TokenStream {
+    let variant = match proto_type {
+        "int32" => quote!(Int32),
+        "int64" => quote!(Int64),
+        "uint32" => quote!(UInt32),
+        "uint64" => quote!(UInt64),
+        "sint32" => quote!(SInt32),
+        "sint64" => quote!(SInt64),
+        "fixed32" => quote!(Fixed32),
+        "fixed64" => quote!(Fixed64),
+        "sfixed32" => quote!(SFixed32),
+        "sfixed64" => quote!(SFixed64),
+        "float" => quote!(Float),
+        "double" => quote!(Double),
+        "bool" => quote!(Bool),
+        "string" => quote!(String),
+        "bytes" => quote!(Bytes),
+        "enum" => quote!(Enum),
+        "message" => quote!(Message),
+        _ => quote!(Custom),
+    };
+    quote!(::twpb::reflect::ProtoType::#variant)
+}
+
+// The message type of an `Option<T>` or `Option<Box<T>>` field.
+fn message_type(optionarg: &syn::Type) -> &syn::Type {
+    if !is_box(optionarg) {
+        return optionarg;
+    }
+    match optionarg {
+        syn::Type::Path(syn::TypePath{path, ..}) => match &path.segments.last().unwrap().arguments {
+            syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+                Some(syn::GenericArgument::Type(t)) => t,
+                _ => optionarg,
+            },
+            _ => optionarg,
+        },
+        _ => optionarg,
+    }
+}
+
+// Whether the type is one of the type parameters of the message, e.g. `T` of `Envelope<T>`.
+// Those have no inherent DESCRIPTOR to refer to.
+fn is_type_param(ty: &syn::Type, generics: &syn::Generics) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath{qself: None, path}) => match path.get_ident() {
+            Some(ident) => generics.type_params().any(|p| p.ident == *ident),
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+// The type of a single value, e.g. `types::Mode` for `heapless::Vec<types::Mode, 4>` of a repeated field.
+fn value_type(ty: &syn::Type, repeated: bool) -> &syn::Type {
+    match ty {
+        syn::Type::Array(array) if repeated => &array.elem,
+        syn::Type::Path(syn::TypePath{path, ..}) if repeated => match &path.segments.last().unwrap().arguments {
+            syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+                Some(syn::GenericArgument::Type(t)) => t,
+                _ => ty,
+            },
+            _ => ty,
+        },
+        _ => ty,
+    }
+}
+
+// The name of the Rust type of a value, without path or generic arguments, e.g. `Mode` for `types::Mode`.
+fn type_name(ty: &syn::Type) -> String {
+    match ty {
+        syn::Type::Path(syn::TypePath{path, ..}) => path.segments.last().unwrap().ident.to_string(),
+        _ => String::new(),
+    }
+}
+
+// The DESCRIPTOR of an enum field, None for a type parameter.
+fn enum_descriptor(ty: &syn::Type, generics: &syn::Generics) -> TokenStream {
+    if is_type_param(ty, generics) {
+        quote!(None)
+    } else {
+        quote!(Some(|| <#ty>::DESCRIPTOR))
+    }
+}
+
+fn field_descriptor(name: &str, number: u32, proto_type: &str, label: TokenStream, message: TokenStream, enumeration: TokenStream, type_name: String) -> TokenStream {
+    let proto_type = proto_type_variant(proto_type);
+    quote!{
+        ::twpb::reflect::FieldDescriptor {
+            name: #name,
+            number: #number,
+            proto_type: #proto_type,
+            label: ::twpb::reflect::Label::#label,
+            message: #message,
+            enumeration: #enumeration,
+            type_name: #type_name,
+        }
+    }
+}
+
+// The DESCRIPTOR of a message, its oneofs and flattened messages refer to their own.
+pub fn descriptor_message(struct_name: &syn::Ident, proto_name: &str, package: &str, generics: &syn::Generics, fields: &[ParsedField]) -> syn::parse::Result<TokenStream> {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut descriptors = vec![];
+    let mut oneofs = vec![];
+    let mut flattened = vec![];
+    for field in fields {
+        let number = field.field_numbers.first().copied().unwrap_or_default();
+        if field.proto_type == "flatten" {
+            flattened.push(&field.field_type);
+        } else if field.proto_type == "oneof" {
+            let optionarg = option_inner(&field.field_type)
+                .ok_or_else(|| syn::Error::new_spanned(&field.field_type, "oneof fields must be wrapped in an Option"))?;
+            let name = &field.proto_name;
+            oneofs.push(quote!((#name, <#optionarg>::DESCRIPTOR)));
+        } else if field.proto_type == "message" {
+            let optionarg = option_inner(&field.field_type)
+                .ok_or_else(|| syn::Error::new_spanned(&field.field_type, "message fields must be wrapped in an Option"))?;
+            let message = message_type(optionarg);
+            let name = type_name(message);
+            let message = if is_type_param(message, generics) {
+                quote!(None)
+            } else {
+                quote!(Some(|| <#message>::DESCRIPTOR))
+            };
+            descriptors.push(field_descriptor(&field.proto_name, number, "message", quote!(Singular), message, quote!(None), name));
+        } else if field.proto_type == "enum" {
+            let label = if field.repeated { quote!(Repeated) } else { quote!(Singular) };
+            let enum_type = value_type(&field.field_type, field.repeated);
+            descriptors.push(field_descriptor(&field.proto_name, number, "enum", label, quote!(None),
+                enum_descriptor(enum_type, generics), type_name(enum_type)));
+        } else {
+            let label = if field.repeated { quote!(Repeated) } else { quote!(Singular) };
+            descriptors.push(field_descriptor(&field.proto_name, number, &field.proto_type, label, quote!(None), quote!(None), String::new()));
+        }
+    }
+
+    Ok(quote!{
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            // The fields of this message, see `::twpb::reflect`.
+            pub const DESCRIPTOR: &'static ::twpb::reflect::MessageDescriptor = &::twpb::reflect::MessageDescriptor {
+                name: #proto_name,
+                package: #package,
+                fields: &[#(#descriptors),*],
+                oneofs: &[#(#oneofs),*],
+                flattened: &[#(<#flattened>::DESCRIPTOR),*],
+            };
+        }
+    })
+}
+
+// The DESCRIPTOR of a oneof, nested oneofs refer to their own.
+pub fn descriptor_enum(struct_name: &syn::Ident, generics: &syn::Generics, variants: &[ParsedVariant]) -> TokenStream {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let name = struct_name.to_string();
+
+    let mut descriptors = vec![];
+    let mut nested = vec![];
+    for variant in variants {
+        let number = variant.field_numbers.first().copied().unwrap_or_default();
+        let field_type = &variant.field_type;
+        if variant.proto_type == "oneof" {
+            nested.push(field_type);
+        } else if variant.proto_type == "message" {
+            let message = if variant.unit {
+                quote!(Some(|| &::twpb::reflect::EMPTY))
+            } else if is_type_param(field_type, generics) {
+                quote!(None)
+            } else {
+                quote!(Some(|| <#field_type>::DESCRIPTOR))
+            };
+            let name = if variant.unit { String::new() } else { type_name(field_type) };
+            descriptors.push(field_descriptor(&variant.proto_name, number, "message", quote!(Oneof), message, quote!(None), name));
+        } else if variant.proto_type == "enum" {
+            descriptors.push(field_descriptor(&variant.proto_name, number, "enum", quote!(Oneof), quote!(None),
+                enum_descriptor(field_type, generics), type_name(field_type)));
+        } else {
+            descriptors.push(field_descriptor(&variant.proto_name, number, &variant.proto_type, quote!(Oneof), quote!(None), quote!(None), String::new()));
+        }
+    }
+
+    quote!{
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            // The variants of this oneof, see `::twpb::reflect`.
+            pub const DESCRIPTOR: &'static ::twpb::reflect::OneofDescriptor = &::twpb::reflect::OneofDescriptor {
+                name: #name,
+                fields: &[#(#descriptors),*],
+                nested: &[#(<#nested>::DESCRIPTOR),*],
+            };
+        }
+    }
+}
+
+// The DESCRIPTOR of a proto enum, its values are those of `EnumNames`.
+pub fn descriptor_proto_enum(enum_name: &syn::Ident, proto_name: &str, package: &str) -> TokenStream {
+    quote!{
+        impl #enum_name {
+            // The values of this enum, see `::twpb::reflect`.
+            pub const DESCRIPTOR: &'static ::twpb::reflect::EnumDescriptor = &::twpb::reflect::EnumDescriptor {
+                name: #proto_name,
+                package: #package,
+                values: <#enum_name as ::twpb::json::EnumNames>::NAMES,
+            };
+        }
+    }
+}
+
+// The code of every Reflect method for one field, in the order of the trait.
+#[derive(Default)]
+struct Methods {
+    get: TokenStream,
+    set: TokenStream,
+    get_mut: TokenStream,
+    repeated_len: TokenStream,
+    get_index: TokenStream,
+    set_index: TokenStream,
+    truncate: TokenStream,
+    clear: TokenStream,
+}
+
+// Reflect for a #[twpb(reflect)] message. Messages of a type parameter must implement Reflect too.
+pub fn derive_message(struct_name: &syn::Ident, generics: &syn::Generics, fields: &[ParsedField]) -> syn::parse::Result<TokenStream> {
+    let mut impl_generics_source = generics.clone();
+    let mut code = Methods::default();
+    for field in fields {
+        let field_name = &field.field_name;
+        let field_type = &field.field_type;
+        let name = &field.proto_name;
+        let number = field.field_numbers.first().copied().unwrap_or_default();
+
+        if field.proto_type == "flatten" {
+            // the fields of a flattened message are fields of this one
+            let check = quote!(<#field_type>::TWPB_FIELD_NUMBERS.contains(number));
+            code.get.extend(quote!{
+                if #check {
+                    return ::twpb::Reflect::get(&self.#field_name, number);
+                }
+            });
+            code.set.extend(quote!{
+                if #check {
+                    return ::twpb::Reflect::set(&mut self.#field_name, number, value);
+                }
+            });
+            code.get_mut.extend(quote!{
+                if #check {
+                    return ::twpb::Reflect::get_mut(&mut self.#field_name, number);
+                }
+            });
+            code.repeated_len.extend(quote!{
+                if #check {
+                    return ::twpb::Reflect::repeated_len(&self.#field_name, number);
+                }
+            });
+            code.get_index.extend(quote!{
+                if #check {
+                    return ::twpb::Reflect::get_index(&self.#field_name, number, index);
+                }
+            });
+            code.set_index.extend(quote!{
+                if #check {
+                    return ::twpb::Reflect::set_index(&mut self.#field_name, number, index, value);
+                }
+            });
+            code.truncate.extend(quote!{
+                if #check {
+                    return ::twpb::Reflect::truncate(&mut self.#field_name, number, len);
+                }
+            });
+            code.clear.extend(quote!{
+                if #check {
+                    return ::twpb::Reflect::clear(&mut self.#field_name, number);
+                }
+            });
+
+        } else if field.proto_type == "oneof" {
+            let optionarg = option_inner(field_type)
+                .ok_or_else(|| syn::Error::new_spanned(field_type, "oneof fields must be wrapped in an Option"))?;
+            let check = quote!(<#optionarg>::TWPB_FIELD_NUMBERS.contains(number));
+            code.get.extend(quote!{
+                if #check {
+                    return <#optionarg>::twpb_reflect_get(self.#field_name.as_ref(), number);
+                }
+            });
+            code.set.extend(quote!{
+                if #check {
+                    return <#optionarg>::twpb_reflect_set(&mut self.#field_name, number, value);
+                }
+            });
+            code.get_mut.extend(quote!{
+                if #check {
+                    return <#optionarg>::twpb_reflect_get_mut(&mut self.#field_name, number);
+                }
+            });
+            code.clear.extend(quote!{
+                if #check {
+                    if <#optionarg>::twpb_reflect_get(self.#field_name.as_ref(), number).is_some() {
+                        self.#field_name = None;
+                    }
+                    return Ok(());
+                }
+            });
+
+        } else if field.proto_type == "message" {
+            let optionarg = option_inner(field_type)
+                .ok_or_else(|| syn::Error::new_spanned(field_type, "message fields must be wrapped in an Option"))?;
+            let message = message_type(optionarg);
+            if is_type_param(message, generics) {
+                impl_generics_source.make_where_clause().predicates
+                    .push(syn::parse_quote!(#message: ::twpb::Reflect));
+            }
+            let (new_value, as_message, as_message_mut) = if is_box(optionarg) {
+                (quote!(<#optionarg as ::twpb::MessageBox>::new_box(::core::default::Default::default())?),
+                    quote!(::core::ops::Deref::deref(value)),
+                    quote!(::core::ops::DerefMut::deref_mut(value)))
+            } else {
+                (quote!(::core::default::Default::default()), quote!(value), quote!(value))
+            };
+            code.get.extend(quote!{
+                if number == #number {
+                    return self.#field_name.as_ref().map(|value| ::twpb::reflect::Value::Message(#as_message));
+                }
+            });
+            code.set.extend(quote!{
+                if number == #number {
+                    return Err(::twpb::reflect::ReflectError::TypeMismatch(#name));
+                }
+            });
+            code.get_mut.extend(quote!{
+                if number == #number {
+                    if self.#field_name.is_none() {
+                        self.#field_name = Some(#new_value);
+                    }
+                    return self.#field_name.as_mut().map(|value| #as_message_mut as &mut dyn ::twpb::Reflect);
+                }
+            });
+            code.clear.extend(quote!{
+                if number == #number {
+                    self.#field_name = None;
+                    return Ok(());
+                }
+            });
+
+        } else {
+            let (to_value, from_value) = if let Some(with) = &field.with {
+                // the module must also provide to_value and from_value
+                (quote!(#with::to_value), quote!(#with::from_value))
+            } else {
+                let codec = field.codec.as_ref()
+                    .ok_or_else(|| syn::Error::new(field.attr_span, format!("no codec for proto type '{}'", field.proto_type)))?;
+                if field.repeated {
+                    (quote!(<#codec as ::twpb::reflect::ReflectCodec<_>>::to_value),
+                        quote!(<#codec as ::twpb::reflect::ReflectCodec<_>>::from_value))
+                } else {
+                    (quote!(<#codec as ::twpb::reflect::ReflectCodec<#field_type>>::to_value),
+                        quote!(<#codec as ::twpb::reflect::ReflectCodec<#field_type>>::from_value))
+                }
+            };
+
+            if field.repeated {
+                code.repeated_len.extend(quote!{
+                    if number == #number {
+                        return self.#field_name.len();
+                    }
+                });
+                code.get_index.extend(quote!{
+                    if number == #number {
+                        return self.#field_name.get(index).map(|value| #to_value(value));
+                    }
+                });
+                if let syn::Type::Array(_) = field_type {
+                    // fixed size arrays always hold all their values
+                    code.set_index.extend(quote!{
+                        if number == #number {
+                            let slot = self.#field_name.get_mut(index)
+                                .ok_or(::twpb::reflect::ReflectError::IndexOutOfRange(#name))?;
+                            *slot = #from_value(value, #name)?;
+                            return Ok(());
+                        }
+                    });
+                    code.truncate.extend(quote!{
+                        if number == #number {
+                            if len < self.#field_name.len() {
+                                return Err(::twpb::reflect::ReflectError::FixedSize(#name));
+                            }
+                            return Ok(());
+                        }
+                    });
+                    code.clear.extend(quote!{
+                        if number == #number {
+                            self.#field_name = ::core::default::Default::default();
+                            return Ok(());
+                        }
+                    });
+                } else {
+                    code.set_index.extend(quote!{
+                        if number == #number {
+                            let value = #from_value(value, #name)?;
+                            return ::twpb::reflect::set_index(&mut self.#field_name, index, value, #name);
+                        }
+                    });
+                    code.truncate.extend(quote!{
+                        if number == #number {
+                            self.#field_name.truncate(len);
+                            return Ok(());
+                        }
+                    });
+                    code.clear.extend(quote!{
+                        if number == #number {
+                            self.#field_name.clear();
+                            return Ok(());
+                        }
+                    });
+                }
+            } else {
+                code.get.extend(quote!{
+                    if number == #number {
+                        return Some(#to_value(&self.#field_name));
+                    }
+                });
+                code.set.extend(quote!{
+                    if number == #number {
+                        self.#field_name = #from_value(value, #name)?;
+                        return Ok(());
+                    }
+                });
+                code.clear.extend(quote!{
+                    if number == #number {
+                        self.#field_name = ::core::default::Default::default();
+                        return Ok(());
+                    }
+                });
+            }
+        }
+    }
+
+    let Methods { get, set, get_mut, repeated_len, get_index, set_index, truncate, clear } = code;
+    let (impl_generics, _, where_clause) = impl_generics_source.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    Ok(quote!{
+        impl #impl_generics ::twpb::Reflect for #struct_name #ty_generics #where_clause {
+            fn descriptor(&self) -> &'static ::twpb::reflect::MessageDescriptor {
+                Self::DESCRIPTOR
+            }
+
+            fn get(&self, number: u32) -> Option<::twpb::reflect::Value<'_>> {
+                #get
+                None
+            }
+
+            fn set(&mut self, number: u32, value: ::twpb::reflect::Value) -> Result<(), ::twpb::reflect::ReflectError> {
+                #set
+                let _ = value;
+                Err(::twpb::reflect::ReflectError::UnknownField(number))
+            }
+
+            fn get_mut(&mut self, number: u32) -> Option<&mut dyn ::twpb::Reflect> {
+                #get_mut
+                None
+            }
+
+            fn repeated_len(&self, number: u32) -> usize {
+                #repeated_len
+                0
+            }
+
+            fn get_index(&self, number: u32, index: usize) -> Option<::twpb::reflect::Value<'_>> {
+                #get_index
+                let _ = index;
+                None
+            }
+
+            fn set_index(&mut self, number: u32, index: usize, value: ::twpb::reflect::Value) -> Result<(), ::twpb::reflect::ReflectError> {
+                #set_index
+                let _ = (index, value);
+                Err(::twpb::reflect::ReflectError::UnknownField(number))
+            }
+
+            fn truncate(&mut self, number: u32, len: usize) -> Result<(), ::twpb::reflect::ReflectError> {
+                #truncate
+                let _ = len;
+                Err(::twpb::reflect::ReflectError::UnknownField(number))
+            }
+
+            fn clear(&mut self, number: u32) -> Result<(), ::twpb::reflect::ReflectError> {
+                #clear
+                Err(::twpb::reflect::ReflectError::UnknownField(number))
+            }
+        }
+    })
+}
+
+// Reflect for a oneof enum. Its variants are fields of the message containing it,
+// so instead of the trait it gets inherent functions working on that field.
+pub fn derive_enum(struct_name: &syn::Ident, generics: &syn::Generics, variants: &[ParsedVariant]) -> syn::parse::Result<TokenStream> {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut getcode = quote!();
+    let mut setcode = quote!();
+    let mut defaultcode = quote!();
+    let mut messagemutcode = quote!();
+    for variant in variants {
+        let field_name = &variant.field_name;
+        let field_type = &variant.field_type;
+        let name = &variant.proto_name;
+        let number = variant.field_numbers.first().copied().unwrap_or_default();
+
+        if variant.proto_type == "oneof" {
+            // a nested oneof is flattened into this one
+            let check = quote!(<#field_type>::TWPB_FIELD_NUMBERS.contains(number));
+            getcode.extend(quote!{
+                Some(#struct_name::#field_name(c)) if #check => <#field_type>::twpb_reflect_get(Some(c), number),
+            });
+            setcode.extend(quote!{
+                if #check {
+                    let (mut nested, previous) = match value.take() {
+                        Some(#struct_name::#field_name(c)) => (Some(c), None),
+                        other => (None, other),
+                    };
+                    let result = <#field_type>::twpb_reflect_set(&mut nested, number, new_value);
+                    // on failure the previous variant stays selected
+                    *value = match result {
+                        Ok(()) => nested.map(#struct_name::#field_name),
+                        Err(_) => previous.or_else(|| nested.map(#struct_name::#field_name)),
+                    };
+                    return result;
+                }
+            });
+            defaultcode.extend(quote!{
+                if #check {
+                    return <#field_type>::twpb_reflect_default(number).map(#struct_name::#field_name);
+                }
+            });
+            messagemutcode.extend(quote!{
+                #struct_name::#field_name(c) => c.twpb_reflect_message_mut(number),
+            });
+
+        } else if variant.proto_type == "message" && variant.unit {
+            getcode.extend(quote!{
+                Some(#struct_name::#field_name) if number == #number => Some(::twpb::reflect::Value::Message(&())),
+            });
+            setcode.extend(quote!{
+                if number == #number {
+                    if let ::twpb::reflect::Value::Message(_) = new_value {
+                        *value = Some(#struct_name::#field_name);
+                        return Ok(());
+                    }
+                    return Err(::twpb::reflect::ReflectError::TypeMismatch(#name));
+                }
+            });
+
+        } else if variant.proto_type == "message" {
+            getcode.extend(quote!{
+                Some(#struct_name::#field_name(c)) if number == #number => Some(::twpb::reflect::Value::Message(c)),
+            });
+            setcode.extend(quote!{
+                if number == #number {
+                    return Err(::twpb::reflect::ReflectError::TypeMismatch(#name));
+                }
+            });
+            defaultcode.extend(quote!{
+                if number == #number {
+                    return Some(#struct_name::#field_name(::core::default::Default::default()));
+                }
+            });
+            messagemutcode.extend(quote!{
+                #struct_name::#field_name(c) if number == #number => Some(c),
+            });
+
+        } else {
+            let (to_value, from_value) = if let Some(with) = &variant.with {
+                (quote!(#with::to_value), quote!(#with::from_value))
+            } else {
+                let codec = variant.codec.as_ref()
+                    .ok_or_else(|| syn::Error::new(variant.attr_span, format!("no codec for proto type '{}'", variant.proto_type)))?;
+                (quote!(<#codec as ::twpb::reflect::ReflectCodec<#field_type>>::to_value),
+                    quote!(<#codec as ::twpb::reflect::ReflectCodec<#field_type>>::from_value))
+            };
+            getcode.extend(quote!{
+                Some(#struct_name::#field_name(c)) if number == #number => Some(#to_value(c)),
+            });
+            setcode.extend(quote!{
+                if number == #number {
+                    *value = Some(#struct_name::#field_name(#from_value(new_value, #name)?));
+                    return Ok(());
+                }
+            });
+        }
+    }
+
+    Ok(quote!{
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            // The value of variant `number` if it's the selected one, see `Reflect::get`.
+            pub fn twpb_reflect_get(value: Option<&Self>, number: u32) -> Option<::twpb::reflect::Value<'_>> {
+                match value {
+                    #getcode
+                    _ => None,
+                }
+            }
+
+            // Select variant `number`, see `Reflect::set`.
+            pub fn twpb_reflect_set(value: &mut Option<Self>, number: u32, new_value: ::twpb::reflect::Value) -> Result<(), ::twpb::reflect::ReflectError> {
+                #setcode
+                let _ = (value, new_value);
+                Err(::twpb::reflect::ReflectError::UnknownField(number))
+            }
+
+            // Select message variant `number` to change it, see `Reflect::get_mut`.
+            pub fn twpb_reflect_get_mut(value: &mut Option<Self>, number: u32) -> Option<&mut dyn ::twpb::Reflect> {
+                let selected = value.as_mut().and_then(|v| v.twpb_reflect_message_mut(number)).is_some();
+                if !selected {
+                    *value = Some(Self::twpb_reflect_default(number)?);
+                }
+                value.as_mut()?.twpb_reflect_message_mut(number)
+            }
+
+            // Message variant `number` at its default value.
+            pub fn twpb_reflect_default(number: u32) -> Option<Self> {
+                #defaultcode
+                None
+            }
+
+            // Message variant `number` if it's the selected one.
+            pub fn twpb_reflect_message_mut(&mut self, number: u32) -> Option<&mut dyn ::twpb::Reflect> {
+                match self {
+                    #messagemutcode
+                    _ => None,
+                }
+            }
+        }
+    })
+}
+
+// ReflectCodec for a #[twpb(transparent, reflect)] newtype, as the value of its inner scalar.
+pub fn derive_transparent(struct_name: &syn::Ident, generics: &syn::Generics, field: &ParsedField) -> TokenStream {
+    let member = &field.field_name;
+    let inner_type = &field.field_type;
+
+    let mut impl_generics_source = generics.clone();
+    let (codec_path, to_value, from_value) = if let Some(with) = &field.with {
+        // parsing the field checked there is a marker for the proto type
+        let codec = crate::codecs::for_proto_type(&field.proto_type).unwrap();
+        (codec, quote!(#with::to_value(&value.#member)), quote!(#with::from_value(value, field_name)?))
+    } else {
+        let codec = field.codec.clone().unwrap();
+        impl_generics_source.make_where_clause().predicates
+            .push(syn::parse_quote!(#codec: ::twpb::reflect::ReflectCodec<#inner_type>));
+        (codec.clone(),
+            quote!(<#codec as ::twpb::reflect::ReflectCodec<#inner_type>>::to_value(&value.#member)),
+            quote!(<#codec as ::twpb::reflect::ReflectCodec<#inner_type>>::from_value(value, field_name)?))
+    };
+
+    let (impl_generics, _, where_clause) = impl_generics_source.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    quote!{
+        impl #impl_generics ::twpb::reflect::ReflectCodec<#struct_name #ty_generics> for #codec_path #where_clause {
+            fn to_value(value: &#struct_name #ty_generics) -> ::twpb::reflect::Value<'_> {
+                #to_value
+            }
+
+            fn from_value(value: ::twpb::reflect::Value, field_name: &'static str) -> Result<#struct_name #ty_generics, ::twpb::reflect::ReflectError> {
+                Ok(#struct_name { #member: #from_value })
+            }
+        }
+    }
+}
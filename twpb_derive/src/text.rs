@@ -0,0 +1,342 @@
+// Code generation for the protobuf text format of #[twpb(text)] messages and oneofs,
+// see `twpb::text` for the runtime side.
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::types::*;
+
+// Write a repeated field as list `name: [a, b]`, `encode` writes the value `val`.
+fn encode_list(field_name: &syn::Member, name: &str, encode: TokenStream) -> TokenStream {
+    quote!{
+        if !self.#field_name.is_empty() {
+            bytes_written += ::twpb::text::field(buffer, first, #name)?;
+            bytes_written += ::twpb::Writer::write_all(buffer, b"[")?;
+            for (i, val) in self.#field_name.iter().enumerate() {
+                if i > 0 {
+                    bytes_written += ::twpb::Writer::write_all(buffer, b", ")?;
+                }
+                bytes_written += #encode;
+            }
+            bytes_written += ::twpb::Writer::write_all(buffer, b"]")?;
+        }
+    }
+}
+
+// Read a list or a single value into a repeated field, `decode` reads one value from `parser`.
+fn decode_list(field: &ParsedField, decode: TokenStream) -> TokenStream {
+    let field_name = &field.field_name;
+    let name = &field.proto_name;
+    if let syn::Type::Array(_) = field.field_type {
+        // fixed size arrays must receive exactly as many values as they can hold, in one list
+        quote!{
+            parser.colon()?;
+            let mut len = 0;
+            parser.list(|parser| {
+                let slot = self.#field_name.get_mut(len)
+                    .ok_or(::twpb::decoder::DecodeError::LengthMismatch(#name))?;
+                *slot = #decode;
+                len += 1;
+                Ok(())
+            })?;
+            if len != self.#field_name.len() {
+                return Err(::twpb::decoder::DecodeError::LengthMismatch(#name));
+            }
+        }
+    } else {
+        // every occurrence of the field adds to it
+        quote!{
+            parser.colon()?;
+            parser.list(|parser| {
+                let value = #decode;
+                self.#field_name.push(value).map_err(|_| ::twpb::decoder::DecodeError::FieldOverflow(#name))
+            })?;
+        }
+    }
+}
+
+// TextEncoder and TextDecoder for a message. `after_decode` is the hook call of the binary decoder.
+pub fn derive_message(struct_name: &syn::Ident, generics: &syn::Generics, fields: &[ParsedField],
+    after_decode: &Option<TokenStream>) -> syn::parse::Result<TokenStream> {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut encodecode = quote!();
+    let mut decodecode = quote!();
+    for field in fields {
+        let field_name = &field.field_name;
+        let field_type = &field.field_type;
+        let name = &field.proto_name;
+
+        if field.proto_type == "flatten" {
+            encodecode.extend(quote!{
+                bytes_written += ::twpb::TextEncoder::twpb_encode_text_fields(&self.#field_name, buffer, first)?;
+            });
+            decodecode.extend(quote!{
+                if ::twpb::TextDecoder::twpb_decode_text_field(&mut self.#field_name, name, parser, recursion_limit)? {
+                    return Ok(true);
+                }
+            });
+
+        } else if field.proto_type == "oneof" {
+            let optionarg = option_inner(field_type)
+                .ok_or_else(|| syn::Error::new_spanned(field_type, "oneof fields must be wrapped in an Option"))?;
+            encodecode.extend(quote!{
+                if let Some(value) = self.#field_name.as_ref() {
+                    bytes_written += <#optionarg>::twpb_encode_text_fields(value, buffer, first)?;
+                }
+            });
+            decodecode.extend(quote!{
+                if <#optionarg>::twpb_decode_text_field(name, parser, recursion_limit, &mut self.#field_name)? {
+                    return Ok(true);
+                }
+            });
+
+        } else if field.proto_type == "message" {
+            let optionarg = option_inner(field_type)
+                .ok_or_else(|| syn::Error::new_spanned(field_type, "message fields must be wrapped in an Option"))?;
+            let (decode_value, as_message) = if is_box(optionarg) {
+                (quote!{{
+                    let value = parser.message::<<#optionarg as ::core::ops::Deref>::Target>(recursion_limit)?;
+                    <#optionarg as ::twpb::MessageBox>::new_box(value)
+                        .ok_or(::twpb::decoder::DecodeError::AllocationFailed(#name))?
+                }}, quote!(::core::ops::Deref::deref(value)))
+            } else {
+                (quote!(parser.message::<#optionarg>(recursion_limit)?), quote!(value))
+            };
+            encodecode.extend(quote!{
+                if let Some(value) = self.#field_name.as_ref() {
+                    bytes_written += ::twpb::text::message(buffer, first, #name, #as_message)?;
+                }
+            });
+            decodecode.extend(quote!{
+                if name == #name {
+                    parser.optional_colon();
+                    self.#field_name = Some(#decode_value);
+                    return Ok(true);
+                }
+            });
+
+        } else if let Some(with) = &field.with {
+            // the module must also provide encode_text, decode_text and is_default
+            if field.repeated {
+                encodecode.extend(encode_list(field_name, name, quote!(#with::encode_text(buffer, val)?)));
+                let decode = decode_list(field, quote!(#with::decode_text(parser, #name)?));
+                decodecode.extend(quote!{
+                    if name == #name {
+                        #decode
+                        return Ok(true);
+                    }
+                });
+            } else {
+                encodecode.extend(quote!{
+                    if !#with::is_default(&self.#field_name) {
+                        bytes_written += ::twpb::text::field(buffer, first, #name)?;
+                        bytes_written += #with::encode_text(buffer, &self.#field_name)?;
+                    }
+                });
+                decodecode.extend(quote!{
+                    if name == #name {
+                        parser.colon()?;
+                        self.#field_name = #with::decode_text(parser, #name)?;
+                        return Ok(true);
+                    }
+                });
+            }
+
+        } else {
+            let codec = field.codec.as_ref()
+                .ok_or_else(|| syn::Error::new(field.attr_span, format!("no codec for proto type '{}'", field.proto_type)))?;
+            if field.repeated {
+                encodecode.extend(encode_list(field_name, name,
+                    quote!(<#codec as ::twpb::text::TextCodec<_>>::encode_text(buffer, val)?)));
+                let decode = decode_list(field, quote!(<#codec as ::twpb::text::TextCodec<_>>::decode_text(parser, #name)?));
+                decodecode.extend(quote!{
+                    if name == #name {
+                        #decode
+                        return Ok(true);
+                    }
+                });
+            } else {
+                encodecode.extend(quote!{
+                    if !<#codec as ::twpb::text::TextCodec<#field_type>>::is_default(&self.#field_name) {
+                        bytes_written += ::twpb::text::field(buffer, first, #name)?;
+                        bytes_written += <#codec as ::twpb::text::TextCodec<#field_type>>::encode_text(buffer, &self.#field_name)?;
+                    }
+                });
+                decodecode.extend(quote!{
+                    if name == #name {
+                        parser.colon()?;
+                        self.#field_name = <#codec as ::twpb::text::TextCodec<#field_type>>::decode_text(parser, #name)?;
+                        return Ok(true);
+                    }
+                });
+            }
+        }
+    }
+
+    Ok(quote!{
+        impl #impl_generics ::twpb::TextEncoder for #struct_name #ty_generics #where_clause {
+            fn twpb_encode_text_fields(&self, buffer: &mut impl ::twpb::traits::Writer, first: &mut bool) -> Result<usize, ::twpb::traits::WriterError> {
+                let mut bytes_written = 0;
+                #encodecode
+                Ok(bytes_written)
+            }
+        }
+        impl #impl_generics ::twpb::TextDecoder for #struct_name #ty_generics #where_clause {
+            fn twpb_decode_text_message(parser: &mut ::twpb::text::TextParser, recursion_limit: u32, close: Option<u8>) -> Result<Self, ::twpb::decoder::DecodeError> {
+                let mut result = <Self as ::core::default::Default>::default();
+                parser.fields(close, |parser, name| {
+                    if !::twpb::TextDecoder::twpb_decode_text_field(&mut result, name, parser, recursion_limit)? {
+                        return Err(parser.unknown_field(name));
+                    }
+                    Ok(())
+                })?;
+
+                #after_decode
+
+                Ok(result)
+            }
+
+            fn twpb_decode_text_field(&mut self, name: &str, parser: &mut ::twpb::text::TextParser, recursion_limit: u32) -> Result<bool, ::twpb::decoder::DecodeError> {
+                #decodecode
+                Ok(false)
+            }
+        }
+    })
+}
+
+// Text format for a oneof enum. Its variants are fields of the message containing it,
+// so instead of TextEncoder and TextDecoder it gets inherent functions working on fields.
+pub fn derive_enum(struct_name: &syn::Ident, generics: &syn::Generics, variants: &[ParsedVariant]) -> syn::parse::Result<TokenStream> {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let mut encodecode = quote!();
+    let mut decodecode = quote!();
+    for variant in variants {
+        let field_name = &variant.field_name;
+        let field_type = &variant.field_type;
+        let name = &variant.proto_name;
+        let error_name = quote!(concat!(stringify!(#struct_name), "::", stringify!(#field_name)));
+
+        // the selected variant is always written, even at its default value
+        if variant.proto_type == "oneof" {
+            encodecode.extend(quote!{
+                #struct_name::#field_name(c) => {
+                    bytes_written += <#field_type>::twpb_encode_text_fields(c, buffer, first)?;
+                },
+            });
+            decodecode.extend(quote!{
+                let mut nested = None;
+                if <#field_type>::twpb_decode_text_field(name, parser, recursion_limit, &mut nested)? {
+                    *value = nested.map(#struct_name::#field_name);
+                    return Ok(true);
+                }
+            });
+        } else if variant.proto_type == "message" {
+            // an empty message without a Rust type is written as `name {}`
+            let (pattern, encode, decode) = if variant.unit {
+                (quote!(#struct_name::#field_name), quote!(&()), quote!{{
+                    parser.message::<()>(recursion_limit)?;
+                    #struct_name::#field_name
+                }})
+            } else {
+                (quote!(#struct_name::#field_name(c)), quote!(c),
+                    quote!(#struct_name::#field_name(parser.message::<#field_type>(recursion_limit)?)))
+            };
+            encodecode.extend(quote!{
+                #pattern => {
+                    bytes_written += ::twpb::text::message(buffer, first, #name, #encode)?;
+                },
+            });
+            decodecode.extend(quote!{
+                if name == #name {
+                    parser.optional_colon();
+                    *value = Some(#decode);
+                    return Ok(true);
+                }
+            });
+        } else {
+            let (encode, decode) = if let Some(with) = &variant.with {
+                (quote!(#with::encode_text(buffer, c)?), quote!(#with::decode_text(parser, #error_name)?))
+            } else {
+                let codec = variant.codec.as_ref()
+                    .ok_or_else(|| syn::Error::new(variant.attr_span, format!("no codec for proto type '{}'", variant.proto_type)))?;
+                (quote!(<#codec as ::twpb::text::TextCodec<#field_type>>::encode_text(buffer, c)?),
+                    quote!(<#codec as ::twpb::text::TextCodec<#field_type>>::decode_text(parser, #error_name)?))
+            };
+            encodecode.extend(quote!{
+                #struct_name::#field_name(c) => {
+                    bytes_written += ::twpb::text::field(buffer, first, #name)?;
+                    bytes_written += #encode;
+                },
+            });
+            decodecode.extend(quote!{
+                if name == #name {
+                    parser.colon()?;
+                    *value = Some(#struct_name::#field_name(#decode));
+                    return Ok(true);
+                }
+            });
+        }
+    }
+
+    Ok(quote!{
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            // Write the selected variant as field of a message, see `TextEncoder::twpb_encode_text_fields`.
+            pub fn twpb_encode_text_fields(&self, buffer: &mut impl ::twpb::traits::Writer, first: &mut bool) -> Result<usize, ::twpb::traits::WriterError> {
+                let mut bytes_written = 0;
+                match &self {
+                    #encodecode
+                };
+                Ok(bytes_written)
+            }
+
+            // Read field `name` into `value` if it's one of the variants.
+            pub fn twpb_decode_text_field(name: &str, parser: &mut ::twpb::text::TextParser, recursion_limit: u32, value: &mut Option<Self>) -> Result<bool, ::twpb::decoder::DecodeError> {
+                #decodecode
+                Ok(false)
+            }
+        }
+    })
+}
+
+// TextCodec for a #[twpb(transparent, text)] newtype, in the text format of its inner scalar.
+pub fn derive_transparent(struct_name: &syn::Ident, generics: &syn::Generics, field: &ParsedField) -> TokenStream {
+    let member = &field.field_name;
+    let inner_type = &field.field_type;
+
+    let mut impl_generics_source = generics.clone();
+    let (codec_path, encode, decode, is_default) = if let Some(with) = &field.with {
+        // parsing the field checked there is a marker for the proto type
+        let codec = crate::codecs::for_proto_type(&field.proto_type).unwrap();
+        (codec,
+            quote!(#with::encode_text(buffer, &value.#member)),
+            quote!(#with::decode_text(parser, field_name)?),
+            quote!(#with::is_default(&value.#member)))
+    } else {
+        let codec = field.codec.clone().unwrap();
+        impl_generics_source.make_where_clause().predicates
+            .push(syn::parse_quote!(#codec: ::twpb::text::TextCodec<#inner_type>));
+        (codec.clone(),
+            quote!(<#codec as ::twpb::text::TextCodec<#inner_type>>::encode_text(buffer, &value.#member)),
+            quote!(<#codec as ::twpb::text::TextCodec<#inner_type>>::decode_text(parser, field_name)?),
+            quote!(<#codec as ::twpb::text::TextCodec<#inner_type>>::is_default(&value.#member)))
+    };
+
+    let (impl_generics, _, where_clause) = impl_generics_source.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    quote!{
+        impl #impl_generics ::twpb::text::TextCodec<#struct_name #ty_generics> for #codec_path #where_clause {
+            fn encode_text(buffer: &mut impl ::twpb::traits::Writer, value: &#struct_name #ty_generics) -> Result<usize, ::twpb::traits::WriterError> {
+                #encode
+            }
+
+            fn decode_text(parser: &mut ::twpb::text::TextParser, field_name: &'static str) -> Result<#struct_name #ty_generics, ::twpb::decoder::DecodeError> {
+                Ok(#struct_name { #member: #decode })
+            }
+
+            fn is_default(value: &#struct_name #ty_generics) -> bool {
+                #is_default
+            }
+        }
+    }
+}
@@ -1,50 +1,97 @@
+use proc_macro2::Span;
 use quote::ToTokens;
-use syn::{self, Lit, Meta, NestedMeta};
+use syn::{self, spanned::Spanned, Lit, Meta, NestedMeta};
+
+use crate::codecs;
 
 #[derive(Debug)]
 pub struct ParsedField {
-    pub field_name: syn::Ident,
+    // the field name, or its index in a tuple struct
+    pub field_name: syn::Member,
+    // empty for a oneof without explicit nr
     pub field_numbers: Vec<u32>,
+    // the nr = .. value, to point errors at
+    pub nr_span: Span,
     pub field_type: syn::Type,
     pub proto_type: String,
+    // the ScalarCodec marker type, for scalar fields
+    pub codec: Option<syn::Path>,
+    // module with encode/decode/encoded_len functions for a custom Rust type
+    pub with: Option<syn::Path>,
     pub repeated: bool,
+    // #[twpb(skip)]: not part of the message, left at its Default value when decoding
+    pub skip: bool,
+    // the name in the proto file, and its lowerCamelCase JSON name or the json_name override
+    pub proto_name: String,
+    pub json_name: String,
+    // the #[twpb(..)] attribute, to point errors at
+    pub attr_span: Span,
 }
 
 #[derive(Debug)]
 pub struct ParsedVariant {
     pub field_name: syn::Ident,
     pub field_numbers: Vec<u32>,
+    // the nr = .. value, to point errors at
+    pub nr_span: Span,
     pub field_type: syn::Type,
     pub proto_type: String,
+    // the ScalarCodec marker type, for scalar variants
+    pub codec: Option<syn::Path>,
+    // module with encode/decode/encoded_len functions for a custom Rust type
+    pub with: Option<syn::Path>,
+    // a variant without value, standing in for an empty message
+    pub unit: bool,
+    // the name in the proto file, and its lowerCamelCase JSON name or the json_name override
+    pub proto_name: String,
+    pub json_name: String,
+    // the #[twpb(..)] attribute, to point errors at
+    pub attr_span: Span,
 }
 
+// Everything that can be specified in a #[twpb(..)] attribute.
+struct ParsedAttr {
+    attr: syn::Attribute,
+    field_numbers: Vec<u32>,
+    nr_span: Option<Span>,
+    proto_type: String,
+    codec: Option<syn::Path>,
+    with: Option<syn::Path>,
+    repeated: Option<syn::Path>,
+    skip: Option<syn::Path>,
+    json_name: Option<syn::LitStr>,
+    name: Option<syn::LitStr>,
+    span: Span,
+}
 
-impl ParsedVariant {
-    pub fn parse(field: syn::Variant) -> syn::parse::Result<Self> {
-        let field_type = match field.fields {
-            syn::Fields::Unnamed(syn::FieldsUnnamed{unnamed: fields, ..}) => fields.into_iter().nth(0),
-            _ => panic!("no enum variant type {}", field.ident),
-        };
-        let field_type = match field_type {
-            Some(field_type) => field_type.ty,
-            _ => panic!("no enum variant type"),
-        };
-        let mut result = ParsedVariant{
-            field_name: field.ident,
-            field_numbers: vec![0],
-            field_type: field_type,
-            proto_type: "".to_owned(),
-        };
-
-        let twpb_attr: Vec<_> = field.attrs
+impl ParsedAttr {
+    // `owner` is the field or variant carrying the attributes, for error reporting.
+    fn parse(attrs: Vec<syn::Attribute>, owner: &dyn ToTokens) -> syn::parse::Result<Self> {
+        let twpb_attr: Vec<_> = attrs
             .into_iter()
             .filter(|a| a.path.is_ident("twpb"))
             .collect();
 
         let twpb_attr = match twpb_attr.len() {
             1 => &twpb_attr[0],
-            0 => panic!("All fields of a message must specify a #[twpb] attribute, missing for field '{:?}'", result.field_name),
-            n => panic!("A field can specify a #[twpb] attribute only once. Field '{:?}' specified it {} times.", result.field_name, n),
+            0 => return Err(syn::Error::new_spanned(owner,
+                "all fields of a message must specify a #[twpb(..)] attribute")),
+            _ => return Err(syn::Error::new_spanned(&twpb_attr[1],
+                "a field can specify a #[twpb(..)] attribute only once")),
+        };
+
+        let mut result = ParsedAttr{
+            attr: twpb_attr.clone(),
+            field_numbers: vec![],
+            nr_span: None,
+            proto_type: "".to_owned(),
+            codec: None,
+            with: None,
+            repeated: None,
+            skip: None,
+            json_name: None,
+            name: None,
+            span: twpb_attr.path.segments[0].ident.span(),
         };
 
         // start parsing the ?? part of #[twpb(??)]
@@ -52,147 +99,485 @@ impl ParsedVariant {
             Meta::List(l) => l.nested,
             // One can also write other attributes, like '#[twpb = value]'.
             // We don't do that here.
-            _ => panic!("twpb attribute can only be of the form '#[twpb(..)]': {:?}", twpb_attr),
+            other => return Err(syn::Error::new_spanned(other,
+                "twpb attribute can only be of the form `#[twpb(..)]`")),
         };
-        
+
         for meta in metas {
             match meta {
                 // parse the field number
                 NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("nr") => {
-                    if let Lit::Int(li) = &nv.lit {
-                        result.field_numbers = vec![li.base10_parse::<u32>()?];
-                    } else if let Lit::Str(ls) = &nv.lit {
-                        let numbers: Vec<u32> = ls.value().split(";")
-                        .map(|s| {
-                            if let Some(div) = s.find('-') {
-                                let left = &s[..div];
-                                let right = &s[div+1..];
-                                let left = left.parse::<u32>().unwrap_or_else(|_| panic!("nr range must be formatted as `^[0-9]+-[0-9]+$`, got '{}'", s));
-                                let right = right.parse::<u32>().unwrap_or_else(|_| panic!("nr range must be formatted as `^[0-9]+-[0-9]+$`, got '{}'", s));
-                                return (left..=right).collect::<Vec<_>>();
-                            } else {
-                                let s = s.parse::<u32>().unwrap_or_else(|_| panic!("invalid number, got '{}'", s));
-                                return vec![s];
-                            }
-                        })
-                        .flatten().collect();
-                        result.field_numbers = numbers;
-                    } else {
-                        panic!("nr must specify a number");
+                    result.nr_span = Some(nv.lit.span());
+                    result.field_numbers = match &nv.lit {
+                        Lit::Int(li) => vec![li.base10_parse::<u32>()?],
+                        Lit::Str(ls) => parse_field_numbers(ls)?,
+                        lit => return Err(syn::Error::new_spanned(lit,
+                            "nr must specify a number, e.g. `nr=1`, or a string of numbers and ranges, e.g. `nr=\"1-3;5\"`")),
+                    };
+                }
+
+                // a module adapting a Rust type to the wire type of the proto type,
+                // e.g. #[twpb(bytes, with = "mac_address", nr=1)]
+                // The module must provide the functions
+                //   encode(&mut impl Writer, &T) -> Result<usize, WriterError>
+                //   decode<I: Iterator<Item = u8>>(I, &'static str) -> Result<T, DecodeError>
+                //   encoded_len(&T) -> usize
+                // and, for the options of the message,
+                //   json: encode_json(&mut impl Writer, &T) -> Result<usize, WriterError>
+                //         decode_json(&mut JsonParser, &'static str) -> Result<T, DecodeError>
+                //         is_default(&T) -> bool
+                //   text: encode_text(&mut impl Writer, &T) -> Result<usize, WriterError>
+                //         decode_text(&mut TextParser, &'static str) -> Result<T, DecodeError>
+                //         is_default(&T) -> bool
+                //   reflect: to_value(&T) -> Value<'_>
+                //            from_value(Value, &'static str) -> Result<T, ReflectError>
+                // Values for which is_default is true aren't written, like scalars at their default.
+                NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("with") => {
+                    match &nv.lit {
+                        Lit::Str(ls) => result.with = Some(ls.parse::<syn::Path>()?),
+                        lit => return Err(syn::Error::new_spanned(lit,
+                            "with must specify a module path as a string, e.g. `with = \"my::module\"`")),
+                    }
+                }
+
+                // the member name in proto3 JSON, e.g. #[twpb(uint32, nr=1, json_name = "id")]
+                NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("json_name") => {
+                    match &nv.lit {
+                        Lit::Str(ls) => result.json_name = Some(ls.clone()),
+                        lit => return Err(syn::Error::new_spanned(lit,
+                            "json_name must be a string, e.g. `json_name = \"id\"`")),
+                    }
+                }
+
+                // the name in the proto file, e.g. #[twpb(string, nr=1, name = "serial_number")]
+                NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("name") => {
+                    match &nv.lit {
+                        Lit::Str(ls) if is_identifier(&ls.value()) => result.name = Some(ls.clone()),
+                        lit => return Err(syn::Error::new_spanned(lit,
+                            "name must be a proto field name as a string, e.g. `name = \"serial_number\"`")),
+                    }
+                }
+
+                // a custom ScalarCodec marker type, e.g. #[twpb(codec = "my::Codec", nr=1)]
+                NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("codec") => {
+                    match &nv.lit {
+                        Lit::Str(ls) => {
+                            result.codec = Some(ls.parse::<syn::Path>()?);
+                            result.proto_type = "codec".to_owned();
+                        },
+                        lit => return Err(syn::Error::new_spanned(lit,
+                            "codec must specify a path as a string, e.g. `codec = \"my::Codec\"`")),
                     }
                 }
 
                 // parse the field type
-                NestedMeta::Meta(Meta::Path(ref p)) => {
-                    if let Some(ident) = p.get_ident() {
-                        let s = ident.to_string();
-                        match s.as_ref() {
-                            // someone likes numbers...
-                            "int32" | "int64" |
-                            "uint32" | "uint64" |
-                            "sint32" | "sint64" |
-                            "fixed32" | "fixed64" |
-                            "sfixed32" | "sfixed64" |
-                            "double" | "float" |
-                            // non-numbers whatever
-                            "bool" | "string" | "bytes" | "oneof" |
-                            // special case, embedded messages
-                            "message" => result.proto_type = s.to_owned(),
-                            _ => panic!("unknown field type '{}'", s),
-                        }
+                NestedMeta::Meta(Meta::Path(ref p)) if p.get_ident().is_some() => {
+                    let s = p.get_ident().unwrap().to_string();
+                    match s.as_ref() {
+                        // someone likes numbers...
+                        "int32" | "int64" |
+                        "uint32" | "uint64" |
+                        "sint32" | "sint64" |
+                        "fixed32" | "fixed64" |
+                        "sfixed32" | "sfixed64" |
+                        "double" | "float" |
+                        // non-numbers whatever
+                        "bool" | "string" | "bytes" | "enum" | "oneof" |
+                        // special case, embedded messages
+                        "message" |
+                        // a message whose fields are inlined, e.g. #[twpb(flatten)] header: Header
+                        "flatten" => {
+                            result.codec = codecs::for_proto_type(&s);
+                            result.proto_type = s.to_owned();
+                        },
+                        "repeated" => result.repeated = Some(p.clone()),
+                        "skip" => result.skip = Some(p.clone()),
+                        _ => return Err(syn::Error::new_spanned(p,
+                            format!("unknown field type `{}`", s))),
                     }
                 }
 
-                _ => panic!("invalid attribute: {:?}", twpb_attr),
+                other => return Err(syn::Error::new_spanned(other, "invalid twpb attribute")),
             }
         }
 
+        if let Some(skip) = &result.skip {
+            // a skipped field has nothing to do with protobuf
+            if !result.proto_type.is_empty() || result.nr_span.is_some() || result.with.is_some()
+                || result.repeated.is_some() || result.json_name.is_some() || result.name.is_some() {
+                return Err(syn::Error::new_spanned(skip, "skip can not be combined with other options"));
+            }
+            return Ok(result);
+        }
+
+        if result.proto_type.is_empty() {
+            return Err(syn::Error::new_spanned(twpb_attr,
+                "missing field type, e.g. `#[twpb(uint32, nr=1)]`"));
+        }
+        // their fields are members of the JSON object themselves
+        if let (Some(json_name), "oneof" | "flatten") = (&result.json_name, result.proto_type.as_str()) {
+            return Err(syn::Error::new_spanned(json_name,
+                format!("json_name can not be used on a {}", result.proto_type)));
+        }
+        // its fields keep their own names
+        if let (Some(name), "flatten") = (&result.name, result.proto_type.as_str()) {
+            return Err(syn::Error::new_spanned(name, "name can not be used on a flatten"));
+        }
         Ok(result)
     }
+
+    fn check_nr(&self) -> syn::parse::Result<()> {
+        // a oneof gets its field numbers from its enum, a flattened message from its fields
+        if self.proto_type == "flatten" {
+            if let Some(span) = self.nr_span {
+                return Err(syn::Error::new(span,
+                    "a flattened message uses the field numbers of its own fields, it can't have an nr"));
+            }
+            if self.repeated.is_some() || self.with.is_some() {
+                return Err(syn::Error::new(self.span,
+                    "flatten can not be combined with other options"));
+            }
+        }
+        if self.nr_span.is_none() && self.proto_type != "oneof" && self.proto_type != "flatten" && self.skip.is_none() {
+            return Err(syn::Error::new_spanned(&self.attr,
+                "missing field number, e.g. `#[twpb(uint32, nr=1)]`"));
+        }
+        Ok(())
+    }
 }
 
-impl ParsedField {
-    pub fn parse(field: syn::Field) -> syn::parse::Result<Self> {
+// Parses field number strings like "1", "1-3" or "1-3;5".
+fn parse_field_numbers(ls: &syn::LitStr) -> syn::parse::Result<Vec<u32>> {
+    let mut numbers = vec![];
+    for s in ls.value().split(';') {
+        if let Some(div) = s.find('-') {
+            let left = s[..div].parse::<u32>();
+            let right = s[div+1..].parse::<u32>();
+            match (left, right) {
+                (Ok(left), Ok(right)) if left <= right => numbers.extend(left..=right),
+                (Ok(_), Ok(_)) => return Err(syn::Error::new_spanned(ls,
+                    format!("nr range '{}' is empty", s))),
+                _ => return Err(syn::Error::new_spanned(ls,
+                    format!("nr range must be formatted as `^[0-9]+-[0-9]+$`, got '{}'", s))),
+            }
+        } else {
+            match s.parse::<u32>() {
+                Ok(n) => numbers.push(n),
+                Err(_) => return Err(syn::Error::new_spanned(ls,
+                    format!("invalid number, got '{}'", s))),
+            }
+        }
+    }
+    Ok(numbers)
+}
 
-        let mut result = ParsedField{
-            field_name: field.ident.expect("Field has no name"),
-            field_numbers: vec![0],
-            proto_type: "".to_owned(),
-            field_type: field.ty,
-            repeated: false,
-        };
+impl ParsedVariant {
+    pub fn parse(field: syn::Variant) -> syn::parse::Result<Self> {
+        let attr = ParsedAttr::parse(field.attrs, &field.ident)?;
+        attr.check_nr()?;
 
+        let field_type = match &field.fields {
+            syn::Fields::Unnamed(syn::FieldsUnnamed{unnamed: fields, ..}) if fields.len() == 1 => fields[0].ty.clone(),
+            // e.g. #[twpb(message, nr=1)] GetInfo, for parameterless requests
+            syn::Fields::Unit if attr.proto_type == "message" => syn::parse_quote!(()),
+            syn::Fields::Unit => return Err(syn::Error::new_spanned(&field.ident,
+                "only message variants can be unit variants, others must hold exactly one value, e.g. `Name(Type)`")),
+            fields => return Err(syn::Error::new_spanned(fields,
+                "oneof variants must hold exactly one value, e.g. `Name(Type)`")),
+        };
+        let unit = matches!(field.fields, syn::Fields::Unit);
+        if let Some(repeated) = attr.repeated {
+            return Err(syn::Error::new_spanned(repeated, "oneof variants can not be repeated"));
+        }
+        if let Some(skip) = attr.skip {
+            return Err(syn::Error::new_spanned(skip, "oneof variants can not be skipped"));
+        }
+        if attr.proto_type == "flatten" {
+            return Err(syn::Error::new(attr.span, "oneof variants can not be flattened"));
+        }
+        if let (Some(name), "oneof") = (&attr.name, attr.proto_type.as_str()) {
+            return Err(syn::Error::new_spanned(name, "a nested oneof has no name, its variants are variants of this oneof"));
+        }
+        check_with(&attr)?;
 
-        let twpb_attr: Vec<_> = field.attrs
-            .into_iter()
-            .filter(|a| a.path.is_ident("twpb"))
-            .collect();
+        // variants are CamelCase, the proto field is snake_case
+        let proto_name = attr.name.as_ref().map(syn::LitStr::value).unwrap_or_else(|| snake_case(&unraw(&field.ident)));
+        let json_name = attr.json_name.map(|n| n.value()).unwrap_or_else(|| json_name(&proto_name));
+        Ok(ParsedVariant{
+            field_name: field.ident,
+            field_numbers: attr.field_numbers,
+            nr_span: attr.nr_span.unwrap_or(attr.span),
+            field_type,
+            proto_type: attr.proto_type,
+            codec: attr.codec,
+            with: attr.with,
+            unit,
+            proto_name,
+            json_name,
+            attr_span: attr.span,
+        })
+    }
+}
 
-        let twpb_attr = match twpb_attr.len() {
-            1 => &twpb_attr[0],
-            0 => panic!("All fields of a message must specify a #[twpb] attribute, missing for field '{:?}' of type '{:}'", result.field_name, result.field_type.to_token_stream()),
-            n => panic!("A field can specify a #[twpb] attribute only once. Field '{:?}' specified it {} times.", result.field_name, n),
-        };
+// Options of the #[twpb(..)] attribute on the message struct itself.
+#[derive(Debug, Default)]
+pub struct ParsedContainer {
+    // #[twpb(transparent)]: a newtype encoded as its inner scalar, not as a message
+    pub transparent: Option<Span>,
+    // #[twpb(enum)]: an enum of unit variants is a proto enum, not a oneof
+    pub enumeration: Option<Span>,
+    // #[twpb(after_decode = "path")]: fn(&mut Self) -> Result<(), DecodeError>,
+    // called on every decoded message, e.g. to fill skipped fields or validate it
+    pub after_decode: Option<syn::Path>,
+    // #[twpb(json)]: also implement the proto3 JSON mapping
+    pub json: bool,
+    // #[twpb(text)]: also implement the protobuf text format
+    pub text: bool,
+    // #[twpb(reflect)]: also implement access to fields by number
+    pub reflect: bool,
+    // #[twpb(package = "api.v1")]: the proto package of the message, part of its type URL
+    pub package: Option<syn::LitStr>,
+    // #[twpb(name = "Request.EmptyRequest")]: the name in the proto file, when it isn't that of the Rust type,
+    // dotted for a message nested in another one
+    pub name: Option<syn::LitStr>,
+}
 
-        // start parsing the ?? part of #[twpb(??)]
-        let metas = match twpb_attr.parse_meta()? {
-            Meta::List(l) => l.nested,
-            // One can also write other attributes, like '#[twpb = value]'.
-            // We don't do that here.
-            _ => panic!("twpb attribute can only be of the form '#[twpb(..)]': {:?}", twpb_attr),
-        };
-        
-        for meta in metas {
-            match meta {
-                // parse the field number
-                NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("nr") => {
-                    if let Lit::Int(li) = &nv.lit {
-                        result.field_numbers = vec![li.base10_parse::<u32>()?];
-                    } else if let Lit::Str(ls) = &nv.lit {
-                        let numbers: Vec<u32> = ls.value().split(";")
-                        .map(|s| {
-                            if let Some(div) = s.find('-') {
-                                let left = &s[..div];
-                                let right = &s[div+1..];
-                                let left = left.parse::<u32>().unwrap_or_else(|_| panic!("nr range must be formatted as `^[0-9]+-[0-9]+$`, got '{}'", s));
-                                let right = right.parse::<u32>().unwrap_or_else(|_| panic!("nr range must be formatted as `^[0-9]+-[0-9]+$`, got '{}'", s));
-                                return (left..=right).collect::<Vec<_>>();
-                            } else {
-                                let s = s.parse::<u32>().unwrap_or_else(|_| panic!("invalid number, got '{}'", s));
-                                return vec![s];
-                            }
-                        })
-                        .flatten().collect();
-                        result.field_numbers = numbers;
-                    } else {
-                        panic!("nr must specify a number");
+impl ParsedContainer {
+    pub fn parse(attrs: &[syn::Attribute]) -> syn::parse::Result<Self> {
+        let mut result = ParsedContainer::default();
+        for attr in attrs.iter().filter(|a| a.path.is_ident("twpb")) {
+            let metas = match attr.parse_meta()? {
+                Meta::List(l) => l.nested,
+                other => return Err(syn::Error::new_spanned(other,
+                    "twpb attribute can only be of the form `#[twpb(..)]`")),
+            };
+            for meta in metas {
+                match meta {
+                    NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("transparent") => {
+                        result.transparent = Some(p.span());
                     }
-                }
-
-                // parse the field type
-                NestedMeta::Meta(Meta::Path(ref p)) => {
-                    if let Some(ident) = p.get_ident() {
-                        let s = ident.to_string();
-                        match s.as_ref() {
-                            // someone likes numbers...
-                            "int32" | "int64" |
-                            "uint32" | "uint64" |
-                            "sint32" | "sint64" |
-                            "fixed32" | "fixed64" |
-                            "sfixed32" | "sfixed64" |
-                            "double" | "float" |
-                            // non-numbers whatever
-                            "bool" | "string" | "bytes" | "oneof" => result.proto_type = s.to_owned(),
-                            "repeated" => result.repeated = true,
-                            _ => panic!("unknown field type '{}'", s),
+                    NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("enum") => {
+                        result.enumeration = Some(p.span());
+                    }
+                    NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("json") => {
+                        result.json = true;
+                    }
+                    NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("text") => {
+                        result.text = true;
+                    }
+                    NestedMeta::Meta(Meta::Path(ref p)) if p.is_ident("reflect") => {
+                        result.reflect = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("after_decode") => {
+                        match &nv.lit {
+                            Lit::Str(ls) => result.after_decode = Some(ls.parse::<syn::Path>()?),
+                            lit => return Err(syn::Error::new_spanned(lit,
+                                "after_decode must specify a function path as a string, e.g. `after_decode = \"validate\"`")),
                         }
                     }
+                    NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("package") => {
+                        match &nv.lit {
+                            Lit::Str(ls) if is_package(&ls.value()) => result.package = Some(ls.clone()),
+                            lit => return Err(syn::Error::new_spanned(lit,
+                                "package must be a proto package name as a string, e.g. `package = \"api.v1\"`")),
+                        }
+                    }
+                    NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("name") => {
+                        match &nv.lit {
+                            Lit::Str(ls) if is_package(&ls.value()) => result.name = Some(ls.clone()),
+                            lit => return Err(syn::Error::new_spanned(lit,
+                                "name must be a proto message name as a string, e.g. `name = \"Request.EmptyRequest\"`")),
+                        }
+                    }
+                    other => return Err(syn::Error::new_spanned(other, "invalid twpb attribute")),
                 }
+            }
+        }
+        Ok(result)
+    }
+
+    // The name in the proto file, that of the Rust type unless overridden.
+    pub fn proto_name(&self, ident: &syn::Ident) -> String {
+        self.name.as_ref().map(syn::LitStr::value).unwrap_or_else(|| unraw(ident))
+    }
+
+    // The proto package, empty without one.
+    pub fn package(&self) -> String {
+        self.package.as_ref().map(syn::LitStr::value).unwrap_or_default()
+    }
+}
+
+// Identifiers separated by dots.
+fn is_package(package: &str) -> bool {
+    package.split('.').all(|part| {
+        let mut chars = part.chars();
+        matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.contains('.') && is_package(name)
+}
+
+// `with` replaces the codec of a scalar type, it can't be combined with other kinds of fields.
+fn check_with(attr: &ParsedAttr) -> syn::parse::Result<()> {
+    if attr.with.is_some() && (attr.proto_type == "message" || codecs::wire_type_for(&attr.proto_type).is_none()) {
+        return Err(syn::Error::new(attr.span,
+            format!("with can not be used on proto type '{}'", attr.proto_type)));
+    }
+    Ok(())
+}
+
+impl ParsedField {
+    // `index` is the position of the field in the struct, for tuple structs.
+    pub fn parse(field: syn::Field, index: usize) -> syn::parse::Result<Self> {
+        Self::parse_any(field, index, false)
+    }
+
+    // The inner field of a #[twpb(transparent)] newtype, it has no field number.
+    pub fn parse_transparent(field: syn::Field, index: usize) -> syn::parse::Result<Self> {
+        let field = Self::parse_any(field, index, true)?;
+        if !field.field_numbers.is_empty() {
+            return Err(syn::Error::new(field.nr_span,
+                "the field of a transparent newtype takes the field number of wherever the newtype is used"));
+        }
+        if field.repeated || field.skip || field.proto_type == "message" || field.proto_type == "oneof" || field.proto_type == "flatten" {
+            return Err(syn::Error::new(field.attr_span,
+                "the field of a transparent newtype must be a single scalar"));
+        }
+        Ok(field)
+    }
+
+    fn parse_any(field: syn::Field, index: usize, transparent: bool) -> syn::parse::Result<Self> {
+        let field_name = match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(syn::Index{index: index as u32, span: field.ty.span()}),
+        };
+
+        let attr = ParsedAttr::parse(field.attrs, &field_name)?;
+        if !transparent {
+            attr.check_nr()?;
+        }
+        check_with(&attr)?;
+
+        let proto_name = match (&attr.name, &field_name) {
+            (Some(name), _) => name.value(),
+            (None, syn::Member::Named(ident)) => unraw(ident),
+            (None, syn::Member::Unnamed(index)) => index.index.to_string(),
+        };
+        let json_name = attr.json_name.map(|n| n.value()).unwrap_or_else(|| json_name(&proto_name));
+        Ok(ParsedField{
+            field_name,
+            field_numbers: attr.field_numbers,
+            nr_span: attr.nr_span.unwrap_or(attr.span),
+            field_type: field.ty,
+            proto_type: attr.proto_type,
+            codec: attr.codec,
+            with: attr.with,
+            repeated: attr.repeated.is_some(),
+            skip: attr.skip.is_some(),
+            proto_name,
+            json_name,
+            attr_span: attr.span,
+        })
+    }
+}
 
-                _ => panic!("invalid attribute: {:?}", twpb_attr),
+// Largest field number that fits in a tag, 2^29 - 1.
+const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+// Reserved for the protobuf implementation.
+const RESERVED_FIELD_NUMBERS: core::ops::RangeInclusive<u32> = 19000..=19999;
+
+// Checks the field numbers of all fields of a message, or all variants of a oneof.
+// Each entry is the field numbers of one field, and where they were specified.
+pub fn check_field_numbers<'a>(fields: impl IntoIterator<Item = (&'a [u32], Span)>) -> syn::parse::Result<()> {
+    let mut seen: Vec<u32> = vec![];
+    for (numbers, span) in fields {
+        for &n in numbers {
+            if n == 0 {
+                return Err(syn::Error::new(span, "field number 0 is not allowed"));
+            }
+            if n > MAX_FIELD_NUMBER {
+                return Err(syn::Error::new(span,
+                    format!("field number {} is larger than the maximum of {}", n, MAX_FIELD_NUMBER)));
             }
+            if RESERVED_FIELD_NUMBERS.contains(&n) {
+                return Err(syn::Error::new(span,
+                    format!("field number {} is in the reserved range 19000-19999", n)));
+            }
+            if seen.contains(&n) {
+                return Err(syn::Error::new(span, format!("field number {} is used more than once", n)));
+            }
+            seen.push(n);
         }
+    }
+    Ok(())
+}
 
-        Ok(result)
+// Get T out of an Option<T> type.
+pub fn option_inner(field_type: &syn::Type) -> Option<&syn::Type> {
+    let segment = match field_type {
+        syn::Type::Path(syn::TypePath{path: syn::Path{segments, ..}, ..}) => segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        // There can be only one
+        syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments{args, ..}) => match args.first()? {
+            syn::GenericArgument::Type(t) => Some(t),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Whether the type is some kind of Box (alloc::boxed::Box, heapless::pool::Box, ...).
+pub fn is_box(field_type: &syn::Type) -> bool {
+    match field_type {
+        syn::Type::Path(syn::TypePath{path: syn::Path{segments, ..}, ..}) =>
+            segments.last().map(|s| s.ident == "Box").unwrap_or(false),
+        _ => false,
     }
-}
\ No newline at end of file
+}
+
+// The identifier without r# prefix.
+fn unraw(ident: &syn::Ident) -> String {
+    let name = ident.to_string();
+    name.strip_prefix("r#").map(str::to_owned).unwrap_or(name)
+}
+
+// GetInfo -> get_info
+pub fn snake_case(name: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                result.push('_');
+            }
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+// The default proto3 JSON name of a field: firmware_version -> firmwareVersion
+fn json_name(proto_name: &str) -> String {
+    let mut result = String::new();
+    let mut upper = false;
+    for c in proto_name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            result.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}